@@ -0,0 +1,1052 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ArgEnum;
+use serde::{Serialize, Serializer};
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::chunk_type::ChunkType;
+use crate::icc::{self, IccHeader};
+use crate::limits::{self, Limits};
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Standard ancillary chunk types with a typed decoder and serializer.
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Ancillary-chunks
+pub const ANCILLARY_CHUNK_TYPES: [&str; 11] = [
+    "gAMA", "cHRM", "sRGB", "iCCP", "sBIT", "bKGD", "hIST", "pHYs", "sPLT", "tIME",
+    "tRNS",
+];
+
+/// Chunk types that must not both be present in a png.
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.iCCP
+const EXCLUSIVE_CHUNK_TYPES: [(&str, &str); 2] =
+    [("sRGB", "iCCP"), ("iCCP", "sRGB")];
+
+/// Inches per meter, pHYs stores pixels per meter.
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// gAMA and cHRM store their values times 100000.
+const SCALE: f64 = 100_000.0;
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+/// Splits data at the null byte ending a 1-79 byte name.
+fn split_name<'a>(
+    data: &'a [u8],
+    error: &'static str,
+) -> Result<(String, &'a [u8])> {
+    match data.iter().position(|&b| b == 0) {
+        Some(separator @ 1..=79) => {
+            Ok((latin1(&data[..separator]), &data[separator + 1..]))
+        }
+        _ => Err(Box::new(ChunkError(error))),
+    }
+}
+
+/// Length of a byte field in place of its contents.
+fn serialize_len<S: Serializer>(
+    bytes: &[u8],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_u64(bytes.len() as u64)
+}
+
+/// gAMA: image gamma times 100000.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Gamma {
+    pub gamma: u32,
+}
+
+impl Gamma {
+    /// Rejects values that don't round to a positive gAMA.
+    pub fn from_value(value: f64) -> Result<Gamma> {
+        let gamma = (value * SCALE).round();
+
+        if !(1.0..=u32::MAX as f64).contains(&gamma) {
+            return Err(Box::new(ChunkError("Gamma must be positive")));
+        }
+
+        Ok(Gamma {
+            gamma: gamma as u32,
+        })
+    }
+
+    pub fn value(&self) -> f64 {
+        self.gamma as f64 / SCALE
+    }
+}
+
+/// cHRM: CIE x,y chromaticities of the white point and primaries
+/// times 100000.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Chromaticities {
+    pub white_x: u32,
+    pub white_y: u32,
+    pub red_x: u32,
+    pub red_y: u32,
+    pub green_x: u32,
+    pub green_y: u32,
+    pub blue_x: u32,
+    pub blue_y: u32,
+}
+
+impl Chromaticities {
+    /// From the white point and primaries as x,y pairs, in the order
+    /// white, red, green, blue.
+    pub fn from_values(values: &[f64]) -> Result<Chromaticities> {
+        let scaled = values
+            .iter()
+            .map(|value| match value {
+                0.0..=1.0 => Ok((value * SCALE).round() as u32),
+                _ => Err(ChunkError("Chromaticities must be between 0 and 1")),
+            })
+            .collect::<std::result::Result<Vec<u32>, ChunkError>>()?;
+
+        let [white_x, white_y, red_x, red_y, green_x, green_y, blue_x, blue_y] =
+            scaled[..]
+        else {
+            return Err(Box::new(ChunkError("cHRM needs 8 values")));
+        };
+
+        Ok(Chromaticities {
+            white_x,
+            white_y,
+            red_x,
+            red_y,
+            green_x,
+            green_y,
+            blue_x,
+            blue_y,
+        })
+    }
+
+    fn values(&self) -> [u32; 8] {
+        [
+            self.white_x,
+            self.white_y,
+            self.red_x,
+            self.red_y,
+            self.green_x,
+            self.green_y,
+            self.blue_x,
+            self.blue_y,
+        ]
+    }
+}
+
+/// sRGB rendering intent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ArgEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl TryFrom<u8> for RenderingIntent {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(Box::new(ChunkError("Invalid sRGB rendering intent"))),
+        }
+    }
+}
+
+impl fmt::Display for RenderingIntent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let intent = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+
+        write!(f, "{}", intent)
+    }
+}
+
+/// sRGB: the image is in the sRGB color space.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Srgb {
+    pub rendering_intent: RenderingIntent,
+}
+
+/// iCCP: an embedded ICC profile, kept compressed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IccProfile {
+    pub name: String,
+    pub compression_method: u8,
+    #[serde(rename = "compressed_size", serialize_with = "serialize_len")]
+    pub compressed_profile: Vec<u8>,
+    /// None if the profile does not inflate to a valid ICC header
+    pub header: Option<IccHeader>,
+}
+
+impl IccProfile {
+    /// Compresses profile into an iCCP chunk called name.
+    pub fn new(name: &str, profile: &[u8]) -> Result<IccProfile> {
+        icc::validate_name(name)?;
+        let header = IccHeader::try_from(profile)?;
+
+        Ok(IccProfile {
+            name: name.to_string(),
+            compression_method: 0,
+            compressed_profile: icc::compress(profile)?,
+            header: Some(header),
+        })
+    }
+
+    /// The decompressed profile, failing beyond the max_image_data limit.
+    pub fn profile(&self, limits: &Limits) -> Result<Vec<u8>> {
+        if self.compression_method != 0 {
+            return Err(Box::new(ChunkError("Unknown iCCP compression method")));
+        }
+
+        limits::inflate(&self.compressed_profile, limits.max_image_data)
+    }
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().to_string() != "iCCP" {
+            return Err(Box::new(ChunkError("Not an iCCP chunk")));
+        }
+
+        let (name, rest) =
+            split_name(chunk.data(), "iCCP profile name must be 1-79 bytes")?;
+        let (&compression_method, profile) = rest
+            .split_first()
+            .ok_or(ChunkError("iCCP is missing the compression method"))?;
+
+        let header = match compression_method {
+            0 => icc::compressed_header(profile).ok(),
+            _ => None,
+        };
+
+        Ok(IccProfile {
+            name,
+            compression_method,
+            compressed_profile: profile.to_vec(),
+            header,
+        })
+    }
+}
+
+/// sBIT: significant bits of every channel of the original image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SignificantBits {
+    pub bits: Vec<u8>,
+}
+
+/// A single color, its layout depends on the color type of the image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Color {
+    Gray { gray: u16 },
+    Rgb { red: u16, green: u16, blue: u16 },
+    PaletteIndex { index: u8 },
+}
+
+impl Color {
+    fn to_data(&self) -> Vec<u8> {
+        match self {
+            Color::Gray { gray } => gray.to_be_bytes().to_vec(),
+            Color::Rgb { red, green, blue } => [red, green, blue]
+                .iter()
+                .flat_map(|sample| sample.to_be_bytes())
+                .collect(),
+            Color::PaletteIndex { index } => vec![*index],
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Gray { gray } => write!(f, "gray {}", gray),
+            Color::Rgb { red, green, blue } => {
+                write!(f, "rgb({}, {}, {})", red, green, blue)
+            }
+            Color::PaletteIndex { index } => write!(f, "palette index {}", index),
+        }
+    }
+}
+
+/// hIST: approximate usage frequency of every palette entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    pub frequencies: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PhysicalUnit {
+    /// only the aspect ratio is known
+    Unknown,
+    Meter,
+}
+
+/// pHYs: intended pixel size or aspect ratio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: PhysicalUnit,
+}
+
+impl PhysicalDimensions {
+    /// Square pixels at dpi dots per inch.
+    pub fn from_dpi(dpi: f64) -> Result<PhysicalDimensions> {
+        let pixels_per_meter = (dpi / METERS_PER_INCH).round();
+
+        if !(1.0..=u32::MAX as f64).contains(&pixels_per_meter) {
+            return Err(Box::new(ChunkError("DPI must be positive")));
+        }
+
+        Ok(PhysicalDimensions {
+            pixels_per_unit_x: pixels_per_meter as u32,
+            pixels_per_unit_y: pixels_per_meter as u32,
+            unit: PhysicalUnit::Meter,
+        })
+    }
+
+    /// Horizontal and vertical dots per inch, None without a unit.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysicalUnit::Meter => Some((
+                self.pixels_per_unit_x as f64 * METERS_PER_INCH,
+                self.pixels_per_unit_y as f64 * METERS_PER_INCH,
+            )),
+            PhysicalUnit::Unknown => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuggestedColor {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+/// sPLT: a named palette suggested for displays with fewer colors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuggestedPalette {
+    pub name: String,
+    pub sample_depth: u8,
+    pub entries: Vec<SuggestedColor>,
+}
+
+/// tIME: time of the last modification, in UTC.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    /// The current UTC time.
+    pub fn now() -> Time {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+
+        // civil date from days since 1970-01-01, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = (seconds / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+            - day_of_era / 146_096)
+            / 365;
+        let day_of_year =
+            day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        let time_of_day = seconds % 86_400;
+
+        Time {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time_of_day / 3600) as u8,
+            minute: (time_of_day / 60 % 60) as u8,
+            second: (time_of_day % 60) as u8,
+        }
+    }
+
+    fn validate(self) -> std::result::Result<Time, ChunkError> {
+        let valid = (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour <= 23
+            && self.minute <= 59
+            // 60 allows for leap seconds
+            && self.second <= 60;
+
+        if !valid {
+            return Err(ChunkError("Invalid tIME date or time"));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Parses "now" or a UTC timestamp like 2024-05-17T13:45:00Z,
+/// the trailing Z is optional.
+impl FromStr for Time {
+    type Err = ChunkError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, ChunkError> {
+        if s == "now" {
+            return Ok(Time::now());
+        }
+
+        let invalid = || ChunkError("Time must be \"now\" or YYYY-MM-DDTHH:MM:SS");
+        let s = s.strip_suffix('Z').unwrap_or(s);
+        let (date, time) = s.split_once('T').ok_or_else(invalid)?;
+
+        let fields = date
+            .split('-')
+            .chain(time.split(':'))
+            .map(|field| field.parse::<u16>().map_err(|_| invalid()))
+            .collect::<std::result::Result<Vec<u16>, _>>()?;
+
+        let [year, month, day, hour, minute, second] = fields[..] else {
+            return Err(invalid());
+        };
+
+        Time {
+            year,
+            month: u8::try_from(month).map_err(|_| invalid())?,
+            day: u8::try_from(day).map_err(|_| invalid())?,
+            hour: u8::try_from(hour).map_err(|_| invalid())?,
+            minute: u8::try_from(minute).map_err(|_| invalid())?,
+            second: u8::try_from(second).map_err(|_| invalid())?,
+        }
+        .validate()
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// tRNS: the transparent color, or alpha values of palette entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Transparency {
+    Color(Color),
+    Palette { alphas: Vec<u8> },
+}
+
+/// Parsed contents of a standard ancillary chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Ancillary {
+    Gamma(Gamma),
+    Chromaticities(Chromaticities),
+    Srgb(Srgb),
+    IccProfile(IccProfile),
+    SignificantBits(SignificantBits),
+    /// bKGD: the preferred background color
+    Background(Color),
+    Histogram(Histogram),
+    PhysicalDimensions(PhysicalDimensions),
+    SuggestedPalette(SuggestedPalette),
+    Time(Time),
+    Transparency(Transparency),
+}
+
+/// Color of a bKGD or tRNS chunk.
+///
+/// The layout depends on the color type of the image, without one
+/// it is guessed from the length.
+fn decode_color(
+    data: &[u8],
+    color_type: Option<u8>,
+    error: &'static str,
+) -> Result<Color> {
+    match (color_type, data.len()) {
+        (Some(0 | 4) | None, 2) => Ok(Color::Gray {
+            gray: read_u16(data),
+        }),
+        (Some(2 | 6) | None, 6) => Ok(Color::Rgb {
+            red: read_u16(&data[0..2]),
+            green: read_u16(&data[2..4]),
+            blue: read_u16(&data[4..6]),
+        }),
+        (Some(3) | None, 1) => Ok(Color::PaletteIndex { index: data[0] }),
+        _ => Err(Box::new(ChunkError(error))),
+    }
+}
+
+fn decode_palette(data: &[u8]) -> Result<SuggestedPalette> {
+    let (name, rest) = split_name(data, "sPLT palette name must be 1-79 bytes")?;
+
+    let (&sample_depth, entries) = rest
+        .split_first()
+        .ok_or(ChunkError("sPLT is missing the sample depth"))?;
+
+    let entry_length = match sample_depth {
+        8 => 6,
+        16 => 10,
+        _ => return Err(Box::new(ChunkError("sPLT sample depth must be 8 or 16"))),
+    };
+
+    if !entries.len().is_multiple_of(entry_length) {
+        return Err(Box::new(ChunkError("sPLT has a partial palette entry")));
+    }
+
+    let entries = entries
+        .chunks(entry_length)
+        .map(|entry| {
+            let sample = |idx: usize| match sample_depth {
+                8 => entry[idx] as u16,
+                _ => read_u16(&entry[idx * 2..]),
+            };
+            let samples = entry_length - 2;
+
+            SuggestedColor {
+                red: sample(0),
+                green: sample(1),
+                blue: sample(2),
+                alpha: sample(3),
+                frequency: read_u16(&entry[samples..]),
+            }
+        })
+        .collect();
+
+    Ok(SuggestedPalette {
+        name,
+        sample_depth,
+        entries,
+    })
+}
+
+impl Ancillary {
+    /// Decodes a standard ancillary chunk.
+    ///
+    /// color_type is the one of the image's IHDR, it decides the layout
+    /// of sBIT, bKGD and tRNS.
+    pub fn decode(chunk: &Chunk, color_type: Option<u8>) -> Result<Ancillary> {
+        let data = chunk.data();
+
+        let decoded = match chunk.chunk_type().to_string().as_str() {
+            "gAMA" if data.len() == 4 => Ancillary::Gamma(Gamma {
+                gamma: read_u32(data),
+            }),
+            "gAMA" => return Err(Box::new(ChunkError("gAMA must be 4 bytes"))),
+            "cHRM" if data.len() == 32 => {
+                let value = |idx: usize| read_u32(&data[idx * 4..]);
+
+                Ancillary::Chromaticities(Chromaticities {
+                    white_x: value(0),
+                    white_y: value(1),
+                    red_x: value(2),
+                    red_y: value(3),
+                    green_x: value(4),
+                    green_y: value(5),
+                    blue_x: value(6),
+                    blue_y: value(7),
+                })
+            }
+            "cHRM" => return Err(Box::new(ChunkError("cHRM must be 32 bytes"))),
+            "sRGB" if data.len() == 1 => Ancillary::Srgb(Srgb {
+                rendering_intent: RenderingIntent::try_from(data[0])?,
+            }),
+            "sRGB" => return Err(Box::new(ChunkError("sRGB must be 1 byte"))),
+            "iCCP" => Ancillary::IccProfile(IccProfile::try_from(chunk)?),
+            "sBIT" => {
+                // grayscale, rgb, palette (stored as rgb) and the alpha variants
+                let expected = match color_type {
+                    Some(0) => 1..=1,
+                    Some(2 | 3) => 3..=3,
+                    Some(4) => 2..=2,
+                    Some(6) => 4..=4,
+                    _ => 1..=4,
+                };
+
+                if !expected.contains(&data.len()) {
+                    return Err(Box::new(ChunkError(
+                        "sBIT length does not match the color type",
+                    )));
+                }
+
+                Ancillary::SignificantBits(SignificantBits {
+                    bits: data.to_vec(),
+                })
+            }
+            "bKGD" => Ancillary::Background(decode_color(
+                data,
+                color_type,
+                "bKGD length does not match the color type",
+            )?),
+            "hIST" if data.len().is_multiple_of(2) => {
+                Ancillary::Histogram(Histogram {
+                    frequencies: data.chunks(2).map(read_u16).collect(),
+                })
+            }
+            "hIST" => return Err(Box::new(ChunkError("hIST has an odd length"))),
+            "pHYs" if data.len() == 9 => {
+                Ancillary::PhysicalDimensions(PhysicalDimensions {
+                    pixels_per_unit_x: read_u32(&data[0..4]),
+                    pixels_per_unit_y: read_u32(&data[4..8]),
+                    unit: match data[8] {
+                        0 => PhysicalUnit::Unknown,
+                        1 => PhysicalUnit::Meter,
+                        _ => return Err(Box::new(ChunkError("Invalid pHYs unit"))),
+                    },
+                })
+            }
+            "pHYs" => return Err(Box::new(ChunkError("pHYs must be 9 bytes"))),
+            "sPLT" => Ancillary::SuggestedPalette(decode_palette(data)?),
+            "tIME" if data.len() == 7 => Ancillary::Time(
+                Time {
+                    year: read_u16(data),
+                    month: data[2],
+                    day: data[3],
+                    hour: data[4],
+                    minute: data[5],
+                    second: data[6],
+                }
+                .validate()?,
+            ),
+            "tIME" => return Err(Box::new(ChunkError("tIME must be 7 bytes"))),
+            "tRNS" => {
+                // palette alphas can have any length up to the palette size
+                let transparency = match (color_type, data.len()) {
+                    (Some(4 | 6), _) => {
+                        return Err(Box::new(ChunkError(
+                            "tRNS is not allowed with an alpha channel",
+                        )))
+                    }
+                    (Some(3), _) | (None, 1 | 3..=5 | 7..) => {
+                        Transparency::Palette {
+                            alphas: data.to_vec(),
+                        }
+                    }
+                    _ => Transparency::Color(decode_color(
+                        data,
+                        color_type,
+                        "tRNS length does not match the color type",
+                    )?),
+                };
+
+                Ancillary::Transparency(transparency)
+            }
+            _ => {
+                return Err(Box::new(ChunkError(
+                    "Not a standard ancillary chunk type",
+                )))
+            }
+        };
+
+        Ok(decoded)
+    }
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            Ancillary::Gamma(_) => "gAMA",
+            Ancillary::Chromaticities(_) => "cHRM",
+            Ancillary::Srgb(_) => "sRGB",
+            Ancillary::IccProfile(_) => "iCCP",
+            Ancillary::SignificantBits(_) => "sBIT",
+            Ancillary::Background(_) => "bKGD",
+            Ancillary::Histogram(_) => "hIST",
+            Ancillary::PhysicalDimensions(_) => "pHYs",
+            Ancillary::SuggestedPalette(_) => "sPLT",
+            Ancillary::Time(_) => "tIME",
+            Ancillary::Transparency(_) => "tRNS",
+        }
+    }
+
+    /// Serializes self back into chunk data.
+    pub fn to_data(&self) -> Vec<u8> {
+        match self {
+            Ancillary::Gamma(gamma) => gamma.gamma.to_be_bytes().to_vec(),
+            Ancillary::Chromaticities(chromaticities) => chromaticities
+                .values()
+                .iter()
+                .flat_map(|value| value.to_be_bytes())
+                .collect(),
+            Ancillary::Srgb(srgb) => vec![srgb.rendering_intent as u8],
+            Ancillary::IccProfile(profile) => profile
+                .name
+                .chars()
+                .map(|c| c as u8)
+                .chain([0, profile.compression_method])
+                .chain(profile.compressed_profile.iter().copied())
+                .collect(),
+            Ancillary::SignificantBits(significant) => significant.bits.clone(),
+            Ancillary::Background(color) => color.to_data(),
+            Ancillary::Histogram(histogram) => histogram
+                .frequencies
+                .iter()
+                .flat_map(|frequency| frequency.to_be_bytes())
+                .collect(),
+            Ancillary::PhysicalDimensions(dimensions) => dimensions
+                .pixels_per_unit_x
+                .to_be_bytes()
+                .into_iter()
+                .chain(dimensions.pixels_per_unit_y.to_be_bytes())
+                .chain([dimensions.unit as u8])
+                .collect(),
+            Ancillary::SuggestedPalette(palette) => {
+                let mut data =
+                    palette.name.chars().map(|c| c as u8).collect::<Vec<u8>>();
+                data.extend([0, palette.sample_depth]);
+
+                for entry in palette.entries.iter() {
+                    for sample in [entry.red, entry.green, entry.blue, entry.alpha] {
+                        match palette.sample_depth {
+                            8 => data.push(sample as u8),
+                            _ => data.extend(sample.to_be_bytes()),
+                        }
+                    }
+                    data.extend(entry.frequency.to_be_bytes());
+                }
+
+                data
+            }
+            Ancillary::Time(time) => time
+                .year
+                .to_be_bytes()
+                .into_iter()
+                .chain([time.month, time.day, time.hour, time.minute, time.second])
+                .collect(),
+            Ancillary::Transparency(Transparency::Color(color)) => color.to_data(),
+            Ancillary::Transparency(Transparency::Palette { alphas }) => {
+                alphas.clone()
+            }
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(self.chunk_type()).unwrap(),
+            self.to_data(),
+        )
+    }
+
+    /// A bKGD, sBIT or tRNS value from plain numbers, laid out for the
+    /// color type of the image: one byte per palette index, alpha or
+    /// significant bit count and two per gray or rgb sample.
+    pub fn from_samples(
+        chunk_type: &str,
+        samples: &[u16],
+        color_type: u8,
+    ) -> Result<Ancillary> {
+        let data = match (chunk_type, color_type) {
+            ("sBIT", _) | (_, 3) => samples
+                .iter()
+                .map(|&sample| u8::try_from(sample))
+                .collect::<std::result::Result<Vec<u8>, _>>()?,
+            _ => samples
+                .iter()
+                .flat_map(|sample| sample.to_be_bytes())
+                .collect(),
+        };
+
+        Ancillary::decode(
+            &Chunk::new(ChunkType::from_str(chunk_type)?, data),
+            Some(color_type),
+        )
+    }
+}
+
+/// Chunk type that must not be present next to chunk_type, if any.
+pub fn excluded_by(chunk_type: &str) -> Option<&'static str> {
+    EXCLUSIVE_CHUNK_TYPES
+        .iter()
+        .find(|(chunk, _)| *chunk == chunk_type)
+        .map(|(_, excluded)| *excluded)
+}
+
+/// Makes room for a chunk of chunk_type in png, whose exclusive
+/// counterpart (sRGB for iCCP and the other way round) is removed when
+/// remove is set. Errors if the counterpart is present otherwise.
+pub fn make_room(png: &mut Png, chunk_type: &str, remove: bool) -> Result<()> {
+    let Some(excluded) = excluded_by(chunk_type) else {
+        return Ok(());
+    };
+
+    if png.chunk_by_type(excluded).is_empty() {
+        return Ok(());
+    }
+
+    if !remove {
+        return Err(Box::new(ChunkError(match excluded {
+            "iCCP" => "png has an iCCP chunk, which must not be combined with sRGB. Pass --remove-iccp to replace it",
+            _ => "png has an sRGB chunk, which must not be combined with iCCP. Pass --remove-srgb to replace it",
+        })));
+    }
+
+    while png.remove_chunk(excluded).is_some() {}
+    Ok(())
+}
+
+/// Human readable value, as shown by print.
+impl fmt::Display for Ancillary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scaled = |value: u32| value as f64 / SCALE;
+
+        match self {
+            Ancillary::Gamma(gamma) => write!(f, "gamma {}", gamma.value()),
+            Ancillary::Chromaticities(c) => write!(
+                f,
+                "white ({}, {}), red ({}, {}), green ({}, {}), blue ({}, {})",
+                scaled(c.white_x),
+                scaled(c.white_y),
+                scaled(c.red_x),
+                scaled(c.red_y),
+                scaled(c.green_x),
+                scaled(c.green_y),
+                scaled(c.blue_x),
+                scaled(c.blue_y)
+            ),
+            Ancillary::Srgb(srgb) => {
+                write!(f, "{} rendering intent", srgb.rendering_intent)
+            }
+            Ancillary::IccProfile(profile) => {
+                write!(
+                    f,
+                    "profile \"{}\", {} bytes compressed",
+                    profile.name,
+                    profile.compressed_profile.len()
+                )?;
+
+                match &profile.header {
+                    Some(header) => write!(f, ", {}", header),
+                    None => write!(f, ", not a valid ICC profile"),
+                }
+            }
+            Ancillary::SignificantBits(significant) => {
+                let bits = significant
+                    .bits
+                    .iter()
+                    .map(|bits| bits.to_string())
+                    .collect::<Vec<String>>();
+                write!(f, "significant bits {}", bits.join(", "))
+            }
+            Ancillary::Background(color) => write!(f, "background {}", color),
+            Ancillary::Histogram(histogram) => {
+                write!(f, "{} palette frequencies", histogram.frequencies.len())
+            }
+            Ancillary::PhysicalDimensions(dimensions) => match dimensions.dpi() {
+                Some((x, y)) => write!(
+                    f,
+                    "{} x {} pixels per meter ({:.0} x {:.0} dpi)",
+                    dimensions.pixels_per_unit_x, dimensions.pixels_per_unit_y, x, y
+                ),
+                None => write!(
+                    f,
+                    "aspect ratio {}:{}",
+                    dimensions.pixels_per_unit_x, dimensions.pixels_per_unit_y
+                ),
+            },
+            Ancillary::SuggestedPalette(palette) => write!(
+                f,
+                "palette \"{}\", {} entries of {} bit samples",
+                palette.name,
+                palette.entries.len(),
+                palette.sample_depth
+            ),
+            Ancillary::Time(time) => write!(f, "modified {}", time),
+            Ancillary::Transparency(Transparency::Color(color)) => {
+                write!(f, "transparent {}", color)
+            }
+            Ancillary::Transparency(Transparency::Palette { alphas }) => {
+                write!(f, "alpha for {} palette entries", alphas.len())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    #[test]
+    fn test_round_trip() {
+        // (chunk, color type of the image)
+        let chunks = [
+            (chunk_of("gAMA", &[0, 0, 177, 143]), 2),
+            (chunk_of("cHRM", &[1; 32]), 2),
+            (chunk_of("sRGB", &[0]), 2),
+            (chunk_of("iCCP", b"sRGB\0\0profile"), 2),
+            (chunk_of("sBIT", &[5, 6, 5]), 2),
+            (chunk_of("sBIT", &[5, 6]), 4),
+            (chunk_of("bKGD", &[0, 1, 0, 2, 0, 3]), 2),
+            (chunk_of("bKGD", &[0, 1]), 0),
+            (chunk_of("bKGD", &[4]), 3),
+            (chunk_of("hIST", &[0, 1, 0, 2]), 3),
+            (chunk_of("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1]), 2),
+            (chunk_of("sPLT", b"web\0\x08\xff\x00\x00\xff\x00\x01"), 2),
+            (
+                chunk_of(
+                    "sPLT",
+                    b"deep\0\x10\x00\x01\x00\x02\x00\x03\x00\x04\x00\x05",
+                ),
+                2,
+            ),
+            (chunk_of("tIME", &[7, 232, 5, 17, 13, 45, 0]), 2),
+            (chunk_of("tRNS", &[0, 255]), 0),
+            (chunk_of("tRNS", &[0, 1, 0, 2, 0, 3]), 2),
+            (chunk_of("tRNS", &[255, 0, 128]), 3),
+        ];
+
+        for (chunk, color_type) in chunks.iter() {
+            let decoded = Ancillary::decode(chunk, Some(*color_type))
+                .unwrap_or_else(|error| {
+                    panic!(
+                        "{} with color type {}: {}",
+                        chunk.chunk_type(),
+                        color_type,
+                        error
+                    )
+                });
+
+            assert_eq!(decoded.to_chunk(), *chunk);
+        }
+    }
+
+    #[test]
+    fn test_human_readable() {
+        let display = |chunk_type: &str, data: &[u8], color_type: Option<u8>| {
+            Ancillary::decode(&chunk_of(chunk_type, data), color_type)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(display("gAMA", &[0, 0, 177, 143], None), "gamma 0.45455");
+        assert_eq!(
+            display("sRGB", &[1], None),
+            "relative colorimetric rendering intent"
+        );
+        assert_eq!(
+            display("pHYs", &[0, 0, 11, 19, 0, 0, 11, 19, 1], None),
+            "2835 x 2835 pixels per meter (72 x 72 dpi)"
+        );
+        assert_eq!(
+            display("tIME", &[7, 232, 5, 17, 13, 45, 0], None),
+            "modified 2024-05-17T13:45:00Z"
+        );
+        assert_eq!(display("tRNS", &[0, 255], Some(0)), "transparent gray 255");
+        assert_eq!(
+            display("tRNS", &[0, 255], Some(3)),
+            "alpha for 2 palette entries"
+        );
+        assert_eq!(display("bKGD", &[4], None), "background palette index 4");
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(Ancillary::decode(&chunk_of("gAMA", &[0, 1]), None).is_err());
+        assert!(Ancillary::decode(&chunk_of("sRGB", &[4]), None).is_err());
+        assert!(Ancillary::decode(&chunk_of("pHYs", &[0; 8]), None).is_err());
+        assert!(Ancillary::decode(
+            &chunk_of("tIME", &[7, 232, 13, 1, 0, 0, 0]),
+            None
+        )
+        .is_err());
+        assert!(Ancillary::decode(&chunk_of("sPLT", b"web\0\x07"), None).is_err());
+        assert!(Ancillary::decode(&chunk_of("bKGD", &[0, 1]), Some(2)).is_err());
+        assert!(Ancillary::decode(&chunk_of("ruSt", &[]), None).is_err());
+    }
+
+    #[test]
+    fn test_from_samples() {
+        let data = |chunk_type: &str, samples: &[u16], color_type: u8| {
+            Ancillary::from_samples(chunk_type, samples, color_type)
+                .map(|value| value.to_data())
+        };
+
+        assert_eq!(data("bKGD", &[1, 2, 3], 2).unwrap(), [0, 1, 0, 2, 0, 3]);
+        assert_eq!(data("bKGD", &[7], 3).unwrap(), [7]);
+        assert_eq!(data("tRNS", &[255, 0], 3).unwrap(), [255, 0]);
+        assert_eq!(data("sBIT", &[5, 6, 5, 8], 6).unwrap(), [5, 6, 5, 8]);
+        assert!(data("bKGD", &[1], 2).is_err());
+        assert!(data("bKGD", &[300], 3).is_err());
+        assert!(data("tRNS", &[0], 6).is_err());
+
+        let chromaticities = Chromaticities::from_values(&[
+            0.3127, 0.329, 0.64, 0.33, 0.3, 0.6, 0.15, 0.06,
+        ])
+        .unwrap();
+        assert_eq!(chromaticities.white_x, 31270);
+        assert!(Chromaticities::from_values(&[0.5; 7]).is_err());
+        assert!(Chromaticities::from_values(&[1.5; 8]).is_err());
+    }
+
+    #[test]
+    fn test_make_room() {
+        let mut png =
+            Png::from_chunks(vec![chunk_of("IHDR", &[]), chunk_of("iCCP", &[])]);
+
+        assert!(make_room(&mut png, "sRGB", false).is_err());
+        make_room(&mut png, "gAMA", false).unwrap();
+        make_room(&mut png, "sRGB", true).unwrap();
+        assert!(png.chunk_by_type("iCCP").is_empty());
+    }
+
+    #[test]
+    fn test_set_values() {
+        assert_eq!(
+            PhysicalDimensions::from_dpi(300.0)
+                .unwrap()
+                .pixels_per_unit_x,
+            11811
+        );
+        assert!(PhysicalDimensions::from_dpi(0.0).is_err());
+        assert_eq!(Gamma::from_value(0.45455).unwrap().gamma, 45455);
+
+        let time = Time::from_str("2024-05-17T13:45:00Z").unwrap();
+        assert_eq!(time.to_string(), "2024-05-17T13:45:00Z");
+        assert_eq!(Time::from_str("2024-05-17T13:45:00").unwrap(), time);
+        assert!(Time::from_str("2024-05-17").is_err());
+        assert!(Time::from_str("2024-13-17T13:45:00").is_err());
+        assert!(Time::now().validate().is_ok());
+    }
+}
@@ -0,0 +1,492 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// Chunk types belonging to an animation. They carry sequence numbers and
+/// must never be added or removed as hidden messages.
+/// https://wiki.mozilla.org/APNG_Specification
+pub const APNG_CHUNK_TYPES: [&str; 3] = ["acTL", "fcTL", "fdAT"];
+
+pub fn is_apng_chunk_type(chunk_type: &str) -> bool {
+    APNG_CHUNK_TYPES.contains(&chunk_type)
+}
+
+/// acTL: number of frames and number of times to loop (0 = forever).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    pub num_plays: u32,
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+
+        if data.len() != 8 {
+            return Err(Box::new(ChunkError("acTL must be 8 bytes")));
+        }
+
+        Ok(AnimationControl {
+            num_frames: read_u32(&data[0..4]),
+            num_plays: read_u32(&data[4..8]),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(DisposeOp::None),
+            1 => Ok(DisposeOp::Background),
+            2 => Ok(DisposeOp::Previous),
+            _ => Err(Box::new(ChunkError("Invalid fcTL dispose_op"))),
+        }
+    }
+}
+
+impl fmt::Display for DisposeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            DisposeOp::None => "none",
+            DisposeOp::Background => "background",
+            DisposeOp::Previous => "previous",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(BlendOp::Source),
+            1 => Ok(BlendOp::Over),
+            _ => Err(Box::new(ChunkError("Invalid fcTL blend_op"))),
+        }
+    }
+}
+
+impl fmt::Display for BlendOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BlendOp::Source => "source",
+            BlendOp::Over => "over",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+/// fcTL: region, timing and compositing of a single frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// Frame delay in seconds. A denominator of 0 is treated as 100.
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+
+        self.delay_num as f64 / den as f64
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = chunk.data();
+
+        if data.len() != 26 {
+            return Err(Box::new(ChunkError("fcTL must be 26 bytes")));
+        }
+
+        Ok(FrameControl {
+            sequence_number: read_u32(&data[0..4]),
+            width: read_u32(&data[4..8]),
+            height: read_u32(&data[8..12]),
+            x_offset: read_u32(&data[12..16]),
+            y_offset: read_u32(&data[16..20]),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: DisposeOp::try_from(data[24])?,
+            blend_op: BlendOp::try_from(data[25])?,
+        })
+    }
+}
+
+/// A single animation frame and its compressed image data.
+///
+/// data holds the zlib stream of the frame in IDAT form, i.e. fdAT
+/// payloads with their sequence numbers stripped.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub data: Vec<Vec<u8>>,
+    pub is_default_image: bool,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = &self.control;
+        write!(
+            f,
+            "seq {}: {}x{} at ({}, {}), delay {:.3}s, dispose {}, blend {}, {} bytes{}",
+            c.sequence_number,
+            c.width,
+            c.height,
+            c.x_offset,
+            c.y_offset,
+            c.delay(),
+            c.dispose_op,
+            c.blend_op,
+            self.data.iter().map(|d| d.len()).sum::<usize>(),
+            if self.is_default_image {
+                " (default image)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl Animation {
+    /// Checks that fcTL and fdAT sequence numbers start at 0 and increase
+    /// by one without gaps, that acTL's frame count matches the fcTL count
+    /// and that every frame fits on the canvas.
+    pub fn validate(png: &Png) -> Result<()> {
+        let animation = Animation::try_from(png)?;
+        let ihdr = png.ihdr()?;
+
+        let sequence_numbers = png
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                let chunk_type = chunk.chunk_type().to_string();
+                chunk_type == "fcTL" || chunk_type == "fdAT"
+            })
+            .map(|chunk| {
+                if chunk.data().len() < 4 {
+                    return Err(ChunkError("fdAT must hold a sequence number"));
+                }
+                Ok(read_u32(&chunk.data()[0..4]))
+            })
+            .collect::<std::result::Result<Vec<u32>, ChunkError>>()?;
+
+        if sequence_numbers
+            .iter()
+            .enumerate()
+            .any(|(idx, &seq)| seq as usize != idx)
+        {
+            return Err(Box::new(ChunkError(
+                "fcTL/fdAT sequence numbers are out of order",
+            )));
+        }
+
+        if animation.control.num_frames as usize != animation.frames.len() {
+            return Err(Box::new(ChunkError(
+                "acTL frame count does not match the number of fcTL chunks",
+            )));
+        }
+
+        let out_of_bounds = animation.frames.iter().any(|frame| {
+            let c = &frame.control;
+            c.width == 0
+                || c.height == 0
+                || c.x_offset as u64 + c.width as u64 > ihdr.width as u64
+                || c.y_offset as u64 + c.height as u64 > ihdr.height as u64
+        });
+
+        if out_of_bounds {
+            return Err(Box::new(ChunkError("Frame region is outside the canvas")));
+        }
+
+        Ok(())
+    }
+
+    /// Builds a standalone png from a single frame.
+    ///
+    /// The frame region is written as-is, disposal and blending against
+    /// previous frames are not applied.
+    pub fn extract_frame(&self, png: &Png, index: usize) -> Result<Png> {
+        let frame = self
+            .frames
+            .get(index)
+            .ok_or(ChunkError("Frame index out of range"))?;
+
+        let ihdr = Ihdr {
+            width: frame.control.width,
+            height: frame.control.height,
+            ..png.ihdr()?
+        };
+
+        let idat_type = ChunkType::from_str("IDAT")?;
+        let iend_type = ChunkType::from_str("IEND")?;
+
+        // palette, transparency and color chunks apply to every frame
+        let first_data_idx = png
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .unwrap_or(png.chunks.len());
+
+        let shared = png.chunks[..first_data_idx].iter().filter(|chunk| {
+            let chunk_type = chunk.chunk_type().to_string();
+            chunk_type != "IHDR" && !is_apng_chunk_type(&chunk_type)
+        });
+
+        let chunks = std::iter::once(ihdr.to_chunk())
+            .chain(shared.cloned())
+            .chain(
+                frame
+                    .data
+                    .iter()
+                    .map(|data| Chunk::new(idat_type.clone(), data.clone())),
+            )
+            .chain(std::iter::once(Chunk::new(iend_type, vec![])))
+            .collect();
+
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+impl TryFrom<&Png> for Animation {
+    type Error = Error;
+
+    fn try_from(png: &Png) -> Result<Self> {
+        let actl = png
+            .chunk_by_type("acTL")
+            .into_iter()
+            .next()
+            .ok_or(ChunkError("Png is not animated (no acTL chunk)"))?;
+
+        let control = AnimationControl::try_from(actl)?;
+        let mut frames: Vec<Frame> = vec![];
+
+        for chunk in png.chunks.iter() {
+            match chunk.chunk_type().to_string().as_str() {
+                "fcTL" => frames.push(Frame {
+                    control: FrameControl::try_from(chunk)?,
+                    data: vec![],
+                    is_default_image: false,
+                }),
+                "IDAT" => {
+                    // an fcTL before the first IDAT makes the default image frame 0
+                    if let Some(frame) = frames.last_mut() {
+                        frame.is_default_image = true;
+                        frame.data.push(chunk.data().to_vec());
+                    }
+                }
+                "fdAT" => {
+                    let frame = frames
+                        .last_mut()
+                        .ok_or(ChunkError("fdAT chunk before any fcTL chunk"))?;
+
+                    if chunk.data().len() < 4 {
+                        return Err(Box::new(ChunkError(
+                            "fdAT must hold a sequence number",
+                        )));
+                    }
+
+                    frame.data.push(chunk.data()[4..].to_vec());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Animation { control, frames })
+    }
+}
+
+impl fmt::Display for Animation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plays = match self.control.num_plays {
+            0 => "forever".to_string(),
+            n => format!("{} times", n),
+        };
+
+        writeln!(
+            f,
+            "Animation: {} frames, plays {}",
+            self.frames.len(),
+            plays
+        )?;
+        for (idx, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "  Frame {}: {}", idx, frame)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn fctl(seq: u32, width: u32, height: u32) -> Chunk {
+        let data = [seq, width, height, 0, 0]
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .chain([0, 1, 0, 10, 1, 0])
+            .collect::<Vec<u8>>();
+        chunk_of("fcTL", &data)
+    }
+
+    fn fdat(seq: u32, data: &[u8]) -> Chunk {
+        chunk_of("fdAT", &[&seq.to_be_bytes(), data].concat())
+    }
+
+    fn testing_apng() -> Png {
+        let ihdr = Ihdr {
+            width: 4,
+            height: 4,
+            bit_depth: 8,
+            color_type: 6,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+
+        let actl = [2u32, 0]
+            .iter()
+            .flat_map(|n| n.to_be_bytes())
+            .collect::<Vec<u8>>();
+
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            chunk_of("acTL", &actl),
+            chunk_of("gAMA", &[0, 0, 177, 143]),
+            fctl(0, 4, 4),
+            chunk_of("IDAT", &[1, 2, 3]),
+            fctl(1, 2, 2),
+            fdat(2, &[4, 5]),
+            fdat(3, &[6]),
+            chunk_of("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_parse_animation() {
+        let png = testing_apng();
+        let animation = Animation::try_from(&png).unwrap();
+
+        assert_eq!(animation.control.num_frames, 2);
+        assert_eq!(animation.frames.len(), 2);
+        assert!(animation.frames[0].is_default_image);
+        assert_eq!(animation.frames[1].data, vec![vec![4, 5], vec![6]]);
+        assert_eq!(
+            animation.frames[1].control.dispose_op,
+            DisposeOp::Background
+        );
+        assert_eq!(animation.frames[1].control.blend_op, BlendOp::Source);
+        assert_eq!(animation.frames[1].control.delay(), 0.1);
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let png = Png::from_chunks(vec![chunk_of("IEND", &[])]);
+        assert!(Animation::try_from(&png).is_err());
+    }
+
+    #[test]
+    fn test_valid_sequence() {
+        assert!(Animation::validate(&testing_apng()).is_ok());
+    }
+
+    #[test]
+    fn test_sequence_gap_is_invalid() {
+        let mut png = testing_apng();
+        png.chunks[7] = fdat(4, &[6]);
+        assert!(Animation::validate(&png).is_err());
+    }
+
+    #[test]
+    fn test_frame_count_mismatch_is_invalid() {
+        let mut png = testing_apng();
+        png.chunks[1] = chunk_of(
+            "acTL",
+            &[3u32, 0]
+                .iter()
+                .flat_map(|n| n.to_be_bytes())
+                .collect::<Vec<u8>>(),
+        );
+        assert!(Animation::validate(&png).is_err());
+    }
+
+    #[test]
+    fn test_append_keeps_sequence_valid() {
+        let mut png = testing_apng();
+        png.append_chunk(chunk_of("ruSt", b"hidden"));
+        assert!(Animation::validate(&png).is_ok());
+    }
+
+    #[test]
+    fn test_extract_frame() {
+        let png = testing_apng();
+        let animation = Animation::try_from(&png).unwrap();
+        let frame = animation.extract_frame(&png, 1).unwrap();
+
+        let types = frame
+            .chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect::<Vec<String>>();
+
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IDAT", "IEND"]);
+        assert_eq!(frame.ihdr().unwrap().width, 2);
+        assert_eq!(frame.chunks[2].data(), &[4, 5]);
+        assert!(animation.extract_frame(&png, 2).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+use rayon::prelude::*;
+
+use crate::commands::CommandErrors;
+use crate::file_type::FileType;
+use crate::Result;
+
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// A single png file found while expanding a batch input.
+///
+/// relative is the path of the file below the input directory and is
+/// used to mirror the input tree inside an output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchFile {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+/// Outcome of running a command over every file of a batch.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub succeeded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl BatchReport {
+    /// Prints a line per file and a summary to stderr so it never
+    /// mixes with command output on stdout.
+    pub fn print(&self) {
+        for path in self.succeeded.iter() {
+            eprintln!("ok     {}", path.display());
+        }
+
+        for (path, error) in self.failed.iter() {
+            eprintln!("FAILED {}: {}", path.display(), error);
+        }
+
+        eprintln!(
+            "{} succeeded, {} failed",
+            self.succeeded.len(),
+            self.failed.len()
+        );
+    }
+}
+
+/// True if the input names a directory or a glob pattern. An existing
+/// file is never a batch, even if its name holds glob characters.
+pub fn is_batch_input(input: &str) -> bool {
+    match FileType::from(input.to_string()) {
+        FileType::Local(path) if path.is_file() => false,
+        FileType::Local(path) => path.is_dir() || input.contains(GLOB_CHARS),
+        _ => false,
+    }
+}
+
+/// File name of a single input inside an output directory.
+fn single_relative(input: &str) -> PathBuf {
+    match FileType::from(input.to_string()) {
+        FileType::Stdio => PathBuf::from("stdin.png"),
+        FileType::DataUri(_) => PathBuf::from("data.png"),
+        _ => PathBuf::from(Path::new(input).file_name().unwrap_or_default()),
+    }
+}
+
+/// A single output_path cannot hold the results of a batch, use
+/// --output-dir instead.
+pub fn check_output_path(input: &str, output_path: &Option<String>) -> Result<()> {
+    if output_path.is_some() && is_batch_input(input) {
+        return Err(Box::new(CommandErrors::Batch(
+            "use --output-dir instead of output_path with directories or globs.",
+        )));
+    }
+
+    Ok(())
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("png"))
+        .unwrap_or(false)
+}
+
+/// Recursively collects every .png file below dir.
+///
+/// Symlinked directories are not followed, so a link cycle can't recurse
+/// forever. Symlinked files are collected.
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            walk_dir(&path, files)?;
+        } else if is_png(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Leading directory of a glob pattern, i.e. every component before the
+/// first one holding a glob character.
+fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component.as_os_str().to_string_lossy().contains(GLOB_CHARS)
+        })
+        .collect()
+}
+
+/// Expands a directory or glob pattern into the png files it names,
+/// sorted by path.
+pub fn expand_input(input: &str) -> Result<Vec<BatchFile>> {
+    let path = PathBuf::from(input);
+
+    let (base, mut paths) = if path.is_dir() {
+        let mut paths = vec![];
+        walk_dir(&path, &mut paths)?;
+        (path, paths)
+    } else {
+        let paths = glob(input)?
+            .collect::<std::result::Result<Vec<PathBuf>, _>>()?
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect();
+        (glob_base(input), paths)
+    };
+
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| BatchFile {
+            relative: path.strip_prefix(&base).unwrap_or(&path).to_path_buf(),
+            path,
+        })
+        .collect())
+}
+
+/// Like expand_input, but a plain file or url becomes a single entry.
+pub fn collect_files(input: &str) -> Result<Vec<BatchFile>> {
+    if is_batch_input(input) {
+        return expand_input(input);
+    }
+
+    Ok(vec![BatchFile {
+        path: PathBuf::from(input),
+        relative: single_relative(input),
+    }])
+}
+
+/// Location of a batch file inside output_dir (if set), creating missing
+/// parent directories along the way.
+pub fn mirrored_output(
+    output_dir: &Option<String>,
+    file: &BatchFile,
+) -> Result<Option<String>> {
+    let output_dir = match output_dir {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    let output = Path::new(output_dir).join(&file.relative);
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(Some(output.to_string_lossy().to_string()))
+}
+
+fn run_file<F>(
+    file: &BatchFile,
+    output_dir: &Option<String>,
+    command: &F,
+) -> Result<()>
+where
+    F: Fn(&str, Option<String>) -> Result<()>,
+{
+    let output = mirrored_output(output_dir, file)?;
+    command(&file.path.to_string_lossy(), output)
+}
+
+/// Runs command over the input.
+///
+/// A plain file or url runs once, exactly as if no batching existed.
+/// Directories and glob patterns run the command for every png file in
+/// parallel on jobs threads (one per core by default), then print a
+/// summary. Errors if any file failed.
+///
+/// command receives the file path and, when output_dir is set, the
+/// mirrored output path for that file.
+pub fn run<F>(
+    input: &str,
+    output_dir: &Option<String>,
+    jobs: &Option<usize>,
+    command: F,
+) -> Result<()>
+where
+    F: Fn(&str, Option<String>) -> Result<()> + Sync,
+{
+    if !is_batch_input(input) {
+        let file = BatchFile {
+            path: PathBuf::from(input),
+            relative: single_relative(input),
+        };
+        let output = mirrored_output(output_dir, &file)?;
+        return command(input, output);
+    }
+
+    let files = expand_input(input)?;
+
+    if files.is_empty() {
+        return Err(Box::new(CommandErrors::Batch(
+            "no png files matched input.",
+        )));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+
+    let results = pool.install(|| {
+        files
+            .par_iter()
+            .map(|file| {
+                // a panic on one malformed file must not abort the whole batch
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    run_file(file, output_dir, &command)
+                        .map_err(|error| error.to_string())
+                }))
+                .unwrap_or_else(|_| {
+                    Err("panicked while processing file".to_string())
+                });
+
+                (file.path.clone(), result)
+            })
+            .collect::<Vec<(PathBuf, std::result::Result<(), String>)>>()
+    });
+
+    let mut report = BatchReport::default();
+
+    for (path, result) in results {
+        match result {
+            Ok(()) => report.succeeded.push(path),
+            Err(error) => report.failed.push((path, error)),
+        }
+    }
+
+    report.print();
+
+    if !report.failed.is_empty() {
+        return Err(Box::new(CommandErrors::Batch("one or more files failed.")));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write::{testing_dir, TestingDir};
+
+    fn testing_tree(name: &str) -> TestingDir {
+        let root = testing_dir(&format!("batch_{}", name));
+        fs::create_dir_all(root.join("nested/deeper")).unwrap();
+
+        for file in [
+            "a.png",
+            "b.PNG",
+            "notes.txt",
+            "nested/c.png",
+            "nested/deeper/d.png",
+        ] {
+            fs::write(root.join(file), []).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("images/**/*.png"), PathBuf::from("images"));
+        assert_eq!(glob_base("*.png"), PathBuf::from(""));
+        assert_eq!(glob_base("a/b/c?.png"), PathBuf::from("a/b"));
+    }
+
+    #[test]
+    fn test_is_batch_input() {
+        assert!(is_batch_input("images/*.png"));
+        assert!(!is_batch_input("https://example.com/*.png"));
+        assert!(!is_batch_input("a_file_that_does_not_exist.png"));
+
+        let root = testing_tree("glob_name");
+        let file = root.join("[1].png");
+        fs::write(&file, []).unwrap();
+        assert!(!is_batch_input(file.to_str().unwrap()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_skips_symlinked_directories() {
+        let root = testing_tree("symlink");
+        std::os::unix::fs::symlink(&root, root.join("nested/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("a.png"), root.join("link.png"))
+            .unwrap();
+
+        let files = expand_input(root.to_str().unwrap()).unwrap();
+
+        assert_eq!(files.len(), 5);
+        assert!(files
+            .iter()
+            .any(|file| file.relative == Path::new("link.png")));
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let root = testing_tree("dir");
+        let files = expand_input(root.to_str().unwrap()).unwrap();
+
+        let relative = files
+            .iter()
+            .map(|file| file.relative.clone())
+            .collect::<Vec<PathBuf>>();
+
+        assert_eq!(
+            relative,
+            vec![
+                PathBuf::from("a.png"),
+                PathBuf::from("b.PNG"),
+                PathBuf::from("nested/c.png"),
+                PathBuf::from("nested/deeper/d.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let root = testing_tree("glob");
+        let pattern = format!("{}/**/*.png", root.display());
+        let files = expand_input(&pattern).unwrap();
+
+        let relative = files
+            .iter()
+            .map(|file| file.relative.clone())
+            .collect::<Vec<PathBuf>>();
+
+        assert_eq!(
+            relative,
+            vec![
+                PathBuf::from("a.png"),
+                PathBuf::from("nested/c.png"),
+                PathBuf::from("nested/deeper/d.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_mirrors_tree_and_reports_failures() {
+        let root = testing_tree("run");
+        let output = testing_dir("batch_run_output");
+        let output_dir = Some(output.to_string_lossy().to_string());
+
+        let result = run(
+            root.to_str().unwrap(),
+            &output_dir,
+            &Some(2),
+            |path, out| {
+                if path.ends_with("c.png") {
+                    return Err(Box::new(CommandErrors::Batch("bad file")));
+                }
+                fs::write(out.unwrap(), path)?;
+                Ok(())
+            },
+        );
+
+        assert!(result.is_err());
+        assert!(output.join("a.png").is_file());
+        assert!(output.join("nested/deeper/d.png").is_file());
+        assert!(!output.join("nested/c.png").exists());
+    }
+}
@@ -0,0 +1,776 @@
+use std::fmt;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::cbor::Cbor;
+use crate::chunk_error::ChunkError;
+use crate::png::Png;
+use crate::report::SCHEMA_VERSION;
+use crate::Result;
+
+/// Chunk type C2PA embeds its manifest store in.
+/// https://c2pa.org/specifications/specifications/2.1/specs/C2PA_Specification.html#_embedding_manifests_into_assets
+pub const MANIFEST_CHUNK_TYPE: &str = "caBX";
+
+/// Superboxes nested deeper than this are refused.
+const MAX_DEPTH: usize = 16;
+
+/// COSE algorithm identifiers C2PA allows for claim signatures.
+const ALGORITHMS: [(i128, &str); 7] = [
+    (-7, "ES256"),
+    (-35, "ES384"),
+    (-36, "ES512"),
+    (-37, "PS256"),
+    (-38, "PS384"),
+    (-39, "PS512"),
+    (-8, "Ed25519"),
+];
+
+/// COSE header holding the signer's certificate chain.
+const X5CHAIN: u64 = 33;
+
+/// Label prefix of hard binding assertions.
+const HARD_BINDING: &str = "c2pa.hash.";
+
+/// ISO BMFF boxes as (type, payload).
+fn boxes(bytes: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let malformed = || ChunkError("JUMBF box is malformed");
+    let mut boxes = vec![];
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let header = bytes.get(position..position + 8).ok_or_else(malformed)?;
+        let box_type = [header[4], header[5], header[6], header[7]];
+
+        let (size, header_length) =
+            match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                // the box extends to the end of its parent
+                0 => ((bytes.len() - position) as u64, 8),
+                1 => {
+                    let large = bytes
+                        .get(position + 8..position + 16)
+                        .ok_or_else(malformed)?;
+                    (u64::from_be_bytes(large.try_into()?), 16)
+                }
+                size => (size as u64, 8),
+            };
+
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| position.checked_add(size))
+            .filter(|&end| size >= header_length && end <= bytes.len())
+            .ok_or_else(malformed)?;
+
+        boxes.push((box_type, &bytes[position + header_length as usize..end]));
+        position = end;
+    }
+
+    Ok(boxes)
+}
+
+/// A JUMBF superbox: a description box followed by content boxes.
+/// ISO/IEC 19566-5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Superbox {
+    /// content type, C2PA uses the first four bytes as a readable name
+    pub uuid: [u8; 16],
+    pub label: Option<String>,
+    pub content: Vec<Content>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    Superbox(Superbox),
+    Box { box_type: [u8; 4], data: Vec<u8> },
+}
+
+impl Superbox {
+    fn parse(payload: &[u8], depth: usize) -> Result<Superbox> {
+        if depth > MAX_DEPTH {
+            return Err(Box::new(ChunkError("JUMBF is nested too deeply")));
+        }
+
+        let mut children = boxes(payload)?.into_iter();
+        let description = match children.next() {
+            Some((box_type, description))
+                if &box_type == b"jumd" && description.len() >= 17 =>
+            {
+                description
+            }
+            _ => {
+                return Err(Box::new(ChunkError(
+                    "JUMBF superbox has no description box",
+                )))
+            }
+        };
+
+        let toggles = description[16];
+        let label = match toggles & 0x02 {
+            0 => None,
+            _ => {
+                let label = &description[17..];
+                let end = label.iter().position(|&b| b == 0).unwrap_or(label.len());
+                Some(String::from_utf8(label[..end].to_vec())?)
+            }
+        };
+
+        let content = children
+            .map(|(box_type, data)| match &box_type {
+                b"jumb" => Superbox::parse(data, depth + 1).map(Content::Superbox),
+                _ => Ok(Content::Box {
+                    box_type,
+                    data: data.to_vec(),
+                }),
+            })
+            .collect::<Result<Vec<Content>>>()?;
+
+        Ok(Superbox {
+            uuid: description[..16].try_into()?,
+            label,
+            content,
+        })
+    }
+
+    /// Readable content type, e.g. c2pa, c2ma, cbor or json.
+    pub fn content_type(&self) -> String {
+        String::from_utf8_lossy(&self.uuid[..4])
+            .trim_end_matches(['\0', ' '])
+            .to_string()
+    }
+
+    fn superboxes(&self) -> impl Iterator<Item = &Superbox> {
+        self.content.iter().filter_map(|content| match content {
+            Content::Superbox(superbox) => Some(superbox),
+            Content::Box { .. } => None,
+        })
+    }
+
+    fn child(&self, content_type: &str) -> Option<&Superbox> {
+        self.superboxes()
+            .find(|superbox| superbox.content_type() == content_type)
+    }
+
+    /// Data of the first content box that isn't a superbox.
+    fn data(&self) -> Option<&[u8]> {
+        self.content.iter().find_map(|content| match content {
+            Content::Box { data, .. } => Some(data.as_slice()),
+            Content::Superbox(_) => None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.content
+            .iter()
+            .map(|content| match content {
+                Content::Box { data, .. } => data.len(),
+                Content::Superbox(superbox) => superbox.size(),
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Assertion {
+    /// e.g. c2pa.actions or c2pa.hash.data
+    pub label: String,
+    /// cbor, json or an embedded file
+    pub content_type: String,
+    /// size of the assertion's content in bytes
+    pub size: usize,
+}
+
+/// COSE_Sign1 signature over the claim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClaimSignature {
+    /// e.g. ES256, null when unknown
+    pub algorithm: Option<String>,
+    /// certificates in the signer's chain
+    pub certificates: usize,
+    pub size: usize,
+}
+
+impl ClaimSignature {
+    fn parse(data: &[u8]) -> Result<ClaimSignature> {
+        let sign1 = Cbor::decode(data)?;
+        let fields = sign1
+            .untagged()
+            .as_array()
+            .filter(|fields| fields.len() == 4)
+            .ok_or(ChunkError("Claim signature is not a COSE_Sign1"))?;
+
+        let protected = match fields[0].as_bytes() {
+            Some([]) | None => Cbor::Map(vec![]),
+            Some(bytes) => Cbor::decode(bytes)?,
+        };
+        let header =
+            |key: &Cbor| protected.get_by(key).or_else(|| fields[1].get_by(key));
+
+        let algorithm = header(&Cbor::Unsigned(1))
+            .and_then(Cbor::as_integer)
+            .and_then(|id| ALGORITHMS.iter().find(|(known, _)| *known == id))
+            .map(|(_, name)| name.to_string());
+
+        let certificates = match header(&Cbor::Unsigned(X5CHAIN)) {
+            Some(Cbor::Bytes(_)) => 1,
+            Some(Cbor::Array(chain)) => chain.len(),
+            _ => 0,
+        };
+
+        Ok(ClaimSignature {
+            algorithm,
+            certificates,
+            size: fields[3].as_bytes().map(<[u8]>::len).unwrap_or_default(),
+        })
+    }
+}
+
+/// Hash over the whole file except the excluded byte ranges, which
+/// hold the manifest itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataHash {
+    pub alg: String,
+    pub hash: Vec<u8>,
+    /// (start, length)
+    pub exclusions: Vec<(u64, u64)>,
+}
+
+impl DataHash {
+    fn parse(data: &[u8], claim_alg: Option<&str>) -> Result<DataHash> {
+        let item = Cbor::decode(data)?;
+        let malformed = || ChunkError("c2pa.hash.data assertion is malformed");
+
+        let exclusions = match item.get("exclusions") {
+            None => vec![],
+            Some(exclusions) => exclusions
+                .as_array()
+                .ok_or_else(malformed)?
+                .iter()
+                .map(|range| {
+                    let field = |name| {
+                        range
+                            .get(name)
+                            .and_then(Cbor::as_integer)
+                            .and_then(|value| u64::try_from(value).ok())
+                    };
+                    field("start").zip(field("length")).ok_or_else(malformed)
+                })
+                .collect::<std::result::Result<Vec<(u64, u64)>, ChunkError>>()?,
+        };
+
+        Ok(DataHash {
+            alg: item
+                .get("alg")
+                .and_then(Cbor::as_text)
+                .or(claim_alg)
+                .unwrap_or("sha256")
+                .to_string(),
+            hash: item
+                .get("hash")
+                .and_then(Cbor::as_bytes)
+                .ok_or_else(malformed)?
+                .to_vec(),
+            exclusions,
+        })
+    }
+
+    /// None when the hash algorithm is unknown.
+    pub fn matches(&self, file: &[u8]) -> Option<bool> {
+        let mut hashed = vec![];
+        let mut position = 0;
+
+        for &(start, length) in self.exclusions.iter() {
+            let start = (start as usize).clamp(position, file.len());
+            hashed.extend(&file[position..start]);
+            position = start.saturating_add(length as usize).min(file.len());
+        }
+        hashed.extend(&file[position..]);
+
+        let digest = match self.alg.as_str() {
+            "sha256" => Sha256::digest(&hashed).to_vec(),
+            "sha384" => Sha384::digest(&hashed).to_vec(),
+            "sha512" => Sha512::digest(&hashed).to_vec(),
+            _ => return None,
+        };
+
+        Some(digest == self.hash)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// usually urn:uuid:..., or urn:c2pa:... in newer manifests
+    pub label: String,
+    pub claim_generator: Option<String>,
+    pub title: Option<String>,
+    pub format: Option<String>,
+    pub assertions: Vec<Assertion>,
+    pub signature: Option<ClaimSignature>,
+    /// label and hash of the hard binding assertion, hash is None for
+    /// bindings other than c2pa.hash.data
+    pub hard_binding: Option<(String, Option<DataHash>)>,
+}
+
+impl Manifest {
+    fn parse(superbox: &Superbox) -> Result<Manifest> {
+        let claim = superbox
+            .child("c2cl")
+            .and_then(Superbox::data)
+            .map(Cbor::decode)
+            .transpose()?;
+        let text = |key: &str| {
+            claim
+                .as_ref()
+                .and_then(|claim| claim.get(key))
+                .and_then(Cbor::as_text)
+                .map(str::to_string)
+        };
+
+        // claims v2 describe the generator in a map
+        let claim_generator = text("claim_generator").or_else(|| {
+            let info = claim.as_ref()?.get("claim_generator_info")?;
+            let info = info.as_array().and_then(<[Cbor]>::first).unwrap_or(info);
+            let name = info.get("name")?.as_text()?;
+
+            Some(match info.get("version").and_then(Cbor::as_text) {
+                Some(version) => format!("{}/{}", name, version),
+                None => name.to_string(),
+            })
+        });
+
+        let mut assertions = vec![];
+        let mut hard_binding = None;
+
+        for assertion in superbox
+            .child("c2as")
+            .into_iter()
+            .flat_map(Superbox::superboxes)
+        {
+            let label = assertion.label.clone().unwrap_or_default();
+
+            if hard_binding.is_none() && label.starts_with(HARD_BINDING) {
+                let hash = match label.starts_with("c2pa.hash.data") {
+                    true => assertion
+                        .data()
+                        .map(|data| DataHash::parse(data, text("alg").as_deref()))
+                        .transpose()?,
+                    false => None,
+                };
+                hard_binding = Some((label.clone(), hash));
+            }
+
+            assertions.push(Assertion {
+                label,
+                content_type: assertion.content_type(),
+                size: assertion.size(),
+            });
+        }
+
+        let signature = superbox
+            .child("c2cs")
+            .and_then(Superbox::data)
+            .map(ClaimSignature::parse)
+            .transpose()?;
+
+        Ok(Manifest {
+            label: superbox.label.clone().unwrap_or_default(),
+            claim_generator,
+            title: text("dc:title"),
+            format: text("dc:format"),
+            assertions,
+            signature,
+            hard_binding,
+        })
+    }
+}
+
+/// Contents of a caBX chunk. The last manifest is the active one, the
+/// others describe ingredients the image was made from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestStore {
+    pub manifests: Vec<Manifest>,
+}
+
+impl TryFrom<&[u8]> for ManifestStore {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let store = match boxes(bytes)?.as_slice() {
+            [([b'j', b'u', b'm', b'b'], payload), ..] => {
+                Superbox::parse(payload, 0)?
+            }
+            _ => return Err(Box::new(ChunkError("caBX does not hold a JUMBF box"))),
+        };
+
+        if store.content_type() != "c2pa" {
+            return Err(Box::new(ChunkError(
+                "caBX does not hold a C2PA manifest store",
+            )));
+        }
+
+        let manifests = store
+            .superboxes()
+            .filter(|superbox| superbox.content_type() == "c2ma")
+            .map(Manifest::parse)
+            .collect::<Result<Vec<Manifest>>>()?;
+
+        Ok(ManifestStore { manifests })
+    }
+}
+
+impl ManifestStore {
+    pub fn active(&self) -> Option<&Manifest> {
+        self.manifests.last()
+    }
+}
+
+/// Warning for writing png over original, None when original carries
+/// no manifest or its content credentials survive unchanged.
+///
+/// The hard binding hashes every byte outside the caBX chunk, so any
+/// other change invalidates it.
+pub fn binding_warning(original: &Png, png: &Png) -> Option<&'static str> {
+    if original.chunk_by_type(MANIFEST_CHUNK_TYPE).is_empty() {
+        return None;
+    }
+
+    if png.chunk_by_type(MANIFEST_CHUNK_TYPE).is_empty() {
+        return Some("removes the C2PA manifest (caBX chunk) and with it the content credentials");
+    }
+
+    if original.as_bytes() != png.as_bytes() {
+        return Some("invalidates the C2PA content credentials, the hard binding hash no longer matches the file");
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingStatus {
+    Valid,
+    Invalid,
+    /// the binding is not a c2pa.hash.data hash or uses an unknown
+    /// algorithm
+    Unchecked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BindingReport {
+    pub assertion: String,
+    pub status: BindingStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestReport {
+    pub label: String,
+    pub active: bool,
+    pub claim_generator: Option<String>,
+    pub title: Option<String>,
+    pub format: Option<String>,
+    pub assertions: Vec<Assertion>,
+    pub signature: Option<ClaimSignature>,
+    /// only checked for the active manifest
+    pub hard_binding: Option<BindingReport>,
+}
+
+/// Output of c2pa.
+#[derive(Debug, Clone, Serialize)]
+pub struct C2paReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub manifests: Vec<ManifestReport>,
+}
+
+impl C2paReport {
+    /// file is the png as written, the hard binding hashes its bytes.
+    pub fn new(path: &str, store: &ManifestStore, file: &[u8]) -> C2paReport {
+        let count = store.manifests.len();
+        let manifests = store
+            .manifests
+            .iter()
+            .enumerate()
+            .map(|(idx, manifest)| {
+                let active = idx + 1 == count;
+                let hard_binding =
+                    manifest.hard_binding.as_ref().filter(|_| active).map(
+                        |(label, hash)| BindingReport {
+                            assertion: label.clone(),
+                            status: match hash
+                                .as_ref()
+                                .and_then(|hash| hash.matches(file))
+                            {
+                                Some(true) => BindingStatus::Valid,
+                                Some(false) => BindingStatus::Invalid,
+                                None => BindingStatus::Unchecked,
+                            },
+                        },
+                    );
+
+                ManifestReport {
+                    label: manifest.label.clone(),
+                    active,
+                    claim_generator: manifest.claim_generator.clone(),
+                    title: manifest.title.clone(),
+                    format: manifest.format.clone(),
+                    assertions: manifest.assertions.clone(),
+                    signature: manifest.signature.clone(),
+                    hard_binding,
+                }
+            })
+            .collect();
+
+        C2paReport {
+            schema_version: SCHEMA_VERSION,
+            path: path.to_string(),
+            manifests,
+        }
+    }
+}
+
+/// Every manifest with its claim, assertions and signature.
+impl fmt::Display for C2paReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: C2PA manifest store with {} manifest(s)",
+            self.path,
+            self.manifests.len()
+        )?;
+
+        for manifest in self.manifests.iter() {
+            write!(
+                f,
+                "\nManifest {}{}",
+                manifest.label,
+                if manifest.active { " (active)" } else { "" }
+            )?;
+
+            let fields = [
+                ("Claim generator", &manifest.claim_generator),
+                ("Title", &manifest.title),
+                ("Format", &manifest.format),
+            ];
+            for (name, value) in fields {
+                if let Some(value) = value {
+                    write!(f, "\n  {}: {}", name, value)?;
+                }
+            }
+
+            write!(f, "\n  Assertions:")?;
+            for assertion in manifest.assertions.iter() {
+                write!(
+                    f,
+                    "\n    {} ({}, {} bytes)",
+                    assertion.label, assertion.content_type, assertion.size
+                )?;
+            }
+
+            match &manifest.signature {
+                Some(signature) => write!(
+                    f,
+                    "\n  Signature: {}, {} certificate(s), {} bytes",
+                    signature
+                        .algorithm
+                        .as_deref()
+                        .unwrap_or("unknown algorithm"),
+                    signature.certificates,
+                    signature.size
+                )?,
+                None => write!(f, "\n  Signature: missing")?,
+            }
+
+            if let Some(binding) = &manifest.hard_binding {
+                let status = match binding.status {
+                    BindingStatus::Valid => "matches the file",
+                    BindingStatus::Invalid => "does NOT match the file",
+                    BindingStatus::Unchecked => "not checked",
+                };
+                write!(f, "\n  Hard binding: {} {}", binding.assertion, status)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn bmff_box(box_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend(box_type);
+        bytes.extend(payload);
+        bytes
+    }
+
+    fn superbox(
+        content_type: &[u8; 4],
+        label: &str,
+        content: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let mut description = content_type.to_vec();
+        description.extend([0x00, 0x11, 0x00, 0x10, 0x80, 0x00]);
+        description.extend([0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71]);
+        description.push(0x03);
+        description.extend(label.as_bytes());
+        description.push(0);
+
+        let mut payload = bmff_box(b"jumd", &description);
+        payload.extend(content.concat());
+        bmff_box(b"jumb", &payload)
+    }
+
+    /// CBOR text string shorter than 24 bytes.
+    fn text(value: &str) -> Vec<u8> {
+        let mut bytes = vec![0x60 + value.len() as u8];
+        bytes.extend(value.as_bytes());
+        bytes
+    }
+
+    /// Store with an ingredient manifest and an active one whose
+    /// c2pa.hash.data excludes the caBX chunk at offset 33.
+    fn testing_store(hash: &[u8; 32], exclusion_length: u16) -> Vec<u8> {
+        let mut hash_data = vec![0xa2];
+        hash_data.extend(text("exclusions"));
+        hash_data.extend([0x81, 0xa2]);
+        hash_data.extend(text("start"));
+        hash_data.push(0x18);
+        hash_data.push(33);
+        hash_data.extend(text("length"));
+        hash_data.push(0x19);
+        hash_data.extend(exclusion_length.to_be_bytes());
+        hash_data.extend(text("hash"));
+        hash_data.extend([0x58, 32]);
+        hash_data.extend(hash);
+
+        let mut claim = vec![0xa2];
+        claim.extend(text("claim_generator"));
+        claim.extend(text("pngsneak/0.1"));
+        claim.extend(text("dc:format"));
+        claim.extend(text("image/png"));
+
+        // 18([h'a1 01 26', {33: h'00'}, null, h'0102'])
+        let signature = [
+            0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x18, 0x21, 0x41, 0x00, 0xf6,
+            0x42, 0x01, 0x02,
+        ];
+
+        let active = superbox(
+            b"c2ma",
+            "urn:uuid:active",
+            &[
+                superbox(
+                    b"c2as",
+                    "c2pa.assertions",
+                    &[superbox(
+                        b"cbor",
+                        "c2pa.hash.data",
+                        &[bmff_box(b"cbor", &hash_data)],
+                    )],
+                ),
+                superbox(b"c2cl", "c2pa.claim", &[bmff_box(b"cbor", &claim)]),
+                superbox(
+                    b"c2cs",
+                    "c2pa.signature",
+                    &[bmff_box(b"cbor", &signature)],
+                ),
+            ],
+        );
+        let ingredient = superbox(b"c2ma", "urn:uuid:ingredient", &[]);
+
+        superbox(b"c2pa", "c2pa", &[ingredient, active])
+    }
+
+    fn testing_png(store: Vec<u8>) -> Png {
+        Png::from_chunks(vec![
+            chunk_of("IHDR", &[0; 13]),
+            chunk_of("caBX", &store),
+            chunk_of("IDAT", &[1, 2, 3]),
+            chunk_of("IEND", &[]),
+        ])
+    }
+
+    /// Png whose hard binding matches, the hash does not change the
+    /// length of the caBX chunk.
+    fn signed_png() -> Png {
+        let length = testing_store(&[0; 32], 0).len() as u16 + 12;
+        let unsigned = testing_png(testing_store(&[0; 32], length)).as_bytes();
+
+        let mut hashed = unsigned[..33].to_vec();
+        hashed.extend(&unsigned[33 + length as usize..]);
+        let hash: [u8; 32] = Sha256::digest(&hashed).into();
+
+        testing_png(testing_store(&hash, length))
+    }
+
+    #[test]
+    fn test_manifest_store() {
+        let png = signed_png();
+        let store = ManifestStore::try_from(png.chunks[1].data()).unwrap();
+
+        assert_eq!(store.manifests.len(), 2);
+
+        let active = store.active().unwrap();
+        assert_eq!(active.label, "urn:uuid:active");
+        assert_eq!(active.claim_generator.as_deref(), Some("pngsneak/0.1"));
+        assert_eq!(active.format.as_deref(), Some("image/png"));
+        assert_eq!(active.assertions[0].label, "c2pa.hash.data");
+        assert_eq!(active.assertions[0].content_type, "cbor");
+        assert_eq!(
+            active.signature,
+            Some(ClaimSignature {
+                algorithm: Some("ES256".to_string()),
+                certificates: 1,
+                size: 2,
+            })
+        );
+
+        assert!(ManifestStore::try_from(&[0, 0, 0, 8, b'j', b'u'][..]).is_err());
+        assert!(
+            ManifestStore::try_from(superbox(b"json", "x", &[]).as_slice()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_hard_binding() {
+        let png = signed_png();
+        let store = ManifestStore::try_from(png.chunks[1].data()).unwrap();
+        let (_, hash) = store.active().unwrap().hard_binding.clone().unwrap();
+        let hash = hash.unwrap();
+
+        assert_eq!(hash.matches(&png.as_bytes()), Some(true));
+
+        let mut changed = png.clone();
+        changed.chunks[2] = chunk_of("IDAT", &[4]);
+        assert_eq!(hash.matches(&changed.as_bytes()), Some(false));
+
+        let report = C2paReport::new("image.png", &store, &changed.as_bytes());
+        assert!(!report.manifests[0].active);
+        assert_eq!(
+            report.manifests[1].hard_binding.as_ref().unwrap().status,
+            BindingStatus::Invalid
+        );
+    }
+
+    #[test]
+    fn test_binding_warning() {
+        let png = signed_png();
+        let mut changed = png.clone();
+        changed.chunks.remove(1);
+
+        assert!(binding_warning(&png, &png).is_none());
+        assert!(binding_warning(&changed, &changed).is_none());
+        assert!(binding_warning(&png, &changed)
+            .unwrap()
+            .starts_with("removes"));
+
+        changed = png.clone();
+        changed.chunks[2] = chunk_of("IDAT", &[4]);
+        assert!(binding_warning(&png, &changed)
+            .unwrap()
+            .starts_with("invalidates"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::chunk_error::ChunkError;
+use crate::Result;
+
+/// Nesting deeper than this is refused instead of recursing further.
+const MAX_DEPTH: usize = 32;
+
+/// A decoded CBOR data item, just enough to read C2PA claims.
+/// https://www.rfc-editor.org/rfc/rfc8949
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Unsigned(u64),
+    Negative(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+    Null,
+    Undefined,
+    Float(f64),
+}
+
+impl Cbor {
+    /// Decodes a single data item, trailing bytes are an error.
+    pub fn decode(bytes: &[u8]) -> Result<Cbor> {
+        let mut decoder = Decoder { bytes, position: 0 };
+        let item = decoder.item(0)?;
+
+        if decoder.position != bytes.len() {
+            return Err(Box::new(ChunkError("CBOR has trailing bytes")));
+        }
+
+        Ok(item)
+    }
+
+    /// Value of a text key of a map.
+    pub fn get(&self, key: &str) -> Option<&Cbor> {
+        self.get_by(&Cbor::Text(key.to_string()))
+    }
+
+    /// Value of any key of a map, COSE headers use integer keys.
+    pub fn get_by(&self, key: &Cbor) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Cbor::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Cbor::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Cbor]> {
+        match self {
+            Cbor::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Cbor::Unsigned(value) => Some(*value as i128),
+            Cbor::Negative(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The tagged item itself, with any tags removed.
+    pub fn untagged(&self) -> &Cbor {
+        match self {
+            Cbor::Tag(_, item) => item.untagged(),
+            item => item,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, length: u64) -> Result<&[u8]> {
+        let truncated = || ChunkError("CBOR is truncated");
+        let end = usize::try_from(length)
+            .ok()
+            .and_then(|length| self.position.checked_add(length))
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(truncated)?;
+
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    /// Argument of the initial byte, the value or length of the item.
+    fn argument(&mut self, info: u8) -> Result<u64> {
+        let length = match info {
+            0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => {
+                return Err(Box::new(ChunkError(
+                    "CBOR indefinite lengths are not supported",
+                )))
+            }
+        };
+
+        Ok(self
+            .take(length)?
+            .iter()
+            .fold(0, |value, &b| value << 8 | b as u64))
+    }
+
+    /// Count of a collection, each element takes at least one byte so
+    /// a count beyond the remaining bytes is malformed.
+    fn count(&mut self, info: u8) -> Result<usize> {
+        let count = self.argument(info)?;
+
+        if count > (self.bytes.len() - self.position) as u64 {
+            return Err(Box::new(ChunkError("CBOR is truncated")));
+        }
+
+        Ok(count as usize)
+    }
+
+    fn item(&mut self, depth: usize) -> Result<Cbor> {
+        if depth > MAX_DEPTH {
+            return Err(Box::new(ChunkError("CBOR is nested too deeply")));
+        }
+
+        let initial = *self.take(1)?.first().unwrap_or(&0);
+        let (major, info) = (initial >> 5, initial & 0x1f);
+
+        let item = match major {
+            0 => Cbor::Unsigned(self.argument(info)?),
+            1 => Cbor::Negative(-1 - self.argument(info)? as i128),
+            2 => {
+                let length = self.argument(info)?;
+                Cbor::Bytes(self.take(length)?.to_vec())
+            }
+            3 => {
+                let length = self.argument(info)?;
+                Cbor::Text(String::from_utf8(self.take(length)?.to_vec())?)
+            }
+            4 => {
+                let count = self.count(info)?;
+                Cbor::Array(
+                    (0..count)
+                        .map(|_| self.item(depth + 1))
+                        .collect::<Result<Vec<Cbor>>>()?,
+                )
+            }
+            5 => {
+                let count = self.count(info)?;
+                Cbor::Map(
+                    (0..count)
+                        .map(|_| Ok((self.item(depth + 1)?, self.item(depth + 1)?)))
+                        .collect::<Result<Vec<(Cbor, Cbor)>>>()?,
+                )
+            }
+            6 => {
+                let tag = self.argument(info)?;
+                Cbor::Tag(tag, Box::new(self.item(depth + 1)?))
+            }
+            _ => match info {
+                20 => Cbor::Bool(false),
+                21 => Cbor::Bool(true),
+                22 => Cbor::Null,
+                23 => Cbor::Undefined,
+                25 => Cbor::Float(half_to_f64(self.argument(info)? as u16)),
+                26 => {
+                    Cbor::Float(f32::from_bits(self.argument(info)? as u32) as f64)
+                }
+                27 => Cbor::Float(f64::from_bits(self.argument(info)?)),
+                _ => {
+                    return Err(Box::new(ChunkError(
+                        "CBOR has an unknown simple value",
+                    )))
+                }
+            },
+        };
+
+        Ok(item)
+    }
+}
+
+fn half_to_f64(half: u16) -> f64 {
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as f64;
+
+    let value = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent as i32 - 25),
+    };
+
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // {"a": [1, -2, h'ff'], "b": 18("x"), 1: true, "c": 1.5}
+        let bytes = [
+            0xa4, 0x61, b'a', 0x83, 0x01, 0x21, 0x41, 0xff, 0x61, b'b', 0xd2, 0x61,
+            b'x', 0x01, 0xf5, 0x61, b'c', 0xf9, 0x3e, 0x00,
+        ];
+        let item = Cbor::decode(&bytes).unwrap();
+
+        assert_eq!(
+            item.get("a").unwrap().as_array().unwrap(),
+            [
+                Cbor::Unsigned(1),
+                Cbor::Negative(-2),
+                Cbor::Bytes(vec![0xff])
+            ]
+        );
+        assert_eq!(item.get("b").unwrap().untagged().as_text(), Some("x"));
+        assert_eq!(item.get_by(&Cbor::Unsigned(1)), Some(&Cbor::Bool(true)));
+        assert_eq!(item.get("c"), Some(&Cbor::Float(1.5)));
+    }
+
+    #[test]
+    fn test_malformed() {
+        // truncated text, huge array count, trailing bytes, indefinite map
+        assert!(Cbor::decode(&[0x63, b'a']).is_err());
+        assert!(Cbor::decode(&[
+            0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+        ])
+        .is_err());
+        assert!(Cbor::decode(&[0x01, 0x02]).is_err());
+        assert!(Cbor::decode(&[0xbf, 0xff]).is_err());
+
+        let mut nested = vec![0x81; 40];
+        nested.push(0x01);
+        assert!(Cbor::decode(&nested).is_err());
+    }
+}
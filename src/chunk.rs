@@ -1,5 +1,6 @@
 use crate::chunk_error::ChunkError;
 use crate::chunk_type::ChunkType;
+use crate::limits::MAX_U31;
 use crc::{Crc, CRC_32_ISO_HDLC};
 use std::fmt;
 use std::io;
@@ -8,7 +9,7 @@ use std::str;
 
 use crate::{Error, Result};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
     pub chunk_type: ChunkType,
     pub data: Vec<u8>,
@@ -20,18 +21,47 @@ const CRC_GEN: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 impl Chunk {
     /// creates a new Chunk struct
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        let crc = Chunk::compute_crc(&chunk_type.bytes(), &data);
+
+        Chunk {
+            chunk_type,
+            data,
+            crc,
+        }
+    }
+
+    /// crc over the chunk type and data bytes
+    pub fn compute_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
         let all_bytes = chunk_type
-            .bytes()
             .iter()
             .chain(data.iter())
             .copied()
             .collect::<Vec<u8>>();
 
-        Chunk {
+        CRC_GEN.checksum(&all_bytes)
+    }
+
+    /// true if Chunk.crc matches the chunk type and data
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc == Chunk::compute_crc(&self.chunk_type.bytes(), &self.data)
+    }
+
+    /// Parses chunk bytes without checking the crc.
+    ///
+    /// The stored crc is kept so damaged chunks can still be
+    /// read and is_crc_valid reports the mismatch.
+    pub fn from_bytes_unchecked(bytes: &[u8]) -> Result<Chunk> {
+        let mut reader = io::BufReader::new(bytes);
+
+        let (b_type, b_data, b_crc) = Chunk::get_byte_sequences(&mut reader)?;
+
+        let chunk_type = ChunkType::try_from(b_type)?;
+
+        Ok(Chunk {
             chunk_type,
-            data,
-            crc: CRC_GEN.checksum(&all_bytes),
-        }
+            data: b_data,
+            crc: u32::from_be_bytes(b_crc),
+        })
     }
 
     /// return the length of Chunk.data
@@ -71,14 +101,21 @@ impl Chunk {
         // read first 4 data bytes and convert to bigendian bytes
         let data_length = Chunk::get_data_length(reader)?;
 
-        let mut b_data = vec![0; data_length];
+        if data_length > MAX_U31 as usize {
+            return Err(Box::new(ChunkError("Chunk length exceeds 2^31-1")));
+        }
+
+        let mut b_data = vec![];
         let mut b_type = [0; 4];
         let mut b_crc = [0; 4];
 
         // grab type bytes
         reader.read_exact(&mut b_type)?;
-        // grab data bytes
-        reader.read_exact(&mut b_data)?;
+        // grab data bytes, the buffer only grows as far as the input goes
+        reader.take(data_length as u64).read_to_end(&mut b_data)?;
+        if b_data.len() != data_length {
+            return Err(Box::new(ChunkError("Chunk is truncated")));
+        }
         // grab crc bytes
         reader.read_exact(&mut b_crc)?;
 
@@ -93,7 +130,7 @@ impl Chunk {
             .iter()
             .chain(self.chunk_type().bytes().iter())
             .chain(self.data.iter())
-            .chain((self.crc as u32).to_be_bytes().iter())
+            .chain(self.crc.to_be_bytes().iter())
             .copied()
             .collect()
     }
@@ -103,15 +140,9 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = io::BufReader::new(bytes);
+        let chunk = Chunk::from_bytes_unchecked(bytes)?;
 
-        let (b_type, b_data, b_crc) = Chunk::get_byte_sequences(&mut reader)?;
-
-        let chunk_type = ChunkType::try_from(b_type)?;
-
-        let chunk = Chunk::new(chunk_type, b_data);
-
-        if chunk.crc() != u32::from_be_bytes(b_crc) {
+        if !chunk.is_crc_valid() {
             return Err(Box::new(ChunkError("Crc does not match")));
         }
 
@@ -119,18 +150,40 @@ impl TryFrom<&[u8]> for Chunk {
     }
 }
 
-impl fmt::Display for Chunk {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Chunk {
+    /// The Display form with an extra line for a decoded value.
+    pub fn fmt_with_value(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        value: Option<&dyn fmt::Display>,
+    ) -> fmt::Result {
         writeln!(f, "Chunk {{",)?;
         writeln!(f, "  Length: {}", self.length())?;
         writeln!(f, "  Type: {}", self.chunk_type())?;
         writeln!(f, "  Data: {} bytes", self.data().len())?;
+        if let Some(value) = value {
+            writeln!(f, "  Value: {}", value)?;
+        }
         writeln!(f, "  Crc: {}", self.crc())?;
         writeln!(f, "}}",)?;
         Ok(())
     }
 }
 
+impl fmt::Display for Chunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_value(f, None)
+    }
+}
+
+/// Chunk of chunk_type holding data, shared by the tests of every module.
+#[cfg(test)]
+pub(crate) fn chunk_of(chunk_type: &str, data: &[u8]) -> Chunk {
+    use std::str::FromStr;
+
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +294,40 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_length_beyond_input() {
+        let mut bytes = vec![0x7F, 0xFF, 0xFF, 0xFF];
+        bytes.extend_from_slice(b"RuSt\0\0\0\0");
+        assert!(Chunk::from_bytes_unchecked(&bytes).is_err());
+
+        bytes[0] = 0xFF;
+        let error = Chunk::from_bytes_unchecked(&bytes).unwrap_err();
+        assert_eq!(error.to_string(), "Chunk length exceeds 2^31-1");
+    }
+
+    #[test]
+    fn test_unchecked_chunk_keeps_bad_crc() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        let crc: u32 = 2882656333;
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .chain(crc.to_be_bytes().iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::from_bytes_unchecked(chunk_data.as_ref()).unwrap();
+
+        assert_eq!(chunk.crc(), 2882656333);
+        assert!(!chunk.is_crc_valid());
+        assert_eq!(chunk.as_bytes(), chunk_data);
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
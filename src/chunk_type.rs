@@ -6,6 +6,22 @@ use std::str::FromStr;
 use crate::chunk_error::ChunkError;
 use crate::{Error, Result};
 
+/// Ancillary chunks that must appear before PLTE and IDAT.
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.Summary-of-standard-chunks
+pub const BEFORE_PLTE: [&str; 5] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB"];
+
+/// Ancillary chunks that must appear after PLTE and before IDAT.
+pub const AFTER_PLTE: [&str; 3] = ["bKGD", "hIST", "tRNS"];
+
+/// Ancillary chunks that must appear before IDAT.
+pub const BEFORE_IDAT: [&str; 3] = ["pHYs", "sPLT", "eXIf"];
+
+/// Ancillary chunks that may appear at most once.
+pub const SINGLE_INSTANCE: [&str; 11] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs", "tIME",
+    "eXIf",
+];
+
 /// A validated PNG chunk type. See the PNG spec for more details.
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Structure.html
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -72,6 +88,12 @@ impl FromStr for ChunkType {
 
         let bytes = chunk_str.as_bytes();
 
+        if !bytes.iter().all(ChunkType::is_valid_byte) {
+            return Err(Box::new(ChunkError(
+                "The string must only hold ASCII letters.",
+            )));
+        }
+
         if !ChunkType::is_reserved_bit_valid(bytes[2]) {
             return Err(Box::new(ChunkError(
                 "Error: Third character must be uppercase.",
@@ -171,6 +193,13 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_str_wrong_length_or_bytes() {
+        assert!(ChunkType::from_str("ruS").is_err());
+        assert!(ChunkType::from_str("ruSTx").is_err());
+        assert!(ChunkType::from_str("1uSt").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -1,16 +1,92 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgEnum, ArgGroup, Parser, Subcommand};
+
+use crate::ancillary::{RenderingIntent, Time};
+use crate::exif::IfdKind;
+use crate::strip::StripPreset;
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Command,
+    /// write results into this directory, mirroring the input tree
+    #[clap(long, global = true)]
+    pub output_dir: Option<String>,
+    /// number of files processed in parallel, defaults to one per core
+    #[clap(long, global = true)]
+    pub jobs: Option<usize>,
+    /// largest chunk data accepted, in bytes (default 64 MiB, at most 2^31-1)
+    #[clap(long, global = true)]
+    pub max_chunk_length: Option<u32>,
+    /// largest file accepted, in bytes (default 256 MiB)
+    #[clap(long, global = true)]
+    pub max_file_size: Option<u64>,
+    /// most chunks accepted in a single file (default 65536)
+    #[clap(long, global = true)]
+    pub max_chunks: Option<usize>,
+    /// largest decompressed image data accepted, in bytes (default 1 GiB)
+    #[clap(long, global = true)]
+    pub max_image_data: Option<u64>,
+    /// seconds an http(s) download may take in total (default 30)
+    #[clap(long, global = true)]
+    pub timeout: Option<u64>,
+    /// seconds to wait for an http(s) connection (default 10)
+    #[clap(long, global = true)]
+    pub connect_timeout: Option<u64>,
+    /// largest http(s) download, in bytes (defaults to --max-file-size)
+    #[clap(long, global = true)]
+    pub max_download_size: Option<u64>,
+    /// most redirects followed per download (default 5)
+    #[clap(long, global = true)]
+    pub max_redirects: Option<usize>,
+    /// only download from this host and its subdomains, repeatable
+    #[clap(long, global = true)]
+    pub allow_host: Vec<String>,
+    /// never download from this host and its subdomains, repeatable
+    #[clap(long, global = true)]
+    pub deny_host: Vec<String>,
+    /// extra request header "Name: value" for downloads, repeatable
+    #[clap(long, global = true)]
+    pub header: Vec<String>,
+    /// proxy url for downloads, HTTP(S)_PROXY is used otherwise
+    #[clap(long, global = true)]
+    pub proxy: Option<String>,
+    /// keep downloads in this directory and reuse them instead of refetching
+    #[clap(long, global = true)]
+    pub cache_dir: Option<String>,
+    /// seconds a cached download is reused before it is fetched again
+    /// (default 86400)
+    #[clap(long, global = true)]
+    pub cache_max_age: Option<u64>,
+    /// keep every replaced file next to the new one
+    #[clap(long, global = true)]
+    pub backup: bool,
+    /// suffix appended to the name of backups
+    #[clap(long, global = true, default_value = ".bak")]
+    pub backup_suffix: String,
+    /// show the planned chunk changes and resulting size without writing
+    #[clap(long, global = true)]
+    pub dry_run: bool,
+    /// re-parse written pngs and check payload and image data survived
+    #[clap(long, global = true)]
+    pub verify: bool,
+    /// write even if it invalidates or removes C2PA content credentials
+    #[clap(long, global = true)]
+    pub force: bool,
+}
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Yaml,
 }
 
 #[derive(Subcommand)]
 pub enum Command {
     /// Hide a message in a PNG file
     Encode {
-        /// relative path of png file
+        /// relative path of png file, directory or glob pattern
         file_path: String,
         /// 4 ascii character string chunk type ex. RuST
         chunk_type: String,
@@ -18,25 +94,342 @@ pub enum Command {
         message: String,
         /// optional write path for final png file
         output_path: Option<String>,
+        /// secret key file used to sign the message
+        #[clap(long)]
+        sign_key: Option<String>,
+        /// split the message into this many Shamir shares, one per carrier file
+        #[clap(long, requires = "threshold")]
+        shares: Option<u8>,
+        /// number of shares needed to reconstruct the message
+        #[clap(long, requires = "shares")]
+        threshold: Option<u8>,
+        /// protect the message with this many Reed-Solomon parity symbols
+        /// per 255 byte block (2-128, repairs up to half as many bad bytes)
+        #[clap(long)]
+        fec: Option<u8>,
+        /// overwrite an existing chunk of chunk_type in place instead of
+        /// adding another one
+        #[clap(long, conflicts_with_all = &["if-absent", "append"])]
+        replace: bool,
+        /// leave the file untouched if it already has a chunk of chunk_type
+        #[clap(long, conflicts_with = "append")]
+        if_absent: bool,
+        /// always add another chunk, the default
+        #[clap(long)]
+        append: bool,
+        /// with chunk type IDAT, scramble which pixels carry the message
+        /// with this key, decode needs the same key
+        #[clap(long)]
+        pixel_key: Option<String>,
+        /// with chunk type IDAT, matrix embed with this code parameter
+        /// (1-8): blocks of 2^K-1 pixel samples carry K bits with at most
+        /// one change, see capacity
+        #[clap(long)]
+        matrix: Option<u8>,
     },
     /// Find a message in a PNG file
     Decode {
-        /// relative path of png file
-        file_path: String,
+        /// relative paths of png files, directories or glob patterns
+        #[clap(required = true)]
+        file_paths: Vec<String>,
         /// 4 ascii character string chunk type ex. tEXT
         chunk_type: String,
+        /// public key file used to verify message signatures
+        #[clap(long)]
+        verify_key: Option<String>,
+        /// reconstruct a message split into shares across the matched files
+        #[clap(long)]
+        shares: bool,
+        /// key the message was hidden in the pixels with (chunk type IDAT)
+        #[clap(long)]
+        pixel_key: Option<String>,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
     },
 
     /// Remove a hidden message from a PNG file
     Remove {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// 4 ascii character string chunk type ex. RuST
+        chunk_type: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// same as output_path
+        #[clap(long, conflicts_with = "output-path")]
+        output: Option<String>,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Remove ancillary metadata (location, author, text...) from a PNG file
+    Strip {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// ancillary chunk type to keep in addition to gAMA, cHRM, sRGB,
+        /// iCCP, tRNS and pHYs
+        #[clap(long)]
+        keep: Vec<String>,
+        /// only remove these categories instead of every ancillary chunk
+        #[clap(long, arg_enum)]
+        preset: Vec<StripPreset>,
+    },
+    /// Copy ancillary chunks from one PNG file into another
+    Transplant {
+        /// relative path of the png file to copy chunks from
+        source_path: String,
+        /// relative path of png file, directory or glob pattern to copy into
+        file_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// also copy chunks that are not marked safe-to-copy
+        #[clap(long)]
+        include_unsafe: bool,
+    },
+    /// Create or edit standard ancillary chunks (pHYs, tIME, sRGB, gAMA,
+    /// cHRM, bKGD, sBIT, tRNS)
+    #[clap(group(ArgGroup::new("values").required(true).multiple(true)))]
+    Set {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// pixel density in dots per inch, stored in pHYs
+        #[clap(long, group = "values")]
+        dpi: Option<f64>,
+        /// last modification time, "now" or a UTC timestamp like
+        /// 2024-05-17T13:45:00Z, stored in tIME
+        #[clap(long, group = "values")]
+        time: Option<Time>,
+        /// rendering intent of an sRGB image, stored in sRGB
+        #[clap(long, arg_enum, group = "values")]
+        srgb: Option<RenderingIntent>,
+        /// image gamma, e.g. 0.45455 for a 2.2 display, stored in gAMA
+        #[clap(long, group = "values")]
+        gamma: Option<f64>,
+        /// white point and red, green and blue primaries as x,y pairs,
+        /// e.g. 0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06, stored in cHRM
+        #[clap(
+            long,
+            group = "values",
+            use_value_delimiter = true,
+            require_value_delimiter = true,
+            number_of_values = 8
+        )]
+        chrm: Vec<f64>,
+        /// background color: a gray value, red,green,blue or a palette
+        /// index depending on the color type, stored in bKGD
+        #[clap(
+            long,
+            group = "values",
+            use_value_delimiter = true,
+            require_value_delimiter = true
+        )]
+        background: Vec<u16>,
+        /// significant bits of every channel, e.g. 5,6,5, stored in sBIT
+        #[clap(
+            long,
+            group = "values",
+            use_value_delimiter = true,
+            require_value_delimiter = true
+        )]
+        sbit: Vec<u16>,
+        /// transparent color: a gray value, red,green,blue or the alpha of
+        /// every palette entry depending on the color type, stored in tRNS
+        #[clap(
+            long,
+            group = "values",
+            use_value_delimiter = true,
+            require_value_delimiter = true
+        )]
+        transparent: Vec<u16>,
+        /// remove an iCCP chunk instead of refusing to set sRGB, the two
+        /// must not both be present
+        #[clap(long)]
+        remove_iccp: bool,
+    },
+    /// List or remove the EXIF tags of a PNG file's eXIf chunk
+    Exif {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// optional write path for final png file, when removing tags
+        output_path: Option<String>,
+        /// tag name (e.g. GPSLatitude) or number (e.g. 0x0112) to remove,
+        /// repeatable
+        #[clap(long)]
+        remove_tag: Vec<String>,
+        /// IFD to remove along with all its tags, repeatable
+        #[clap(long, arg_enum)]
+        remove_ifd: Vec<IfdKind>,
+        /// output format when listing, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Show how much a PNG file's pixels carry and how many change per matrix code
+    Capacity {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// message length in bytes to estimate the changed samples for
+        #[clap(long)]
+        length: Option<usize>,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// List the C2PA manifests, assertions and claim signatures of a PNG file
+    C2pa {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Write the ICC color profile of a PNG file to an .icc file
+    ExtractIcc {
         /// relative path of png file
         file_path: String,
+        /// write path for the decompressed profile
+        output_path: String,
+    },
+    /// Embed an .icc file as the color profile of a PNG file
+    EmbedIcc {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// ICC profile to embed
+        profile_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// name stored with the profile
+        #[clap(long, default_value = "ICC profile")]
+        name: String,
+        /// remove an sRGB chunk instead of refusing to embed, the two
+        /// must not both be present
+        #[clap(long)]
+        remove_srgb: bool,
+    },
+    /// Read, edit, replace or delete the XMP packet of a PNG file
+    Xmp {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// optional write path for final png file, when editing
+        output_path: Option<String>,
+        /// property to print (e.g. dc:creator), repeatable
+        #[clap(long, conflicts_with_all = &["set", "replace", "delete"])]
+        get: Vec<String>,
+        /// property to set as PROPERTY=VALUE (e.g. xmp:CreatorTool=pngsneak),
+        /// repeatable
+        #[clap(long)]
+        set: Vec<String>,
+        /// file holding a packet to store instead of the existing one
+        #[clap(long)]
+        replace: Option<String>,
+        /// remove the XMP packet
+        #[clap(long, conflicts_with_all = &["set", "replace"])]
+        delete: bool,
+        /// print the packet as stored instead of indented
+        #[clap(long)]
+        raw: bool,
+    },
+    /// Show the data of a chunk as a hex dump or raw bytes
+    Cat {
+        /// relative path of png file
+        file_path: String,
+        /// 4 ascii character string chunk type ex. RuST
+        chunk_type: String,
+        /// which chunk of that type, counting from 0
+        #[clap(long, default_value = "0")]
+        index: usize,
+        /// write the raw bytes to stdout instead of a hex dump
+        #[clap(long)]
+        raw: bool,
+        /// write the raw bytes to this file instead
+        #[clap(long)]
+        output: Option<String>,
+    },
+    /// Add a chunk holding the raw contents of a file
+    Inject {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
         /// 4 ascii character string chunk type ex. RuST
         chunk_type: String,
+        /// file whose bytes become the chunk data
+        data_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+    },
+    /// Check a PNG file against the PNG specification
+    Lint {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// fail on warnings as well as errors
+        #[clap(long)]
+        strict: bool,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Compare the chunks of two PNG files
+    Diff {
+        /// relative path of the original png file
+        left_path: String,
+        /// relative path of the changed png file
+        right_path: String,
+        /// output format
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
     },
     /// print the contents of a PNG file
     Print {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// output format, see docs/output.md for the json/yaml schema
+        #[clap(long, arg_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Generate an Ed25519 keypair for signing messages
+    Keygen {
+        /// write path for the secret key, public key is written to <path>.pub
+        key_path: String,
+    },
+    /// print the fingerprint of a signing key
+    Fingerprint {
+        /// path of a public (.pub) or secret key file
+        key_path: String,
+    },
+    /// Sign the image data of a PNG file
+    Sign {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// secret key file used to sign the image
+        key_path: String,
+        /// optional write path for final png file
+        output_path: Option<String>,
+        /// ancillary chunk type to cover in addition to critical chunks
+        #[clap(long)]
+        include: Vec<String>,
+    },
+    /// Check the image signature of a PNG file
+    Verify {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+        /// public key file used to verify the image signature
+        key_path: String,
+    },
+    /// List the frames of an animated PNG file
+    Frames {
+        /// relative path of png file, directory or glob pattern
+        file_path: String,
+    },
+    /// Write a single frame of an animated PNG file as a standalone PNG
+    ExtractFrame {
         /// relative path of png file
         file_path: String,
+        /// zero based frame index
+        frame_index: usize,
+        /// write path for the frame png file
+        output_path: String,
     },
 }
@@ -1,18 +1,42 @@
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::str::FromStr;
 use std::{error, fmt, fs, io};
 
+use crate::ancillary::{self, Ancillary, IccProfile};
+use crate::apng::{self, Animation};
+use crate::batch;
+use crate::c2pa::{self, C2paReport, ManifestStore};
 use crate::chunk::Chunk;
 use crate::chunk_type::ChunkType;
-use crate::file_type::FileType;
+use crate::cli::OutputFormat;
+use crate::diff::PngDiff;
+use crate::exif::{Exif, ExifReport, IfdKind};
+use crate::fec;
+use crate::file_type::{self, FileType};
+use crate::hexdump;
+use crate::http::{self, HttpOptions};
+use crate::limits::{self, Limits};
+use crate::lint::{self, Severity};
+use crate::pixels::{self, CapacityReport, Pixels};
 use crate::png::Png;
+use crate::report::{self, DecodeReport, MessageReport, PngReport, RemoveReport};
+use crate::shamir::{self, Share};
+use crate::signature::{self, SignatureStatus};
+use crate::strip::{self, StripPreset};
+use crate::transplant;
+use crate::verify::{self, Payload};
+use crate::write::{self, WriteOptions};
+use crate::xmp;
 use crate::Result;
-use reqwest::blocking::get;
 
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum CommandErrors {
     Encode(&'static str),
     Decode(&'static str),
+    Verify(&'static str),
+    Batch(&'static str),
+    Diff(&'static str),
 }
 
 impl error::Error for CommandErrors {}
@@ -22,112 +46,925 @@ impl fmt::Display for CommandErrors {
         let error = match self {
             CommandErrors::Encode(msg) => msg,
             CommandErrors::Decode(msg) => msg,
+            CommandErrors::Verify(msg) => msg,
+            CommandErrors::Batch(msg) => msg,
+            CommandErrors::Diff(msg) => msg,
         };
 
         write!(f, "{}", error)
     }
 }
 
+/// What embedding does when the png already has a chunk of the type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeMode {
+    /// add another chunk
+    #[default]
+    Append,
+    /// overwrite the first chunk in place and drop the others
+    Replace,
+    /// leave the png untouched
+    IfAbsent,
+}
+
+impl EncodeMode {
+    pub fn from_flags(replace: bool, if_absent: bool) -> EncodeMode {
+        match (replace, if_absent) {
+            (true, _) => EncodeMode::Replace,
+            (_, true) => EncodeMode::IfAbsent,
+            _ => EncodeMode::Append,
+        }
+    }
+}
+
+/// Optional behaviour of commands that embed a payload.
+#[derive(Debug, Default, Clone)]
+pub struct EncodeOptions {
+    /// secret key file used to sign the payload chunk
+    pub sign_key: Option<String>,
+    /// Reed-Solomon parity symbols per 255 byte block
+    pub fec_parity: Option<u8>,
+    pub mode: EncodeMode,
+    /// key that scrambles which pixels carry a pixel payload
+    pub pixel_key: Option<String>,
+    /// matrix code parameter of a pixel payload, plain LSB if None
+    pub matrix: Option<u8>,
+}
+
+/// Standard ancillary chunks the set command writes.
+#[derive(Debug, Default, Clone)]
+pub struct SetOptions {
+    /// values that are the same for every image
+    pub values: Vec<Ancillary>,
+    /// bKGD, sBIT and tRNS samples, laid out by the color type of
+    /// each image
+    pub samples: Vec<(&'static str, Vec<u16>)>,
+    /// remove an iCCP chunk instead of refusing to set sRGB
+    pub remove_iccp: bool,
+}
+
+/// What the xmp command does, listing the packet when nothing is set.
+#[derive(Debug, Default, Clone)]
+pub struct XmpOptions {
+    /// properties to print, e.g. dc:creator
+    pub get: Vec<String>,
+    /// PROPERTY=VALUE pairs to set
+    pub set: Vec<String>,
+    /// file holding a packet that replaces the existing one
+    pub replace: Option<String>,
+    pub delete: bool,
+    /// print the packet as stored instead of indented
+    pub raw: bool,
+}
+
 #[derive(Debug)]
 pub struct Commands {}
 
+// every command receives its inputs and options as arguments
+#[allow(clippy::too_many_arguments)]
 impl Commands {
     /// Writes the altered png contents to disk.
     ///
     /// Location is output_path if defined or file_path
-    /// of original png file.
-    fn write_file(file_path: String, contents: Vec<u8>) -> io::Result<()> {
-        let path_buf = PathBuf::from(file_path);
-        fs::write(path_buf, contents)
-    }
+    /// of original png file. Files are replaced atomically, see
+    /// write::write_atomic. `-` writes the bytes to stdout
+    /// and a data URI prints them as one.
+    fn write_file(
+        file_path: String,
+        contents: Vec<u8>,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        if write_options.dry_run {
+            return Ok(());
+        }
+
+        match FileType::from(file_path.clone()) {
+            FileType::Local(path) => {
+                write::write_atomic(&path, &contents, write_options)?
+            }
+            FileType::Stdio => io::stdout().write_all(&contents)?,
+            FileType::DataUri(_) => {
+                println!("{}", file_type::encode_data_uri(&file_path, &contents))
+            }
+            FileType::Url(_) => {
+                return Err(Box::new(CommandErrors::Encode(
+                    "cannot write to a url, pass a local output_path.",
+                )))
+            }
+        }
 
-    fn convert_to_4_byte_array(val: &String) -> [u8; 4] {
-        let bytes = val.as_bytes();
-        [bytes[0], bytes[1], bytes[2], bytes[3]]
+        Ok(())
     }
 
-    /// Converts an http/https url to a Png struct.
+    /// Writes the altered png to write_path, see write_file.
+    ///
+    /// original is the png the command started from, None if the
+    /// output is a new image. payload is the chunk type and data a
+    /// command embedded.
     ///
+    /// With --dry-run only the planned chunk changes and resulting
+    /// size are printed. With --verify the written file is read back
+    /// and checked, see verify::verify_written.
+    fn write_png(
+        original: Option<&Png>,
+        png: &Png,
+        write_path: &str,
+        payload: Option<Payload>,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let bytes = png.as_bytes();
+
+        let warning =
+            original.and_then(|original| c2pa::binding_warning(original, png));
+
+        if let (Some(warning), false, false) =
+            (warning, write_options.dry_run, write_options.force)
+        {
+            eprintln!(
+                "Refusing to write {}: it {}. Pass --force to write anyway.",
+                file_type::output_name(write_path),
+                warning
+            );
+            return Err(Box::new(CommandErrors::Encode(
+                "write would break C2PA content credentials.",
+            )));
+        }
+
+        if let Some(warning) = warning.filter(|_| write_options.force) {
+            eprintln!(
+                "Warning: writing {} {}",
+                file_type::output_name(write_path),
+                warning
+            );
+        }
+
+        if write_options.dry_run {
+            let mut plan = match original {
+                Some(original) => format!(
+                    "{}\nResulting size: {} bytes (was {})",
+                    PngDiff::new(write_path, original, write_path, png)?,
+                    bytes.len(),
+                    original.as_bytes().len()
+                ),
+                None => format!(
+                    "New png with {} chunk(s)\nResulting size: {} bytes",
+                    png.chunks.len(),
+                    bytes.len()
+                ),
+            };
+            if let Some(warning) = warning {
+                plan.push_str(&format!(
+                    "\nWarning: this {}, writing needs --force",
+                    warning
+                ));
+            }
+            Self::status(
+                write_path,
+                format!(
+                    "Planned changes to {}:\n{}",
+                    file_type::output_name(write_path),
+                    plan
+                ),
+                write_options,
+            );
+            return Ok(());
+        }
+
+        Self::write_file(write_path.to_string(), bytes.clone(), write_options)?;
+
+        if write_options.verify {
+            // files are read back, anything printed is checked as written
+            let written = match FileType::from(write_path.to_string()) {
+                FileType::Local(path) => fs::read(path)?,
+                _ => bytes,
+            };
+
+            verify::verify_written(&written, original, payload, limits)?;
+            Self::status(
+                write_path,
+                format!("Verified {}", file_type::output_name(write_path)),
+                write_options,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Prints a status line about a written file, on stderr
+    /// when the file itself went to stdout.
     ///
-    fn convert_url_to_png(url: &String) -> Result<Png> {
-        println!("\nGET to ------> {}", url);
-        let bytes = get(url)?.bytes()?.to_vec();
-        Png::try_from(bytes.as_slice())
+    /// Dry runs mark the line, nothing was written.
+    fn status(write_path: &str, message: String, write_options: &WriteOptions) {
+        let message = if write_options.dry_run {
+            format!("[dry run] {}", message)
+        } else {
+            message
+        };
+
+        if FileType::from(write_path.to_string()).is_stdout() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
     }
 
-    /// Convert passed file_path arg to a Png.
+    /// Reads everything from reader, failing once it passes the
+    /// max file size instead of buffering an unbounded input.
+    fn read_limited<R: Read>(reader: R, limits: &Limits) -> Result<Vec<u8>> {
+        limits::read_limited(reader, limits.max_file_size)
+    }
+
+    /// Reads the raw bytes behind the passed file_path arg.
     ///
-    /// Accepts local and absolute system file paths
-    /// as well as http/https urls.
+    /// Accepts local and absolute system file paths, file://
+    /// and http/https urls, data URIs and `-` for stdin.
     ///
     /// Not Async -> TODO: Make this async
-    fn convert_to_png(file_path: &String) -> Result<Png> {
+    fn read_bytes(
+        file_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<Vec<u8>> {
         match FileType::from(file_path.to_string()) {
-            FileType::Url(url) => Self::convert_url_to_png(&url),
-            FileType::Local(file) => Png::try_from(file),
+            FileType::Url(url) => http::fetch(&url, http_options, limits),
+            FileType::Local(file) => {
+                Self::read_limited(fs::File::open(file)?, limits)
+            }
+            FileType::Stdio => Self::read_limited(io::stdin().lock(), limits),
+            FileType::DataUri(uri) => Self::read_limited(
+                file_type::decode_data_uri(&uri)?.as_slice(),
+                limits,
+            ),
         }
     }
 
+    /// Convert passed file_path arg to a Png.
+    fn convert_to_png(
+        file_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<Png> {
+        Png::try_from_limited(
+            &Self::read_bytes(file_path, http_options, limits)?,
+            limits,
+        )
+    }
+
+    /// Convert passed file_path arg to a Png, keeping chunks
+    /// with a bad crc so damaged payloads can still be read.
+    fn convert_to_png_lenient(
+        file_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<Png> {
+        Png::from_bytes_lenient(
+            &Self::read_bytes(file_path, http_options, limits)?,
+            limits,
+        )
+    }
+
     /// Encodes the passed message into the png file
     /// located at the file_path arg.
     ///
     /// Writes the altered png file to disk at
     /// the passed output_path (if defined) or
     /// the original file_path.
+    ///
+    /// When options.sign_key is defined a detached signature chunk
+    /// is appended directly after the message chunk.
+    ///
+    /// When options.fec_parity is defined the message is protected
+    /// with Reed-Solomon parity symbols before it is embedded.
+    ///
+    /// options.mode decides what happens to an existing chunk of
+    /// chunk_type: another one is added (Append), it is overwritten
+    /// in place (Replace) or the file is left alone (IfAbsent).
     pub fn encode(
-        file_path: &String,
-        chunk_type: &String,
-        message: &String,
+        file_path: &str,
+        chunk_type: &str,
+        message: &str,
         output_path: &Option<String>,
+        options: &EncodeOptions,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
     ) -> Result<()> {
-        let mut png = Self::convert_to_png(file_path)?;
+        let data = message.as_bytes().to_vec();
+
+        if chunk_type == pixels::PIXEL_CHUNK_TYPE {
+            return Self::embed_pixels(
+                file_path,
+                data,
+                output_path,
+                options,
+                http_options,
+                limits,
+                write_options,
+            );
+        }
+        if options.pixel_key.is_some() || options.matrix.is_some() {
+            return Err(Box::new(CommandErrors::Encode(
+                "--pixel-key and --matrix only apply to chunk type IDAT.",
+            )));
+        }
+
+        Self::embed_chunk(
+            file_path,
+            chunk_type,
+            data,
+            output_path,
+            options,
+            http_options,
+            limits,
+            write_options,
+        )?;
+        Ok(())
+    }
+
+    /// Hides data in the least significant bits of the pixels, in an
+    /// order scrambled by options.pixel_key if defined, matrix embedded
+    /// with options.matrix to change fewer of them.
+    ///
+    /// The whole payload is replaced on every run, so options.mode has
+    /// no effect. Writes the altered png file to disk at the passed
+    /// output_path (if defined) or the original file_path.
+    fn embed_pixels(
+        file_path: &str,
+        data: Vec<u8>,
+        output_path: &Option<String>,
+        options: &EncodeOptions,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        if options.sign_key.is_some() {
+            return Err(Box::new(CommandErrors::Encode(
+                "Messages hidden in the pixels can't be signed.",
+            )));
+        }
+
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+        let key = options.pixel_key.as_deref();
 
-        let b_chunk_type = Self::convert_to_4_byte_array(&chunk_type);
-        let chunk_type = ChunkType::try_from(b_chunk_type)?;
-        let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
+        let encoded = match options.fec_parity {
+            Some(parity) => fec::encode(&data, parity)?,
+            None => data.clone(),
+        };
+
+        let mut pixels = Pixels::from_png(&png, limits)?;
+        pixels.embed(&encoded, key, options.matrix.unwrap_or(1))?;
+        pixels.write_to(&mut png)?;
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            Some(Payload::Pixels(&data, key)),
+            limits,
+            write_options,
+        )
+    }
+
+    /// Adds a chunk holding data to the png file as options.mode
+    /// says and writes it to output_path (if defined) or file_path.
+    ///
+    /// Returns false if nothing was written because the png already
+    /// has a chunk of chunk_type and the mode is IfAbsent.
+    fn embed_chunk(
+        file_path: &str,
+        chunk_type: &str,
+        data: Vec<u8>,
+        output_path: &Option<String>,
+        options: &EncodeOptions,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<bool> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+
+        if apng::is_apng_chunk_type(chunk_type) {
+            return Err(Box::new(CommandErrors::Encode(
+                "acTL, fcTL and fdAT are reserved for animation frames.",
+            )));
+        }
+
+        let parsed_type = ChunkType::from_str(chunk_type)?;
+
+        // an extra IHDR, IDAT or IEND (or an unknown critical chunk)
+        // makes decoders reject the whole image
+        if parsed_type.is_critical() {
+            return Err(Box::new(CommandErrors::Encode(
+                "critical chunk types can't hold data, use an ancillary (lowercase first letter) type.",
+            )));
+        }
 
-        png.append_chunk(chunk);
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        let exists = !png.chunk_by_type(chunk_type).is_empty();
+
+        if exists && options.mode == EncodeMode::IfAbsent {
+            Self::status(
+                write_path,
+                format!(
+                    "{} already has a {} chunk, left unchanged",
+                    file_type::display_name(file_path),
+                    chunk_type
+                ),
+                write_options,
+            );
+            return Ok(false);
+        }
+
+        let encoded = match options.fec_parity {
+            Some(parity) => fec::encode(&data, parity)?,
+            None => data.clone(),
+        };
+
+        // signatures cover the payload, not its parity, so a payload
+        // repaired on decode still verifies
+        let signature_chunk = match &options.sign_key {
+            Some(key_path) => {
+                let key = signature::read_signing_key(key_path)?;
+                let signed = Chunk::new(parsed_type.clone(), data.clone());
+                Some(signature::sign_chunk(&key, &signed)?)
+            }
+            None => None,
+        };
+
+        let chunk = Chunk::new(parsed_type.clone(), encoded);
+
+        let replaced = if exists && options.mode == EncodeMode::Replace {
+            // signatures of the old payload would no longer verify
+            png.chunks
+                .retain(|chunk| !signature::is_signature_for(chunk, &parsed_type));
+            png.replace_chunk(chunk)
+        } else {
+            png.append_chunk(chunk);
+            None
+        };
+
+        if let Some(signature_chunk) = signature_chunk {
+            match replaced {
+                Some(idx) => png.chunks.insert(idx + 1, signature_chunk),
+                None => png.append_chunk(signature_chunk),
+            }
+        }
+
+        if png.is_animated() {
+            Animation::validate(&png)?;
+        }
+
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            Some(Payload::Chunk(chunk_type, &data)),
+            limits,
+            write_options,
+        )?;
+        Ok(true)
+    }
+
+    /// Creates a chunk holding the raw contents of data_path and
+    /// appends it to the png file.
+    ///
+    /// Writes the altered png file to disk at the passed
+    /// output_path (if defined) or the original file_path.
+    pub fn inject(
+        file_path: &str,
+        chunk_type: &str,
+        data_path: &str,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let data = Self::read_bytes(data_path, http_options, limits)?;
+        let length = data.len();
+
+        Self::embed_chunk(
+            file_path,
+            chunk_type,
+            data,
+            output_path,
+            &EncodeOptions::default(),
+            http_options,
+            limits,
+            write_options,
+        )?;
+
+        Self::status(
+            output_path.as_deref().unwrap_or(file_path),
+            format!(
+                "Injected {} bytes from {} as {}",
+                length,
+                file_type::display_name(data_path),
+                chunk_type
+            ),
+            write_options,
+        );
 
-        let write_path = output_path.as_ref().unwrap_or(file_path);
-        Self::write_file(write_path.to_string(), png.as_bytes())?;
         Ok(())
     }
 
     /// Searches a png file for a specific message type (ex. tEXt).
     ///
     /// Prints the text contained in each chunk or not found message.
-    pub fn decode(file_path: &String, chunk_type: &String) -> Result<()> {
-        let png = Self::convert_to_png(file_path)?;
+    ///
+    /// When verify_key is defined each message is prefixed with its
+    /// signature status (valid, invalid or unsigned).
+    ///
+    /// Error corrected payloads are repaired transparently and
+    /// prefixed with the number of repaired symbols. Chunks with
+    /// a bad crc are still read.
+    pub fn decode(
+        file_path: &str,
+        chunk_type: &str,
+        verify_key: &Option<String>,
+        pixel_key: &Option<String>,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let png = Self::convert_to_png_lenient(file_path, http_options, limits)?;
 
-        let found = png.chunk_by_type(chunk_type.as_str());
+        if chunk_type == pixels::PIXEL_CHUNK_TYPE {
+            let message = Self::decode_pixels(&png, pixel_key, limits)?;
+            return Self::print_messages(
+                file_path,
+                chunk_type,
+                vec![message],
+                format,
+            );
+        }
+        if pixel_key.is_some() {
+            return Err(Box::new(CommandErrors::Decode(
+                "--pixel-key only applies to chunk type IDAT.",
+            )));
+        }
+
+        let found = png
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type)
+            .collect::<Vec<(usize, &Chunk)>>();
 
         if found.is_empty() {
             return Err(Box::new(CommandErrors::Decode("chunk_type not found.")));
         }
 
+        let key = match verify_key {
+            Some(key_path) => Some(signature::read_verifying_key(key_path)?),
+            None => None,
+        };
+
         let messages = found
             .iter()
-            .map(|&chunk| chunk.data_as_string().unwrap())
-            .collect::<Vec<String>>();
+            .map(|&(index, chunk)| {
+                let mut report = MessageReport {
+                    index,
+                    message: None,
+                    signature: None,
+                    repaired: None,
+                    crc_valid: chunk.is_crc_valid(),
+                    error: None,
+                };
+
+                // binary payloads fail this message, not the whole report
+                let decoded = fec::decode_if_encoded(chunk.data());
+
+                // signatures cover the repaired payload, see embed_chunk
+                report.signature = key.as_ref().map(|key| {
+                    let payload = match &decoded {
+                        Ok((payload, _)) => payload.clone(),
+                        Err(_) => chunk.data().to_vec(),
+                    };
+                    let signed = Chunk::new(chunk.chunk_type().clone(), payload);
+                    signature::verify_chunk(&png, &signed, key)
+                });
+
+                let decoded =
+                    decoded.and_then(|(payload, repaired)| match repaired {
+                        Some(_) => Ok((String::from_utf8(payload)?, repaired)),
+                        None => Ok((chunk.data_as_string()?, None)),
+                    });
+
+                match decoded {
+                    Ok((message, repaired)) => {
+                        report.message = Some(message);
+                        report.repaired = repaired;
+                    }
+                    Err(error) => report.error = Some(error.to_string()),
+                }
+
+                report
+            })
+            .collect::<Vec<MessageReport>>();
+
+        Self::print_messages(file_path, chunk_type, messages, format)
+    }
+
+    /// Reads a message hidden in the pixels by encode with chunk type
+    /// IDAT, selecting the pixels with pixel_key.
+    fn decode_pixels(
+        png: &Png,
+        pixel_key: &Option<String>,
+        limits: &Limits,
+    ) -> Result<MessageReport> {
+        let mut report = MessageReport {
+            index: png
+                .chunks
+                .iter()
+                .position(|chunk| {
+                    chunk.chunk_type().to_string() == pixels::PIXEL_CHUNK_TYPE
+                })
+                .unwrap_or_default(),
+            message: None,
+            signature: None,
+            repaired: None,
+            crc_valid: true,
+            error: None,
+        };
+
+        let payload = Pixels::from_png(png, limits)
+            .and_then(|pixels| pixels.extract(pixel_key.as_deref()))
+            .and_then(|payload| fec::decode_if_encoded(&payload));
+
+        match payload {
+            Ok((payload, repaired)) => match String::from_utf8(payload) {
+                Ok(message) => {
+                    report.message = Some(message);
+                    report.repaired = repaired;
+                }
+                Err(_) => {
+                    report.error = Some(
+                        "No message found in the pixels, is the key right?"
+                            .to_string(),
+                    )
+                }
+            },
+            Err(error) => report.error = Some(error.to_string()),
+        }
+
+        Ok(report)
+    }
+
+    /// Prints decoded messages and fails if any could not be recovered
+    /// or has an invalid signature.
+    fn print_messages(
+        file_path: &str,
+        chunk_type: &str,
+        messages: Vec<MessageReport>,
+        format: OutputFormat,
+    ) -> Result<()> {
+        match format {
+            OutputFormat::Text => println!(
+                "Chunk type decoded as the following message: \n{}",
+                messages
+                    .iter()
+                    .map(|message| message.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            ),
+            _ => println!(
+                "{}",
+                report::render(
+                    &DecodeReport {
+                        schema_version: report::SCHEMA_VERSION,
+                        path: file_path.to_string(),
+                        chunk_type: chunk_type.to_string(),
+                        messages: messages.clone(),
+                    },
+                    format
+                )?
+            ),
+        }
+
+        if messages.iter().any(|message| message.error.is_some()) {
+            return Err(Box::new(CommandErrors::Decode(
+                "payload could not be recovered.",
+            )));
+        }
+
+        if messages
+            .iter()
+            .any(|message| message.signature == Some(SignatureStatus::Invalid))
+        {
+            return Err(Box::new(CommandErrors::Decode(
+                "signature verification failed.",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Splits the message into share_count Shamir shares, any
+    /// threshold of which recover it.
+    ///
+    /// file_path must name exactly share_count carrier pngs
+    /// (directory or glob), each receives one share as a chunk.
+    /// Carriers are written to output_dir (mirroring the input
+    /// tree) if defined or overwritten in place.
+    pub fn encode_shares(
+        file_path: &str,
+        chunk_type: &str,
+        message: &str,
+        share_count: u8,
+        threshold: u8,
+        output_dir: &Option<String>,
+        options: &EncodeOptions,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        if chunk_type == pixels::PIXEL_CHUNK_TYPE {
+            return Err(Box::new(CommandErrors::Encode(
+                "Shares are stored in chunks, not in the pixels.",
+            )));
+        }
+        if options.pixel_key.is_some() || options.matrix.is_some() {
+            return Err(Box::new(CommandErrors::Encode(
+                "--pixel-key and --matrix only apply to chunk type IDAT.",
+            )));
+        }
+
+        let carriers = batch::collect_files(file_path)?;
+
+        if carriers.len() != share_count as usize {
+            return Err(Box::new(CommandErrors::Encode(
+                "the number of carrier files must match --shares.",
+            )));
+        }
+
+        let shares = shamir::split(message.as_bytes(), share_count, threshold)?;
+
+        for (carrier, share) in carriers.iter().zip(shares.iter()) {
+            let output_path = batch::mirrored_output(output_dir, carrier)?;
+            let written = Self::embed_chunk(
+                &carrier.path.to_string_lossy(),
+                chunk_type,
+                share.as_bytes(),
+                &output_path,
+                options,
+                http_options,
+                limits,
+                write_options,
+            )?;
+            if !written {
+                continue;
+            }
+            let write_path = output_path
+                .unwrap_or_else(|| carrier.path.to_string_lossy().to_string());
+            Self::status(
+                &write_path,
+                format!(
+                    "Share {} written to {}",
+                    share.index,
+                    file_type::output_name(&write_path)
+                ),
+                write_options,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Collects the shares stored in chunk_type chunks of every
+    /// png named by file_paths (files, directories or globs) and prints
+    /// the reconstructed message.
+    ///
+    /// Error corrected shares are repaired first. Files that can't be
+    /// read, chunks that are not valid shares (corrupted ones included)
+    /// and shares that disagree with the rest of their set are skipped,
+    /// the message is reconstructed as long as enough valid shares remain.
+    pub fn decode_shares(
+        file_paths: &[String],
+        chunk_type: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let mut shares = vec![];
+        let mut skipped = 0;
+        let mut unreadable = 0;
+
+        let mut files = vec![];
+        for file_path in file_paths {
+            files.extend(batch::collect_files(file_path)?);
+        }
+
+        for file in files {
+            let png = match Self::convert_to_png_lenient(
+                &file.path.to_string_lossy(),
+                http_options,
+                limits,
+            ) {
+                Ok(png) => png,
+                Err(error) => {
+                    eprintln!("Skipping {}: {}", file.path.display(), error);
+                    unreadable += 1;
+                    continue;
+                }
+            };
+
+            for chunk in png.chunk_by_type(chunk_type) {
+                let share = fec::decode_if_encoded(chunk.data())
+                    .and_then(|(data, _)| Share::try_from(data.as_slice()));
+
+                match share {
+                    Ok(share) => shares.push(share),
+                    Err(_) => skipped += 1,
+                }
+            }
+        }
+
+        eprintln!(
+            "Found {} share(s), skipped {} invalid chunk(s) and {} unreadable file(s)",
+            shares.len(),
+            skipped,
+            unreadable
+        );
+
+        let (secret, dropped) = shamir::combine(&shares)?;
+
+        if dropped > 0 {
+            eprintln!(
+                "Dropped {} share(s) that disagree with their set on threshold or length",
+                dropped
+            );
+        }
 
         println!(
-            "Chunk type decoded as the following message: \n{}",
-            messages.as_slice().join("\n")
+            "Shares reconstructed as the following message: \n{}",
+            String::from_utf8(secret)?
         );
 
         Ok(())
     }
 
-    /// Removes a specific chunk type from the png file and
-    /// overwrites the original file.
+    /// Removes a specific chunk type from the png file.
+    ///
+    /// Writes the altered png file to disk at
+    /// the passed output_path (if defined) or
+    /// the original file_path.
     ///
     /// Prints removed chunk or not found message.
-    pub fn remove(file_path: &String, chunk_type: &String) -> Result<()> {
-        let mut png = Self::convert_to_png(file_path)?;
+    pub fn remove(
+        file_path: &str,
+        chunk_type: &str,
+        output_path: &Option<String>,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+
+        if apng::is_apng_chunk_type(chunk_type) {
+            return Err(Box::new(CommandErrors::Encode(
+                "removing acTL, fcTL or fdAT would break the animation.",
+            )));
+        }
+
+        let removed = report::chunk_reports(&png)
+            .into_iter()
+            .find(|chunk| chunk.chunk_type == chunk_type);
 
         if let Some(chunk) = png.remove_chunk(chunk_type) {
-            Self::write_file(file_path.to_string(), png.as_bytes())?;
-            println!("Removed the following chunk:\n{}", chunk.to_string());
+            let write_path = output_path.as_deref().unwrap_or(file_path);
+            Self::write_png(
+                Some(&original),
+                &png,
+                write_path,
+                None,
+                limits,
+                write_options,
+            )?;
+
+            match (format, removed) {
+                (OutputFormat::Text, _) | (_, None) => Self::status(
+                    write_path,
+                    format!("Removed the following chunk:\n{}", chunk),
+                    write_options,
+                ),
+                (_, Some(removed)) => Self::status(
+                    write_path,
+                    report::render(
+                        &RemoveReport {
+                            schema_version: report::SCHEMA_VERSION,
+                            path: file_path.to_string(),
+                            output_path: write_path.to_string(),
+                            removed,
+                        },
+                        format,
+                    )?,
+                    write_options,
+                ),
+            }
         } else {
             return Err(Box::new(CommandErrors::Decode("chunk_type not found.")));
         }
@@ -135,18 +972,829 @@ impl Commands {
         Ok(())
     }
 
+    /// Removes ancillary metadata chunks from the png file.
+    ///
+    /// Critical chunks are never removed. Without presets every
+    /// ancillary chunk outside the allowlist (gAMA, cHRM, sRGB, iCCP,
+    /// tRNS, pHYs and keep) is removed, with presets only the chunks
+    /// they name.
+    ///
+    /// Writes the altered png file to disk at the passed output_path
+    /// (if defined) or the original file_path and prints a report of
+    /// the removed chunks.
+    pub fn strip(
+        file_path: &str,
+        keep: &[String],
+        presets: &[StripPreset],
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+        let keep = strip::keep_list(keep)?;
+
+        let removed = strip::strip(&mut png, &keep, presets);
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        let report = removed
+            .iter()
+            .map(|chunk| {
+                format!("  {} ({} bytes)", chunk.chunk_type(), chunk.length())
+            })
+            .collect::<Vec<String>>();
+
+        Self::status(
+            write_path,
+            format!(
+                "Stripped {} chunk(s) from {}{}{}",
+                removed.len(),
+                file_type::display_name(file_path),
+                if report.is_empty() { "" } else { ":\n" },
+                report.join("\n")
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Copies the ancillary chunks of the png at source_path into
+    /// the png at file_path.
+    ///
+    /// Only safe-to-copy chunks are copied unless include_unsafe
+    /// is set, as those may depend on the image data.
+    ///
+    /// Writes the altered png file to disk at the passed output_path
+    /// (if defined) or the original file_path.
+    pub fn transplant(
+        source_path: &str,
+        file_path: &str,
+        include_unsafe: bool,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let source = Self::convert_to_png(source_path, http_options, limits)?;
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+
+        let copied = transplant::transplant(&source, &mut png, include_unsafe);
+
+        if png.is_animated() {
+            Animation::validate(&png)?;
+        }
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        let chunk_types = copied
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect::<Vec<String>>();
+
+        Self::status(
+            write_path,
+            format!(
+                "Copied {} chunk(s) from {} into {}: {}",
+                copied.len(),
+                file_type::display_name(source_path),
+                file_type::output_name(write_path),
+                chunk_types.join(", ")
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Creates or overwrites a standard ancillary chunk for every value.
+    ///
+    /// Existing chunks keep their position, new ones are placed where
+    /// the spec allows them. sRGB is refused on a png with an iCCP chunk
+    /// unless options.remove_iccp is set. Writes the altered png file to
+    /// disk at the passed output_path (if defined) or the original
+    /// file_path.
+    pub fn set(
+        file_path: &str,
+        options: &SetOptions,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+
+        let mut values = options.values.clone();
+        if !options.samples.is_empty() {
+            let color_type = png.ihdr()?.color_type;
+            for (chunk_type, samples) in options.samples.iter() {
+                values
+                    .push(Ancillary::from_samples(chunk_type, samples, color_type)?);
+            }
+        }
+
+        for value in values.iter() {
+            ancillary::make_room(&mut png, value.chunk_type(), options.remove_iccp)?;
+            transplant::set_chunk(&mut png, value.to_chunk());
+        }
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        let report = values
+            .iter()
+            .map(|value| format!("  {}: {}", value.chunk_type(), value))
+            .collect::<Vec<String>>();
+
+        Self::status(
+            write_path,
+            format!(
+                "Set {} chunk(s) in {}:\n{}",
+                values.len(),
+                file_type::output_name(write_path),
+                report.join("\n")
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Lists the tags of the eXIf chunk, or removes tags and whole
+    /// IFDs from it when remove_tags or remove_ifds are given.
+    ///
+    /// Writes the altered png file to disk at the passed output_path
+    /// (if defined) or the original file_path.
+    pub fn exif(
+        file_path: &str,
+        remove_tags: &[String],
+        remove_ifds: &[IfdKind],
+        output_path: &Option<String>,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let chunk = png
+            .chunk_by_type("eXIf")
+            .first()
+            .copied()
+            .ok_or(CommandErrors::Decode("png has no eXIf chunk."))?;
+        let mut exif = Exif::try_from(chunk.data())?;
+
+        for warning in exif.warnings.iter() {
+            eprintln!(
+                "Warning: {}: {}",
+                file_type::display_name(file_path),
+                warning
+            );
+        }
+
+        if remove_tags.is_empty() && remove_ifds.is_empty() {
+            let report = ExifReport::new(file_path, &exif);
+
+            match format {
+                OutputFormat::Text => println!("{}", report),
+                _ => println!("{}", report::render(&report, format)?),
+            }
+
+            return Ok(());
+        }
+
+        let original = png.clone();
+        let mut removed = 0;
+
+        for kind in remove_ifds.iter() {
+            removed += exif.remove_ifd(*kind)?;
+        }
+        for tag in remove_tags.iter() {
+            removed += exif.remove_tag(tag)?;
+        }
+
+        png.replace_chunk(Chunk::new(ChunkType::from_str("eXIf")?, exif.to_bytes()));
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        Self::status(
+            write_path,
+            format!(
+                "Removed {} EXIF tag(s) from {}",
+                removed,
+                file_type::display_name(file_path)
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Prints how many bytes the pixels of a png carry with every
+    /// matrix code parameter and how many samples embedding changes,
+    /// on average for a payload of length bytes if defined.
+    pub fn capacity(
+        file_path: &str,
+        length: Option<usize>,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let pixels = Pixels::from_png(&png, limits)?;
+        let report = CapacityReport::new(file_path, pixels.samples(), length);
+
+        match format {
+            OutputFormat::Text => println!("{}", report),
+            _ => println!("{}", report::render(&report, format)?),
+        }
+
+        Ok(())
+    }
+
+    /// Lists the manifests of the C2PA manifest store in the caBX chunk
+    /// with their assertions and claim signatures, and checks the hard
+    /// binding of the active manifest against the file.
+    pub fn c2pa(
+        file_path: &str,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        // the hard binding covers the bytes as read, not as re-serialized
+        let bytes = Self::read_bytes(file_path, http_options, limits)?;
+        let png = Png::try_from_limited(&bytes, limits)?;
+        let chunk = png
+            .chunk_by_type(c2pa::MANIFEST_CHUNK_TYPE)
+            .first()
+            .copied()
+            .ok_or(CommandErrors::Decode("png has no caBX chunk."))?;
+
+        let store = ManifestStore::try_from(chunk.data())?;
+        let report = C2paReport::new(file_path, &store, &bytes);
+
+        match format {
+            OutputFormat::Text => println!("{}", report),
+            _ => println!("{}", report::render(&report, format)?),
+        }
+
+        Ok(())
+    }
+
+    /// Writes the decompressed profile of the iCCP chunk to output_path.
+    pub fn extract_icc(
+        file_path: &str,
+        output_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let chunk = png
+            .chunk_by_type("iCCP")
+            .first()
+            .copied()
+            .ok_or(CommandErrors::Decode("png has no iCCP chunk."))?;
+
+        let icc_profile = IccProfile::try_from(chunk)?;
+        let profile = icc_profile.profile(limits)?;
+        let length = profile.len();
+
+        Self::write_file(output_path.to_string(), profile, write_options)?;
+
+        Self::status(
+            output_path,
+            format!(
+                "Wrote {} byte ICC profile \"{}\" to {}",
+                length,
+                icc_profile.name,
+                file_type::output_name(output_path)
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Embeds the ICC profile at profile_path as a compressed iCCP
+    /// chunk, replacing any existing one.
+    ///
+    /// sRGB and iCCP must not both be present, so a png with an sRGB
+    /// chunk is refused unless remove_srgb is set. Writes the altered
+    /// png file to disk at the passed output_path (if defined) or the
+    /// original file_path.
+    pub fn embed_icc(
+        file_path: &str,
+        profile_path: &str,
+        name: &str,
+        remove_srgb: bool,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+        let profile = IccProfile::new(
+            name,
+            &Self::read_bytes(profile_path, http_options, limits)?,
+        )?;
+
+        ancillary::make_room(&mut png, "iCCP", remove_srgb)?;
+
+        let header = profile.header.clone();
+        transplant::set_chunk(&mut png, Ancillary::IccProfile(profile).to_chunk());
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        Self::status(
+            write_path,
+            format!(
+                "Embedded ICC profile \"{}\" ({}) in {}",
+                name,
+                header.map(|header| header.to_string()).unwrap_or_default(),
+                file_type::output_name(write_path)
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Reads or edits the XMP packet stored in the iTXt chunk with
+    /// keyword XML:com.adobe.xmp.
+    ///
+    /// Prints the packet (or the properties in options.get) unless
+    /// options asks for changes. Edits start from the replacement
+    /// packet, the existing one or an empty one, in that order. Writes
+    /// the altered png file to disk at the passed output_path (if
+    /// defined) or the original file_path.
+    pub fn xmp(
+        file_path: &str,
+        options: &XmpOptions,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+        let existing = xmp::find_packet(&png, limits).map(|(_, text)| text.text);
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+
+        if options.delete {
+            if xmp::remove_packets(&mut png) == 0 {
+                return Err(Box::new(CommandErrors::Decode(
+                    "png has no XMP packet.",
+                )));
+            }
+            Self::write_png(
+                Some(&original),
+                &png,
+                write_path,
+                None,
+                limits,
+                write_options,
+            )?;
+            Self::status(
+                write_path,
+                format!(
+                    "Removed XMP packet from {}",
+                    file_type::display_name(file_path)
+                ),
+                write_options,
+            );
+            return Ok(());
+        }
+
+        if options.replace.is_none() && options.set.is_empty() {
+            let packet =
+                existing.ok_or(CommandErrors::Decode("png has no XMP packet."))?;
+
+            if options.get.is_empty() {
+                match options.raw {
+                    true => println!("{}", packet),
+                    false => println!("{}", xmp::pretty_print(&packet)?),
+                }
+            }
+            for property in options.get.iter() {
+                let value = xmp::get_property(&packet, property)?
+                    .ok_or(CommandErrors::Decode("XMP property not found."))?;
+                println!("{}", value);
+            }
+
+            return Ok(());
+        }
+
+        let mut packet = match &options.replace {
+            Some(path) => {
+                String::from_utf8(Self::read_bytes(path, http_options, limits)?)?
+            }
+            None => existing.unwrap_or_else(xmp::empty_packet),
+        };
+
+        for assignment in options.set.iter() {
+            let (property, value) = assignment.split_once('=').ok_or(
+                CommandErrors::Encode("XMP properties are set as PROPERTY=VALUE."),
+            )?;
+            packet = xmp::set_property(&packet, property, value)?;
+        }
+
+        xmp::set_packet(&mut png, &packet)?;
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        Self::status(
+            write_path,
+            format!(
+                "Updated XMP packet in {}",
+                file_type::display_name(file_path)
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Shows the data of the index-th chunk of chunk_type.
+    ///
+    /// Prints an annotated hex dump with file offsets, or the raw
+    /// bytes when raw is set. Raw bytes are written to output_path
+    /// instead if it is defined.
+    pub fn cat(
+        file_path: &str,
+        chunk_type: &str,
+        index: usize,
+        raw: bool,
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let png = Self::convert_to_png_lenient(file_path, http_options, limits)?;
+
+        let (chunk, info) = png
+            .chunks
+            .iter()
+            .zip(report::chunk_reports(&png))
+            .filter(|(chunk, _)| chunk.chunk_type().to_string() == chunk_type)
+            .nth(index)
+            .ok_or(CommandErrors::Decode("chunk_type not found at index."))?;
+
+        if let Some(output_path) = output_path {
+            Self::write_file(
+                output_path.to_string(),
+                chunk.data().to_vec(),
+                write_options,
+            )?;
+            Self::status(
+                output_path,
+                format!(
+                    "Wrote {} bytes of {} #{} to {}",
+                    chunk.length(),
+                    chunk_type,
+                    index,
+                    file_type::output_name(output_path)
+                ),
+                write_options,
+            );
+            return Ok(());
+        }
+
+        if raw {
+            io::stdout().write_all(chunk.data())?;
+            return Ok(());
+        }
+
+        // data starts after the 4 byte length and 4 byte type fields
+        println!(
+            "{} #{} (chunk {}) at offset {:#x}, {} bytes, crc {:08x} ({})\n{}",
+            chunk_type,
+            index,
+            info.index,
+            info.offset,
+            info.length,
+            info.crc,
+            if info.crc_valid { "valid" } else { "mismatch" },
+            hexdump::hex_dump(chunk.data(), info.offset + 8)
+        );
+
+        Ok(())
+    }
+
+    /// Checks the png file against the PNG specification and
+    /// prints the errors and warnings found.
+    ///
+    /// Errors if any error was found, or any warning when strict
+    /// is set, so the exit code can gate CI.
+    pub fn lint(
+        file_path: &str,
+        strict: bool,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let bytes = Self::read_bytes(file_path, http_options, limits)?;
+        let report = lint::lint(file_path, &bytes, limits);
+
+        match format {
+            OutputFormat::Text => println!("{}", report),
+            _ => println!("{}", report::render(&report, format)?),
+        }
+
+        let failed = report.count(Severity::Error) > 0
+            || (strict && report.count(Severity::Warning) > 0);
+
+        if failed {
+            return Err(Box::new(CommandErrors::Verify("lint found problems.")));
+        }
+
+        Ok(())
+    }
+
+    /// Prints the chunk level differences between two png files.
+    ///
+    /// Errors if the files differ so the exit code can be used
+    /// in scripts.
+    pub fn diff(
+        left_path: &str,
+        right_path: &str,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let left = Self::convert_to_png_lenient(left_path, http_options, limits)?;
+        let right = Self::convert_to_png_lenient(right_path, http_options, limits)?;
+
+        let diff = PngDiff::new(left_path, &left, right_path, &right)?;
+
+        match format {
+            OutputFormat::Text => {
+                println!("--- {}\n+++ {}\n{}", left_path, right_path, diff)
+            }
+            _ => println!("{}", report::render(&diff, format)?),
+        }
+
+        if !diff.is_identical() {
+            return Err(Box::new(CommandErrors::Diff("files differ.")));
+        }
+
+        Ok(())
+    }
+
     /// Prints the contents of png file as Chunks
-    pub fn print(file_path: &String) -> Result<()> {
-        let png = Self::convert_to_png(file_path)?;
-        let formatted_png = format!("{}", png);
+    ///
+    /// Json and yaml output hold every chunk with its offset, flags,
+    /// crc validity and decoded contents, see docs/output.md.
+    pub fn print(
+        file_path: &str,
+        format: OutputFormat,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        if format != OutputFormat::Text {
+            let bytes = Self::read_bytes(file_path, http_options, limits)?;
+            let png = Png::from_bytes_lenient(&bytes, limits)?;
+            let report = PngReport::new(
+                &file_type::display_name(file_path),
+                &png,
+                bytes.len(),
+            );
+            println!("{}", report::render(&report, format)?);
+            return Ok(());
+        }
+
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let animation = if png.is_animated() {
+            format!("\n{}\n", Animation::try_from(&png)?)
+        } else {
+            String::new()
+        };
+
+        // single print so batch output from parallel files never interleaves
+        println!(
+            "________________________________________________\n\n\
+             Total Chunks: {}\n\n\
+             Png file at path {} has the following chunks:\n\n{}\n{}\
+             ________________________________________________",
+            png.chunks.len(),
+            file_type::display_name(file_path),
+            png,
+            animation
+        );
+
+        Ok(())
+    }
+
+    /// Generates a new Ed25519 keypair.
+    ///
+    /// Writes the secret key to key_path and the public key
+    /// to key_path.pub, then prints the public key fingerprint.
+    pub fn keygen(key_path: &str) -> Result<()> {
+        let key = signature::generate_key();
+        signature::write_keypair(&key, key_path)?;
 
-        println!("________________________________________________\n");
-        println!("Total Chunks: {}\n", png.chunks.len());
         println!(
-            "Png file at path {} has the following chunks:\n\n{}",
-            file_path, formatted_png
+            "Wrote secret key to {} and public key to {}.pub\nFingerprint: {}",
+            key_path,
+            key_path,
+            signature::fingerprint(&key.verifying_key())
+        );
+
+        Ok(())
+    }
+
+    /// Prints the fingerprint of a public key.
+    ///
+    /// Files ending in .pub are read as public keys, anything
+    /// else is read as a secret key.
+    pub fn fingerprint(key_path: &str) -> Result<()> {
+        let key = if key_path.ends_with(".pub") {
+            signature::read_verifying_key(key_path)?
+        } else {
+            signature::read_signing_key(key_path)?.verifying_key()
+        };
+
+        println!("{}", signature::fingerprint(&key));
+
+        Ok(())
+    }
+
+    /// Signs the image data of the png file.
+    ///
+    /// Critical chunks are always covered, ancillary chunks only
+    /// when their type is listed in include. Any previous image
+    /// signature is replaced.
+    pub fn sign(
+        file_path: &str,
+        key_path: &str,
+        include: &[String],
+        output_path: &Option<String>,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let mut png = Self::convert_to_png(file_path, http_options, limits)?;
+        let original = png.clone();
+        let key = signature::read_signing_key(key_path)?;
+
+        let include = include
+            .iter()
+            .map(|chunk_type| ChunkType::from_str(chunk_type))
+            .collect::<Result<Vec<ChunkType>>>()?;
+
+        if include.iter().any(|chunk_type| chunk_type.is_critical()) {
+            return Err(Box::new(CommandErrors::Encode(
+                "critical chunks are always signed, include takes ancillary chunk types.",
+            )));
+        }
+
+        while png
+            .remove_chunk(signature::IMAGE_SIGNATURE_CHUNK_TYPE)
+            .is_some()
+        {}
+
+        let signature_chunk = signature::sign_image(&key, &png, &include)?;
+        png.append_chunk(signature_chunk);
+
+        let write_path = output_path.as_deref().unwrap_or(file_path);
+        Self::write_png(
+            Some(&original),
+            &png,
+            write_path,
+            None,
+            limits,
+            write_options,
+        )?;
+
+        Self::status(
+            write_path,
+            format!(
+                "Signed image with key {}",
+                signature::fingerprint(&key.verifying_key())
+            ),
+            write_options,
+        );
+
+        Ok(())
+    }
+
+    /// Verifies the image signature of the png file.
+    ///
+    /// Prints the signature status, anything but valid is an error.
+    pub fn verify(
+        file_path: &str,
+        key_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let key = signature::read_verifying_key(key_path)?;
+
+        let status = signature::verify_image(&png, &key);
+        println!("Image signature: {}", status);
+
+        match status {
+            SignatureStatus::Valid => Ok(()),
+            SignatureStatus::Invalid => Err(Box::new(CommandErrors::Verify(
+                "image signature does not match.",
+            ))),
+            SignatureStatus::Unsigned => {
+                Err(Box::new(CommandErrors::Verify("image is not signed.")))
+            }
+        }
+    }
+
+    /// Prints the frames of an animated png file.
+    ///
+    /// Errors if the fcTL/fdAT sequence numbers are invalid.
+    pub fn frames(
+        file_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+    ) -> Result<()> {
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let animation = Animation::try_from(&png)?;
+
+        print!("{}", animation);
+        Animation::validate(&png)?;
+
+        Ok(())
+    }
+
+    /// Writes a single frame of an animated png file
+    /// to output_path as a standalone png.
+    pub fn extract_frame(
+        file_path: &str,
+        frame_index: usize,
+        output_path: &str,
+        http_options: &HttpOptions,
+        limits: &Limits,
+        write_options: &WriteOptions,
+    ) -> Result<()> {
+        let png = Self::convert_to_png(file_path, http_options, limits)?;
+        let animation = Animation::try_from(&png)?;
+
+        let frame = animation.extract_frame(&png, frame_index)?;
+        Self::write_png(None, &frame, output_path, None, limits, write_options)?;
+
+        Self::status(
+            output_path,
+            format!(
+                "Wrote frame {} to {}",
+                frame_index,
+                file_type::output_name(output_path)
+            ),
+            write_options,
         );
-        println!("________________________________________________");
 
         Ok(())
     }
@@ -0,0 +1,504 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::png::Png;
+use crate::report::SCHEMA_VERSION;
+use crate::Result;
+
+/// Data differences beyond this many ranges are only counted.
+const MAX_RANGES: usize = 16;
+
+/// Alignments comparing more chunk pairs than this are refused, the
+/// time spent grows with the product of both chunk counts.
+const MAX_COMPARISONS: usize = 1 << 28;
+
+/// Kind of change between the left (old) and right (new) png.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Unchanged,
+    Added,
+    Removed,
+    Moved,
+    Modified,
+}
+
+/// A run of differing bytes in the chunk data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ByteRange {
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// One aligned chunk. Indices are positions in the chunk list of the
+/// left and right png, None when the chunk only exists on one side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkDiff {
+    pub change: Change,
+    pub chunk_type: String,
+    pub left_index: Option<usize>,
+    pub right_index: Option<usize>,
+    pub left_length: Option<u32>,
+    pub right_length: Option<u32>,
+    pub left_crc: Option<u32>,
+    pub right_crc: Option<u32>,
+    /// number of differing bytes, bytes past the shorter data included
+    pub bytes_changed: usize,
+    /// first MAX_RANGES runs of differing bytes
+    pub ranges: Vec<ByteRange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PngDiff {
+    pub schema_version: u32,
+    pub left_path: String,
+    pub right_path: String,
+    pub chunks: Vec<ChunkDiff>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+    Moved(usize, usize),
+    Modified(usize, usize),
+}
+
+/// LCS lengths of a against every prefix of b, in linear space.
+fn lcs_row(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut row = vec![0; b.len() + 1];
+
+    for &x in a {
+        let mut diagonal = 0;
+
+        for (j, &y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+/// Matching (left, right) positions of a longest common subsequence,
+/// Hirschberg's divide and conquer keeps memory linear. Ties prefer
+/// matching later left items, so removals come before additions.
+fn lcs(
+    a: &[u32],
+    b: &[u32],
+    offset: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+
+    if let [x] = a {
+        if let Some(j) = b.iter().position(|y| y == x) {
+            pairs.push((offset.0, offset.1 + j));
+        }
+        return;
+    }
+
+    let mid = a.len() / 2;
+    let forward = lcs_row(&a[..mid], b);
+    let backward = lcs_row(
+        &a[mid..].iter().rev().copied().collect::<Vec<u32>>(),
+        &b.iter().rev().copied().collect::<Vec<u32>>(),
+    );
+
+    let split = (0..=b.len())
+        .max_by_key(|&k| (forward[k] + backward[b.len() - k], Reverse(k)))
+        .unwrap_or_default();
+
+    lcs(&a[..mid], &b[..split], offset, pairs);
+    lcs(
+        &a[mid..],
+        &b[split..],
+        (offset.0 + mid, offset.1 + split),
+        pairs,
+    );
+}
+
+/// Longest common subsequence of identical chunks, removals are listed
+/// before additions.
+///
+/// Chunks are compared by id and the common head and tail are skipped,
+/// the rest is refused past MAX_COMPARISONS.
+fn align(left: &[Chunk], right: &[Chunk]) -> Result<Vec<Op>> {
+    let mut ids = HashMap::new();
+    let mut id = |chunk: &Chunk| {
+        let next = ids.len() as u32;
+        *ids.entry((
+            chunk.chunk_type().bytes(),
+            chunk.crc(),
+            chunk.data().to_vec(),
+        ))
+        .or_insert(next)
+    };
+    let left_ids = left.iter().map(&mut id).collect::<Vec<u32>>();
+    let right_ids = right.iter().map(&mut id).collect::<Vec<u32>>();
+
+    let head = left_ids
+        .iter()
+        .zip(right_ids.iter())
+        .take_while(|(l, r)| l == r)
+        .count();
+    let tail = left_ids[head..]
+        .iter()
+        .rev()
+        .zip(right_ids[head..].iter().rev())
+        .take_while(|(l, r)| l == r)
+        .count();
+
+    let (n, m) = (left.len() - tail, right.len() - tail);
+    if (n - head).saturating_mul(m - head) > MAX_COMPARISONS {
+        return Err(Box::new(ChunkError("Too many differing chunks to align")));
+    }
+
+    let mut pairs = (0..head).map(|i| (i, i)).collect::<Vec<(usize, usize)>>();
+    lcs(
+        &left_ids[head..n],
+        &right_ids[head..m],
+        (head, head),
+        &mut pairs,
+    );
+    pairs.extend((0..tail).map(|k| (n + k, m + k)));
+
+    let (mut i, mut j) = (0, 0);
+    let mut ops = vec![];
+
+    for (matched_i, matched_j) in pairs {
+        ops.extend((i..matched_i).map(Op::Removed));
+        ops.extend((j..matched_j).map(Op::Added));
+        ops.push(Op::Same(matched_i, matched_j));
+        i = matched_i + 1;
+        j = matched_j + 1;
+    }
+
+    ops.extend((i..left.len()).map(Op::Removed));
+    ops.extend((j..right.len()).map(Op::Added));
+
+    Ok(ops)
+}
+
+/// Pairs a removed chunk with an added one, first one matching wins.
+/// The pair takes the place of the added chunk.
+fn pair<F>(ops: &mut Vec<Op>, matches: F, paired: fn(usize, usize) -> Op)
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut idx = 0;
+
+    while idx < ops.len() {
+        if let Op::Removed(i) = ops[idx] {
+            let found = ops.iter().position(|op| match *op {
+                Op::Added(j) => matches(i, j),
+                _ => false,
+            });
+
+            if let Some(found) = found {
+                if let Op::Added(j) = ops[found] {
+                    ops[found] = paired(i, j);
+                }
+                ops.remove(idx);
+                continue;
+            }
+        }
+
+        idx += 1;
+    }
+}
+
+/// Runs of differing bytes between two byte slices.
+fn byte_ranges(left: &[u8], right: &[u8]) -> (usize, Vec<ByteRange>) {
+    let longest = left.len().max(right.len());
+    let mut changed = 0;
+    let mut ranges: Vec<ByteRange> = vec![];
+
+    for offset in 0..longest {
+        if left.get(offset) == right.get(offset) {
+            continue;
+        }
+
+        changed += 1;
+
+        match ranges.last_mut() {
+            Some(range) if range.offset + range.length == offset => {
+                range.length += 1
+            }
+            _ => ranges.push(ByteRange { offset, length: 1 }),
+        }
+    }
+
+    ranges.truncate(MAX_RANGES);
+    (changed, ranges)
+}
+
+fn chunk_diff(
+    change: Change,
+    left: Option<(usize, &Chunk)>,
+    right: Option<(usize, &Chunk)>,
+) -> ChunkDiff {
+    let chunk_type = left
+        .or(right)
+        .map(|(_, chunk)| chunk.chunk_type().to_string())
+        .unwrap_or_default();
+
+    let (bytes_changed, ranges) = match (change, left, right) {
+        (Change::Modified, Some((_, left)), Some((_, right))) => {
+            byte_ranges(left.data(), right.data())
+        }
+        _ => (0, vec![]),
+    };
+
+    ChunkDiff {
+        change,
+        chunk_type,
+        left_index: left.map(|(idx, _)| idx),
+        right_index: right.map(|(idx, _)| idx),
+        left_length: left.map(|(_, chunk)| chunk.length()),
+        right_length: right.map(|(_, chunk)| chunk.length()),
+        left_crc: left.map(|(_, chunk)| chunk.crc()),
+        right_crc: right.map(|(_, chunk)| chunk.crc()),
+        bytes_changed,
+        ranges,
+    }
+}
+
+impl PngDiff {
+    /// Aligns the chunk sequences of two pngs.
+    ///
+    /// Identical chunks in the same relative order are unchanged.
+    /// Identical chunks that changed position are moved, chunks of the
+    /// same type that could not be matched otherwise are modified and
+    /// anything left over was added or removed.
+    pub fn new(
+        left_path: &str,
+        left: &Png,
+        right_path: &str,
+        right: &Png,
+    ) -> Result<PngDiff> {
+        let (l, r) = (&left.chunks, &right.chunks);
+        let mut ops = align(l, r)?;
+
+        pair(&mut ops, |i, j| l[i] == r[j], Op::Moved);
+        pair(
+            &mut ops,
+            |i, j| l[i].chunk_type() == r[j].chunk_type(),
+            Op::Modified,
+        );
+
+        let chunks = ops
+            .into_iter()
+            .map(|op| match op {
+                Op::Same(i, j) => {
+                    chunk_diff(Change::Unchanged, Some((i, &l[i])), Some((j, &r[j])))
+                }
+                Op::Moved(i, j) => {
+                    chunk_diff(Change::Moved, Some((i, &l[i])), Some((j, &r[j])))
+                }
+                Op::Modified(i, j) => {
+                    chunk_diff(Change::Modified, Some((i, &l[i])), Some((j, &r[j])))
+                }
+                Op::Removed(i) => {
+                    chunk_diff(Change::Removed, Some((i, &l[i])), None)
+                }
+                Op::Added(j) => chunk_diff(Change::Added, None, Some((j, &r[j]))),
+            })
+            .collect();
+
+        Ok(PngDiff {
+            schema_version: SCHEMA_VERSION,
+            left_path: left_path.to_string(),
+            right_path: right_path.to_string(),
+            chunks,
+        })
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.chunks
+            .iter()
+            .all(|chunk| chunk.change == Change::Unchanged)
+    }
+
+    fn count(&self, change: Change) -> usize {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.change == change)
+            .count()
+    }
+}
+
+fn index(idx: Option<usize>) -> String {
+    idx.map(|idx| idx.to_string()).unwrap_or_default()
+}
+
+impl fmt::Display for ChunkDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (left, right) = (index(self.left_index), index(self.right_index));
+        let length = self.left_length.or(self.right_length).unwrap_or_default();
+
+        match self.change {
+            Change::Unchanged => write!(f, "  {} [{}]", self.chunk_type, right),
+            Change::Added => {
+                write!(f, "+ {} [{}] {} bytes", self.chunk_type, right, length)
+            }
+            Change::Removed => {
+                write!(f, "- {} [{}] {} bytes", self.chunk_type, left, length)
+            }
+            Change::Moved => {
+                write!(f, "> {} [{} -> {}]", self.chunk_type, left, right)
+            }
+            Change::Modified => {
+                let ranges = self
+                    .ranges
+                    .iter()
+                    .map(|range| {
+                        format!("{}..{}", range.offset, range.offset + range.length)
+                    })
+                    .collect::<Vec<String>>();
+
+                write!(
+                    f,
+                    "~ {} [{} -> {}] {} -> {} bytes, crc {:08x} -> {:08x}, \
+                     {} byte(s) differ at {}",
+                    self.chunk_type,
+                    left,
+                    right,
+                    self.left_length.unwrap_or_default(),
+                    self.right_length.unwrap_or_default(),
+                    self.left_crc.unwrap_or_default(),
+                    self.right_crc.unwrap_or_default(),
+                    self.bytes_changed,
+                    ranges.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Lists every change (unchanged chunks are left out) and a summary.
+impl fmt::Display for PngDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.chunks.iter() {
+            if chunk.change != Change::Unchanged {
+                writeln!(f, "{}", chunk)?;
+            }
+        }
+
+        write!(
+            f,
+            "{} added, {} removed, {} moved, {} modified, {} unchanged",
+            self.count(Change::Added),
+            self.count(Change::Removed),
+            self.count(Change::Moved),
+            self.count(Change::Modified),
+            self.count(Change::Unchanged)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn changes(diff: &PngDiff) -> Vec<(Change, String)> {
+        diff.chunks
+            .iter()
+            .map(|chunk| (chunk.change, chunk.chunk_type.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_identical() {
+        let png =
+            Png::from_chunks(vec![chunk_of("IHDR", b"a"), chunk_of("IEND", b"")]);
+        assert!(PngDiff::new("a", &png, "b", &png).unwrap().is_identical());
+    }
+
+    #[test]
+    fn test_added_removed_moved_modified() {
+        let left = Png::from_chunks(vec![
+            chunk_of("IHDR", b"header"),
+            chunk_of("gAMA", b"gamma"),
+            chunk_of("tEXt", b"old text"),
+            chunk_of("IDAT", b"pixels"),
+            chunk_of("ruSt", b"secret"),
+            chunk_of("IEND", b""),
+        ]);
+        let right = Png::from_chunks(vec![
+            chunk_of("IHDR", b"header"),
+            chunk_of("tEXt", b"new text!"),
+            chunk_of("IDAT", b"pixels"),
+            chunk_of("gAMA", b"gamma"),
+            chunk_of("tIME", b"time"),
+            chunk_of("IEND", b""),
+        ]);
+
+        let diff = PngDiff::new("left", &left, "right", &right).unwrap();
+
+        assert_eq!(
+            changes(&diff),
+            vec![
+                (Change::Unchanged, "IHDR".to_string()),
+                (Change::Modified, "tEXt".to_string()),
+                (Change::Unchanged, "IDAT".to_string()),
+                (Change::Removed, "ruSt".to_string()),
+                (Change::Moved, "gAMA".to_string()),
+                (Change::Added, "tIME".to_string()),
+                (Change::Unchanged, "IEND".to_string()),
+            ]
+        );
+
+        let modified = &diff.chunks[1];
+        assert_eq!(modified.bytes_changed, 4);
+        assert_eq!(
+            modified.ranges,
+            vec![
+                ByteRange {
+                    offset: 0,
+                    length: 3
+                },
+                ByteRange {
+                    offset: 8,
+                    length: 1
+                }
+            ]
+        );
+        assert_ne!(modified.left_crc, modified.right_crc);
+    }
+
+    #[test]
+    fn test_too_many_differing_chunks() {
+        let chunks = |prefix: &str| {
+            (0..20_000)
+                .map(|idx| chunk_of("tEXt", format!("{}{}", prefix, idx).as_bytes()))
+                .collect::<Vec<Chunk>>()
+        };
+        let left = Png::from_chunks(chunks("a"));
+        let right = Png::from_chunks(chunks("b"));
+
+        assert!(PngDiff::new("left", &left, "right", &right).is_err());
+
+        // shared head and tail are not compared pairwise
+        let mut changed = chunks("a");
+        changed[10_000] = chunk_of("tEXt", b"changed");
+        let right = Png::from_chunks(changed);
+        let diff = PngDiff::new("left", &left, "right", &right).unwrap();
+        assert_eq!(diff.count(Change::Modified), 1);
+        assert_eq!(diff.count(Change::Unchanged), 19_999);
+    }
+}
@@ -0,0 +1,860 @@
+use std::fmt;
+
+use clap::ArgEnum;
+use serde::Serialize;
+
+use crate::chunk_error::ChunkError;
+use crate::report::SCHEMA_VERSION;
+use crate::{Error, Result};
+
+/// TIFF field type of the offsets pngsneak writes itself.
+const LONG: u16 = 4;
+
+/// Tag of the thumbnail's offset in IFD1, rewritten on every write.
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// Values longer than this are listed by their count instead.
+const MAX_LISTED_VALUES: usize = 16;
+
+/// Known tags as (ifd, tag, name). IFD0 names also apply to IFD1.
+/// https://www.cipa.jp/std/documents/download_e.html?DC-008-Translation-2023-E
+const TAG_NAMES: &[(IfdKind, u16, &str)] = &[
+    (IfdKind::Primary, 0x0100, "ImageWidth"),
+    (IfdKind::Primary, 0x0101, "ImageLength"),
+    (IfdKind::Primary, 0x0102, "BitsPerSample"),
+    (IfdKind::Primary, 0x0103, "Compression"),
+    (IfdKind::Primary, 0x0106, "PhotometricInterpretation"),
+    (IfdKind::Primary, 0x010e, "ImageDescription"),
+    (IfdKind::Primary, 0x010f, "Make"),
+    (IfdKind::Primary, 0x0110, "Model"),
+    (IfdKind::Primary, 0x0112, "Orientation"),
+    (IfdKind::Primary, 0x011a, "XResolution"),
+    (IfdKind::Primary, 0x011b, "YResolution"),
+    (IfdKind::Primary, 0x0128, "ResolutionUnit"),
+    (IfdKind::Primary, 0x0131, "Software"),
+    (IfdKind::Primary, 0x0132, "DateTime"),
+    (IfdKind::Primary, 0x013b, "Artist"),
+    (IfdKind::Primary, 0x0201, "JPEGInterchangeFormat"),
+    (IfdKind::Primary, 0x0202, "JPEGInterchangeFormatLength"),
+    (IfdKind::Primary, 0x0213, "YCbCrPositioning"),
+    (IfdKind::Primary, 0x8298, "Copyright"),
+    (IfdKind::Exif, 0x829a, "ExposureTime"),
+    (IfdKind::Exif, 0x829d, "FNumber"),
+    (IfdKind::Exif, 0x8822, "ExposureProgram"),
+    (IfdKind::Exif, 0x8827, "PhotographicSensitivity"),
+    (IfdKind::Exif, 0x9000, "ExifVersion"),
+    (IfdKind::Exif, 0x9003, "DateTimeOriginal"),
+    (IfdKind::Exif, 0x9004, "DateTimeDigitized"),
+    (IfdKind::Exif, 0x9010, "OffsetTime"),
+    (IfdKind::Exif, 0x9011, "OffsetTimeOriginal"),
+    (IfdKind::Exif, 0x9012, "OffsetTimeDigitized"),
+    (IfdKind::Exif, 0x9201, "ShutterSpeedValue"),
+    (IfdKind::Exif, 0x9202, "ApertureValue"),
+    (IfdKind::Exif, 0x9204, "ExposureBiasValue"),
+    (IfdKind::Exif, 0x9207, "MeteringMode"),
+    (IfdKind::Exif, 0x9209, "Flash"),
+    (IfdKind::Exif, 0x920a, "FocalLength"),
+    (IfdKind::Exif, 0x927c, "MakerNote"),
+    (IfdKind::Exif, 0x9286, "UserComment"),
+    (IfdKind::Exif, 0x9290, "SubSecTime"),
+    (IfdKind::Exif, 0x9291, "SubSecTimeOriginal"),
+    (IfdKind::Exif, 0x9292, "SubSecTimeDigitized"),
+    (IfdKind::Exif, 0xa000, "FlashpixVersion"),
+    (IfdKind::Exif, 0xa001, "ColorSpace"),
+    (IfdKind::Exif, 0xa002, "PixelXDimension"),
+    (IfdKind::Exif, 0xa003, "PixelYDimension"),
+    (IfdKind::Exif, 0xa402, "ExposureMode"),
+    (IfdKind::Exif, 0xa403, "WhiteBalance"),
+    (IfdKind::Exif, 0xa405, "FocalLengthIn35mmFilm"),
+    (IfdKind::Exif, 0xa406, "SceneCaptureType"),
+    (IfdKind::Exif, 0xa420, "ImageUniqueID"),
+    (IfdKind::Exif, 0xa430, "CameraOwnerName"),
+    (IfdKind::Exif, 0xa431, "BodySerialNumber"),
+    (IfdKind::Exif, 0xa432, "LensSpecification"),
+    (IfdKind::Exif, 0xa433, "LensMake"),
+    (IfdKind::Exif, 0xa434, "LensModel"),
+    (IfdKind::Exif, 0xa435, "LensSerialNumber"),
+    (IfdKind::Gps, 0x0000, "GPSVersionID"),
+    (IfdKind::Gps, 0x0001, "GPSLatitudeRef"),
+    (IfdKind::Gps, 0x0002, "GPSLatitude"),
+    (IfdKind::Gps, 0x0003, "GPSLongitudeRef"),
+    (IfdKind::Gps, 0x0004, "GPSLongitude"),
+    (IfdKind::Gps, 0x0005, "GPSAltitudeRef"),
+    (IfdKind::Gps, 0x0006, "GPSAltitude"),
+    (IfdKind::Gps, 0x0007, "GPSTimeStamp"),
+    (IfdKind::Gps, 0x000c, "GPSSpeedRef"),
+    (IfdKind::Gps, 0x000d, "GPSSpeed"),
+    (IfdKind::Gps, 0x0010, "GPSImgDirectionRef"),
+    (IfdKind::Gps, 0x0011, "GPSImgDirection"),
+    (IfdKind::Gps, 0x0012, "GPSMapDatum"),
+    (IfdKind::Gps, 0x0017, "GPSDestBearingRef"),
+    (IfdKind::Gps, 0x0018, "GPSDestBearing"),
+    (IfdKind::Gps, 0x001d, "GPSDateStamp"),
+    (IfdKind::Gps, 0x001f, "GPSHPositioningError"),
+    (IfdKind::Interop, 0x0001, "InteroperabilityIndex"),
+    (IfdKind::Interop, 0x0002, "InteroperabilityVersion"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn put_u16(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn put_u32(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// The image file directories (IFDs) EXIF data is split into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IfdKind {
+    /// IFD0, tags of the image itself (camera, orientation, dates)
+    Primary,
+    /// camera settings
+    Exif,
+    /// location
+    Gps,
+    /// interoperability, a child of the Exif IFD
+    Interop,
+    /// IFD1, the embedded thumbnail
+    Thumbnail,
+}
+
+impl IfdKind {
+    /// Order IFDs are written in.
+    const ALL: [IfdKind; 5] = [
+        IfdKind::Primary,
+        IfdKind::Exif,
+        IfdKind::Gps,
+        IfdKind::Interop,
+        IfdKind::Thumbnail,
+    ];
+
+    /// IFD holding the pointer to self and the pointer's tag.
+    /// IFD1 is linked from IFD0's next IFD offset instead.
+    fn pointer(&self) -> Option<(IfdKind, u16)> {
+        match self {
+            IfdKind::Exif => Some((IfdKind::Primary, 0x8769)),
+            IfdKind::Gps => Some((IfdKind::Primary, 0x8825)),
+            IfdKind::Interop => Some((IfdKind::Exif, 0xa005)),
+            IfdKind::Primary | IfdKind::Thumbnail => None,
+        }
+    }
+
+    /// IFD a pointer tag in parent leads to.
+    fn child(parent: IfdKind, tag: u16) -> Option<IfdKind> {
+        IfdKind::ALL
+            .into_iter()
+            .find(|kind| kind.pointer() == Some((parent, tag)))
+    }
+
+    /// IFD whose tag names apply to self.
+    fn names(&self) -> IfdKind {
+        match self {
+            IfdKind::Thumbnail => IfdKind::Primary,
+            kind => *kind,
+        }
+    }
+}
+
+impl fmt::Display for IfdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            IfdKind::Primary => "IFD0",
+            IfdKind::Exif => "Exif",
+            IfdKind::Gps => "GPS",
+            IfdKind::Interop => "Interop",
+            IfdKind::Thumbnail => "IFD1 (thumbnail)",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Bytes of a single value of a TIFF field type, None for unknown types.
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// A single tag, value holds its bytes in the byte order of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub tag: u16,
+    pub field_type: u16,
+    pub count: u32,
+    pub value: Vec<u8>,
+}
+
+impl Entry {
+    pub fn name(&self, ifd: IfdKind) -> Option<&'static str> {
+        TAG_NAMES
+            .iter()
+            .find(|(kind, tag, _)| *kind == ifd.names() && *tag == self.tag)
+            .map(|(_, _, name)| *name)
+    }
+
+    /// Human readable value, long lists are only counted.
+    pub fn format_value(&self, order: ByteOrder) -> String {
+        let size = type_size(self.field_type).unwrap_or(1);
+        let values = self.value.chunks(size);
+
+        if self.field_type == 2 {
+            let text = self.value.split(|&b| b == 0).next().unwrap_or_default();
+            return String::from_utf8_lossy(text).to_string();
+        }
+
+        if self.count as usize > MAX_LISTED_VALUES {
+            return format!("{} values", self.count);
+        }
+
+        let values = values
+            .map(|value| match self.field_type {
+                3 => order.u16(value).to_string(),
+                4 | 13 => order.u32(value).to_string(),
+                5 => format!("{}/{}", order.u32(value), order.u32(&value[4..])),
+                6 => (value[0] as i8).to_string(),
+                8 => (order.u16(value) as i16).to_string(),
+                9 => (order.u32(value) as i32).to_string(),
+                10 => format!(
+                    "{}/{}",
+                    order.u32(value) as i32,
+                    order.u32(&value[4..]) as i32
+                ),
+                11 => f32::from_bits(order.u32(value)).to_string(),
+                12 => {
+                    let bits = match order {
+                        ByteOrder::Little => {
+                            u64::from_le_bytes(value.try_into().unwrap())
+                        }
+                        ByteOrder::Big => {
+                            u64::from_be_bytes(value.try_into().unwrap())
+                        }
+                    };
+                    f64::from_bits(bits).to_string()
+                }
+                _ => value[0].to_string(),
+            })
+            .collect::<Vec<String>>();
+
+        values.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ifd {
+    pub kind: IfdKind,
+    /// tags in file order, pointers to other IFDs excluded
+    pub entries: Vec<Entry>,
+}
+
+/// Parsed eXIf chunk data: a TIFF header followed by IFDs.
+/// https://www.cipa.jp/std/documents/download_e.html?DC-008-Translation-2023-E
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    pub ifds: Vec<Ifd>,
+    /// JPEG thumbnail referenced by IFD1
+    pub thumbnail: Option<Vec<u8>>,
+    /// parts of the data that were dropped while parsing and why
+    pub warnings: Vec<String>,
+}
+
+/// length bytes of tiff starting at offset.
+fn bytes(tiff: &[u8], offset: usize, length: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(length)
+        .and_then(|end| tiff.get(offset..end))
+        .ok_or_else(|| Box::new(ChunkError("EXIF offset is out of bounds")) as Error)
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = Error;
+
+    fn try_from(tiff: &[u8]) -> Result<Self> {
+        let byte_order = match tiff.get(0..4) {
+            Some(b"II*\0") => ByteOrder::Little,
+            Some(b"MM\0*") => ByteOrder::Big,
+            _ => {
+                return Err(Box::new(ChunkError(
+                    "eXIf must start with a TIFF header",
+                )))
+            }
+        };
+
+        let mut exif = Exif {
+            byte_order,
+            ifds: vec![],
+            thumbnail: None,
+            warnings: vec![],
+        };
+
+        let first = byte_order.u32(bytes(tiff, 4, 4)?);
+        let next = exif.read_ifd(tiff, first, IfdKind::Primary)?;
+
+        // only IFD1 may follow IFD0, anything after it is ignored. A broken
+        // IFD1 only holds the thumbnail, so it is dropped instead of
+        // making the primary tags unreadable.
+        if next != 0 {
+            if let Err(error) = exif.read_ifd(tiff, next, IfdKind::Thumbnail) {
+                exif.warnings
+                    .push(format!("dropped IFD1 (thumbnail): {}", error));
+            }
+        }
+
+        Ok(exif)
+    }
+}
+
+impl Exif {
+    pub fn ifd(&self, kind: IfdKind) -> Option<&Ifd> {
+        self.ifds.iter().find(|ifd| ifd.kind == kind)
+    }
+
+    /// Reads the IFD at offset along with the IFDs it points to and
+    /// returns its next IFD offset.
+    fn read_ifd(&mut self, tiff: &[u8], offset: u32, kind: IfdKind) -> Result<u32> {
+        let order = self.byte_order;
+        let offset = offset as usize;
+
+        let count = order.u16(bytes(tiff, offset, 2)?) as usize;
+        let table = bytes(tiff, offset + 2, count * 12 + 4)?;
+
+        let mut entries = vec![];
+        let mut children = vec![];
+
+        for raw in table[..count * 12].chunks(12) {
+            let tag = order.u16(&raw[0..2]);
+            let field_type = order.u16(&raw[2..4]);
+            let value_count = order.u32(&raw[4..8]);
+
+            if let Some(child) = IfdKind::child(kind, tag) {
+                children.push((child, order.u32(&raw[8..12])));
+                continue;
+            }
+
+            // the size of unknown types is unknown, their value field is
+            // kept as stored so they survive a rewrite
+            let Some(size) = type_size(field_type) else {
+                entries.push(Entry {
+                    tag,
+                    field_type,
+                    count: value_count,
+                    value: raw[8..12].to_vec(),
+                });
+                continue;
+            };
+
+            let length = size
+                .checked_mul(value_count as usize)
+                .ok_or(ChunkError("EXIF value is too long"))?;
+
+            let value = if length <= 4 {
+                raw[8..8 + length].to_vec()
+            } else {
+                bytes(tiff, order.u32(&raw[8..12]) as usize, length)?.to_vec()
+            };
+
+            entries.push(Entry {
+                tag,
+                field_type,
+                count: value_count,
+                value,
+            });
+        }
+
+        if kind == IfdKind::Thumbnail {
+            self.thumbnail = Self::read_thumbnail(tiff, order, &entries)?;
+            entries.retain(|entry| entry.tag != THUMBNAIL_OFFSET);
+        }
+
+        self.ifds.push(Ifd { kind, entries });
+
+        for (child, offset) in children {
+            // a pointer repeated in a broken file would read the IFD twice
+            if self.ifd(child).is_none() {
+                self.read_ifd(tiff, offset, child)?;
+            }
+        }
+
+        Ok(order.u32(&table[count * 12..]))
+    }
+
+    fn read_thumbnail(
+        tiff: &[u8],
+        order: ByteOrder,
+        entries: &[Entry],
+    ) -> Result<Option<Vec<u8>>> {
+        let long = |tag: u16| {
+            entries
+                .iter()
+                .find(|entry| entry.tag == tag && entry.value.len() == 4)
+                .map(|entry| order.u32(&entry.value) as usize)
+        };
+
+        match (long(THUMBNAIL_OFFSET), long(THUMBNAIL_LENGTH)) {
+            (Some(offset), Some(length)) => {
+                Ok(Some(bytes(tiff, offset, length)?.to_vec()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Removes an IFD, the IFDs it points to and their tags.
+    ///
+    /// Returns the number of removed tags. IFD0 holds every other
+    /// IFD and can't be removed.
+    pub fn remove_ifd(&mut self, kind: IfdKind) -> Result<usize> {
+        if kind == IfdKind::Primary {
+            return Err(Box::new(ChunkError("IFD0 can't be removed")));
+        }
+
+        let removed = IfdKind::ALL
+            .into_iter()
+            .filter(|child| {
+                *child == kind
+                    || child.pointer().map(|(parent, _)| parent) == Some(kind)
+            })
+            .collect::<Vec<IfdKind>>();
+
+        if removed.contains(&IfdKind::Thumbnail) {
+            self.thumbnail = None;
+        }
+
+        let count = self
+            .ifds
+            .iter()
+            .filter(|ifd| removed.contains(&ifd.kind))
+            .map(|ifd| ifd.entries.len())
+            .sum();
+
+        self.ifds.retain(|ifd| !removed.contains(&ifd.kind));
+
+        Ok(count)
+    }
+
+    /// Removes every tag matching selector, a tag name like GPSLatitude
+    /// or a tag number like 0x0002 (which matches in every IFD).
+    ///
+    /// Returns the number of removed tags.
+    pub fn remove_tag(&mut self, selector: &str) -> Result<usize> {
+        let number = match selector.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).ok(),
+            None => selector.parse::<u16>().ok(),
+        };
+
+        let matches = |kind: IfdKind, entry: &Entry| match number {
+            Some(tag) => entry.tag == tag,
+            None => entry.name(kind) == Some(selector),
+        };
+
+        let known = number.is_some()
+            || TAG_NAMES.iter().any(|(_, _, name)| *name == selector);
+
+        if !known {
+            return Err(Box::new(ChunkError("Unknown EXIF tag name")));
+        }
+
+        let mut removed = 0;
+
+        for ifd in self.ifds.iter_mut() {
+            let before = ifd.entries.len();
+            let kind = ifd.kind;
+            ifd.entries.retain(|entry| !matches(kind, entry));
+            removed += before - ifd.entries.len();
+        }
+
+        Ok(removed)
+    }
+
+    /// The entries of an IFD as written: tags sorted as TIFF requires,
+    /// with placeholders for the offsets that are only known on write.
+    fn layout(&self, ifd: &Ifd) -> Vec<Entry> {
+        let placeholder = |tag: u16| Entry {
+            tag,
+            field_type: LONG,
+            count: 1,
+            value: vec![0; 4],
+        };
+
+        let mut entries = ifd.entries.clone();
+
+        for child in self.ifds.iter() {
+            if let Some((parent, tag)) = child.kind.pointer() {
+                if parent == ifd.kind {
+                    entries.push(placeholder(tag));
+                }
+            }
+        }
+
+        if ifd.kind == IfdKind::Thumbnail && self.thumbnail.is_some() {
+            entries.push(placeholder(THUMBNAIL_OFFSET));
+        }
+
+        entries.sort_by_key(|entry| entry.tag);
+        entries
+    }
+
+    /// Serializes self back into eXIf chunk data.
+    ///
+    /// Every IFD is followed by its values that don't fit into an entry.
+    /// Offsets inside a MakerNote are vendor specific and are not
+    /// rewritten, so they can break when the data before it moves.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let order = self.byte_order;
+
+        let layouts = IfdKind::ALL
+            .iter()
+            .filter_map(|kind| self.ifd(*kind))
+            .map(|ifd| (ifd.kind, self.layout(ifd)))
+            .collect::<Vec<(IfdKind, Vec<Entry>)>>();
+
+        // values are word aligned
+        let value_size = |entry: &Entry| match entry.value.len() {
+            0..=4 => 0,
+            length => length + length % 2,
+        };
+
+        let mut offsets = vec![];
+        let mut offset = 8;
+
+        for (kind, entries) in layouts.iter() {
+            offsets.push((*kind, offset));
+            offset += 2 + 12 * entries.len() + 4;
+            offset += entries.iter().map(value_size).sum::<usize>();
+        }
+
+        let thumbnail_offset = offset;
+        let offset_of = |kind: IfdKind| {
+            offsets
+                .iter()
+                .find(|(other, _)| *other == kind)
+                .map(|(_, offset)| *offset as u32)
+        };
+
+        let mut bytes = match order {
+            ByteOrder::Little => b"II*\0".to_vec(),
+            ByteOrder::Big => b"MM\0*".to_vec(),
+        };
+        bytes.extend(order.put_u32(8));
+
+        for ((kind, entries), (_, ifd_offset)) in layouts.iter().zip(offsets.iter())
+        {
+            let mut values = vec![];
+            let mut value_offset = ifd_offset + 2 + 12 * entries.len() + 4;
+
+            bytes.extend(order.put_u16(entries.len() as u16));
+
+            for entry in entries.iter() {
+                let pointer = match (IfdKind::child(*kind, entry.tag), entry.tag) {
+                    (Some(child), _) => offset_of(child),
+                    (None, THUMBNAIL_OFFSET) if *kind == IfdKind::Thumbnail => {
+                        Some(thumbnail_offset as u32)
+                    }
+                    _ => None,
+                };
+
+                bytes.extend(order.put_u16(entry.tag));
+                bytes.extend(order.put_u16(entry.field_type));
+                bytes.extend(order.put_u32(entry.count));
+
+                if let Some(pointer) = pointer {
+                    bytes.extend(order.put_u32(pointer));
+                } else if entry.value.len() <= 4 {
+                    let mut inline = entry.value.clone();
+                    inline.resize(4, 0);
+                    bytes.extend(inline);
+                } else {
+                    bytes.extend(order.put_u32(value_offset as u32));
+                    values.extend(&entry.value);
+                    values.resize(values.len() + entry.value.len() % 2, 0);
+                    value_offset += value_size(entry);
+                }
+            }
+
+            let next = match kind {
+                IfdKind::Primary => offset_of(IfdKind::Thumbnail).unwrap_or(0),
+                _ => 0,
+            };
+            bytes.extend(order.put_u32(next));
+            bytes.extend(values);
+        }
+
+        if let Some(thumbnail) = &self.thumbnail {
+            bytes.extend(thumbnail);
+        }
+
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagReport {
+    pub tag: u16,
+    pub name: Option<String>,
+    pub field_type: u16,
+    pub count: u32,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IfdReport {
+    pub ifd: IfdKind,
+    pub tags: Vec<TagReport>,
+}
+
+/// Output of exif.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExifReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub byte_order: ByteOrder,
+    pub ifds: Vec<IfdReport>,
+    pub thumbnail_size: Option<usize>,
+}
+
+impl ExifReport {
+    pub fn new(path: &str, exif: &Exif) -> ExifReport {
+        let ifds = exif
+            .ifds
+            .iter()
+            .map(|ifd| IfdReport {
+                ifd: ifd.kind,
+                tags: ifd
+                    .entries
+                    .iter()
+                    .map(|entry| TagReport {
+                        tag: entry.tag,
+                        name: entry.name(ifd.kind).map(str::to_string),
+                        field_type: entry.field_type,
+                        count: entry.count,
+                        value: entry.format_value(exif.byte_order),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ExifReport {
+            schema_version: SCHEMA_VERSION,
+            path: path.to_string(),
+            byte_order: exif.byte_order,
+            ifds,
+            thumbnail_size: exif.thumbnail.as_ref().map(Vec::len),
+        }
+    }
+}
+
+/// Every IFD with its tags, one per line.
+impl fmt::Display for ExifReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} endian EXIF",
+            self.path,
+            match self.byte_order {
+                ByteOrder::Little => "little",
+                ByteOrder::Big => "big",
+            }
+        )?;
+
+        for ifd in self.ifds.iter() {
+            write!(f, "\n{}", ifd.ifd)?;
+
+            for tag in ifd.tags.iter() {
+                write!(
+                    f,
+                    "\n  {:#06x} {}: {}",
+                    tag.tag,
+                    tag.name.as_deref().unwrap_or("unknown"),
+                    tag.value
+                )?;
+            }
+        }
+
+        if let Some(size) = self.thumbnail_size {
+            write!(f, "\nthumbnail: {} bytes", size)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Big endian EXIF with orientation, a camera model, a GPS IFD
+    /// and a thumbnail.
+    fn testing_exif() -> Exif {
+        let entry = |tag: u16, field_type: u16, count: u32, value: &[u8]| Entry {
+            tag,
+            field_type,
+            count,
+            value: value.to_vec(),
+        };
+
+        Exif {
+            byte_order: ByteOrder::Big,
+            ifds: vec![
+                Ifd {
+                    kind: IfdKind::Primary,
+                    entries: vec![
+                        entry(0x0110, 2, 8, b"Pixel 8\0"),
+                        entry(0x0112, 3, 1, &[0, 6]),
+                    ],
+                },
+                Ifd {
+                    kind: IfdKind::Exif,
+                    entries: vec![entry(0x829a, 5, 1, &[0, 0, 0, 1, 0, 0, 0, 60])],
+                },
+                Ifd {
+                    kind: IfdKind::Gps,
+                    entries: vec![
+                        entry(0x0001, 2, 2, b"N\0"),
+                        entry(
+                            0x0002,
+                            5,
+                            3,
+                            &[
+                                0, 0, 0, 37, 0, 0, 0, 1, 0, 0, 0, 46, 0, 0, 0, 1, 0,
+                                0, 0, 3, 0, 0, 0, 1,
+                            ],
+                        ),
+                    ],
+                },
+                Ifd {
+                    kind: IfdKind::Thumbnail,
+                    entries: vec![entry(THUMBNAIL_LENGTH, 4, 1, &[0, 0, 0, 3])],
+                },
+            ],
+            thumbnail: Some(vec![0xff, 0xd8, 0xff]),
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let exif = testing_exif();
+        let bytes = exif.to_bytes();
+
+        assert_eq!(&bytes[..4], b"MM\0*");
+        assert_eq!(Exif::try_from(bytes.as_slice()).unwrap(), exif);
+
+        let mut little = exif.clone();
+        little.byte_order = ByteOrder::Little;
+        little.ifds[0].entries[1].value = vec![6, 0];
+        little.ifds[3].entries[0].value = vec![3, 0, 0, 0];
+        let parsed = Exif::try_from(little.to_bytes().as_slice()).unwrap();
+        assert_eq!(
+            parsed.ifds[0].entries[1].format_value(ByteOrder::Little),
+            "6"
+        );
+    }
+
+    #[test]
+    fn test_remove_gps_keeps_orientation() {
+        let mut exif = testing_exif();
+
+        assert_eq!(exif.remove_ifd(IfdKind::Gps).unwrap(), 2);
+        assert_eq!(exif.remove_tag("Model").unwrap(), 1);
+        assert_eq!(exif.remove_tag("0x829a").unwrap(), 1);
+        assert!(exif.remove_tag("Nonsense").is_err());
+        assert!(exif.remove_ifd(IfdKind::Primary).is_err());
+
+        let parsed = Exif::try_from(exif.to_bytes().as_slice()).unwrap();
+        assert!(parsed.ifd(IfdKind::Gps).is_none());
+        assert_eq!(parsed.ifd(IfdKind::Exif).unwrap().entries, vec![]);
+        assert_eq!(
+            parsed.ifd(IfdKind::Primary).unwrap().entries[0].name(IfdKind::Primary),
+            Some("Orientation")
+        );
+        assert_eq!(parsed.thumbnail, Some(vec![0xff, 0xd8, 0xff]));
+    }
+
+    #[test]
+    fn test_report() {
+        let report = ExifReport::new("a.png", &testing_exif()).to_string();
+
+        assert!(report.contains("  0x0112 Orientation: 6"));
+        assert!(report.contains("  0x0110 Model: Pixel 8"));
+        assert!(report.contains("  0x0002 GPSLatitude: 37/1 46/1 3/1"));
+        assert!(report.contains("  0x829a ExposureTime: 1/60"));
+        assert!(report.contains("thumbnail: 3 bytes"));
+    }
+
+    #[test]
+    fn test_unknown_field_type_is_kept() {
+        let mut exif = testing_exif();
+        exif.ifds[0].entries.push(Entry {
+            tag: 0xc000,
+            field_type: 0x99,
+            count: 7,
+            value: vec![1, 2, 3, 4],
+        });
+
+        let parsed = Exif::try_from(exif.to_bytes().as_slice()).unwrap();
+        assert_eq!(parsed, exif);
+    }
+
+    #[test]
+    fn test_broken_thumbnail_ifd_is_dropped() {
+        let mut bytes = testing_exif().to_bytes();
+        // IFD0 holds 4 entries (2 tags and the Exif and GPS pointers)
+        let next = 8 + 2 + 4 * 12;
+        bytes[next..next + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let parsed = Exif::try_from(bytes.as_slice()).unwrap();
+        assert!(parsed.ifd(IfdKind::Thumbnail).is_none());
+        assert_eq!(parsed.thumbnail, None);
+        assert_eq!(parsed.ifd(IfdKind::Primary).unwrap().entries.len(), 2);
+        assert_eq!(parsed.warnings.len(), 1);
+        assert!(parsed.warnings[0].starts_with("dropped IFD1 (thumbnail)"));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(Exif::try_from(&b"II*\0"[..]).is_err());
+        assert!(Exif::try_from(&b"II*\0\x08\0\0\0\x01"[..]).is_err());
+        // an entry pointing past the end of the data
+        let mut bytes =
+            b"MM\0*\0\0\0\x08\0\x01\x01\x0f\0\x02\0\0\0\x10\0\0\xff\xff".to_vec();
+        bytes.extend([0; 4]);
+        assert!(Exif::try_from(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use std::error;
+use std::fmt;
+
+use reed_solomon::{Decoder, Encoder};
+
+use crate::Result;
+
+/// 0xF5 never appears in UTF-8 so plain text messages are never mistaken
+/// for encoded payloads.
+const MAGIC: [u8; 2] = [0xF5, 0xEC];
+const HEADER_LENGTH: usize = 7;
+const HEADER_COPIES: usize = 3;
+const BLOCK_LENGTH: usize = 255;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FecError {
+    InvalidParity,
+    Unrecoverable,
+    TooLong,
+}
+
+impl fmt::Display for FecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FecError::InvalidParity => {
+                write!(f, "Parity must be between 2 and 128 symbols per block")
+            }
+            FecError::Unrecoverable => {
+                write!(f, "Payload is too damaged to be recovered")
+            }
+            FecError::TooLong => {
+                write!(f, "Payload is too long to be error corrected")
+            }
+        }
+    }
+}
+
+impl error::Error for FecError {}
+
+fn header(parity: u8, length: u32) -> Vec<u8> {
+    MAGIC
+        .iter()
+        .chain([parity].iter())
+        .chain(length.to_be_bytes().iter())
+        .copied()
+        .collect()
+}
+
+/// Majority vote over the header copies, None if the data is too short
+/// or does not start with an encoded payload header.
+fn read_header(data: &[u8]) -> Option<(u8, usize)> {
+    if data.len() < HEADER_LENGTH * HEADER_COPIES {
+        return None;
+    }
+
+    let header = (0..HEADER_LENGTH)
+        .map(|idx| {
+            let a = data[idx];
+            let b = data[HEADER_LENGTH + idx];
+            let c = data[2 * HEADER_LENGTH + idx];
+            if a == b || a == c {
+                a
+            } else {
+                b
+            }
+        })
+        .collect::<Vec<u8>>();
+
+    if header[0..2] != MAGIC {
+        return None;
+    }
+
+    let length = u32::from_be_bytes([header[3], header[4], header[5], header[6]]);
+    Some((header[2], length as usize))
+}
+
+/// True if data starts with an error corrected payload header.
+pub fn is_encoded(data: &[u8]) -> bool {
+    read_header(data).is_some()
+}
+
+/// Adds Reed-Solomon parity symbols to the payload.
+///
+/// The payload is cut into blocks of 255 - parity data bytes, each block
+/// gets parity symbols and can repair up to parity / 2 damaged bytes.
+/// Blocks are interleaved byte by byte so a burst of damage is spread over
+/// many blocks instead of wiping out one.
+///
+/// Layout: [header x3, interleaved blocks]
+/// header: [magic (2 bytes), parity, payload length (4 bytes)]
+///
+/// The header is stored three times and recovered by majority vote per byte.
+pub fn encode(payload: &[u8], parity: u8) -> Result<Vec<u8>> {
+    if !(2..=128).contains(&parity) {
+        return Err(Box::new(FecError::InvalidParity));
+    }
+
+    let length = u32::try_from(payload.len()).map_err(|_| FecError::TooLong)?;
+
+    let data_length = BLOCK_LENGTH - parity as usize;
+    let encoder = Encoder::new(parity as usize);
+
+    let blocks = payload
+        .chunks(data_length)
+        .map(|block| {
+            // pad the final block so every block has the same length
+            let mut padded = block.to_vec();
+            padded.resize(data_length, 0);
+            encoder.encode(&padded).to_vec()
+        })
+        .collect::<Vec<Vec<u8>>>();
+
+    let header = header(parity, length);
+
+    Ok(header
+        .iter()
+        .cycle()
+        .take(HEADER_LENGTH * HEADER_COPIES)
+        .copied()
+        .chain(
+            (0..BLOCK_LENGTH)
+                .flat_map(|idx| blocks.iter().map(move |block| block[idx])),
+        )
+        .collect())
+}
+
+/// Repairs and returns the payload along with the number of repaired
+/// symbols.
+pub fn decode(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let (parity, length) = read_header(data).ok_or(FecError::Unrecoverable)?;
+
+    if !(2..=128).contains(&parity) {
+        return Err(Box::new(FecError::Unrecoverable));
+    }
+
+    let body = &data[HEADER_LENGTH * HEADER_COPIES..];
+
+    if !body.len().is_multiple_of(BLOCK_LENGTH) {
+        return Err(Box::new(FecError::Unrecoverable));
+    }
+
+    let block_count = body.len() / BLOCK_LENGTH;
+    let decoder = Decoder::new(parity as usize);
+    let mut payload = Vec::with_capacity(block_count * BLOCK_LENGTH);
+    let mut repaired = 0;
+
+    for block_idx in 0..block_count {
+        let block = (0..BLOCK_LENGTH)
+            .map(|idx| body[idx * block_count + block_idx])
+            .collect::<Vec<u8>>();
+
+        let (corrected, fixed) = decoder
+            .correct_err_count(&block, None)
+            .map_err(|_| FecError::Unrecoverable)?;
+
+        payload.extend_from_slice(corrected.data());
+        repaired += fixed;
+    }
+
+    if length > payload.len() {
+        return Err(Box::new(FecError::Unrecoverable));
+    }
+
+    payload.truncate(length);
+
+    Ok((payload, repaired))
+}
+
+/// Decodes data if it is error corrected, otherwise returns it unchanged.
+///
+/// The second value holds the number of repaired symbols for encoded data.
+pub fn decode_if_encoded(data: &[u8]) -> Result<(Vec<u8>, Option<usize>)> {
+    if !is_encoded(data) {
+        return Ok((data.to_vec(), None));
+    }
+
+    let (payload, repaired) = decode(data)?;
+    Ok((payload, Some(repaired)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_payload() -> Vec<u8> {
+        (0..1000).map(|n| (n * 7 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let payload = testing_payload();
+        let encoded = encode(&payload, 16).unwrap();
+
+        assert!(is_encoded(&encoded));
+        assert_eq!(decode(&encoded).unwrap(), (payload, 0));
+    }
+
+    #[test]
+    fn test_repairs_damage() {
+        let payload = testing_payload();
+        let mut encoded = encode(&payload, 16).unwrap();
+
+        // a burst of damage plus a damaged header copy
+        for byte in encoded[100..140].iter_mut() {
+            *byte ^= 0xFF;
+        }
+        encoded[3] ^= 0xFF;
+
+        let (decoded, repaired) = decode(&encoded).unwrap();
+        assert_eq!(decoded, payload);
+        assert_eq!(repaired, 40);
+    }
+
+    #[test]
+    fn test_unrecoverable_damage() {
+        let payload = testing_payload();
+        let mut encoded = encode(&payload, 4).unwrap();
+
+        for byte in encoded[21..200].iter_mut() {
+            *byte ^= 0x55;
+        }
+
+        let error = decode(&encoded)
+            .unwrap_err()
+            .downcast::<FecError>()
+            .unwrap();
+        assert_eq!(*error, FecError::Unrecoverable);
+    }
+
+    #[test]
+    fn test_plain_text_is_not_encoded() {
+        let message = "This is where your secret message will be!".as_bytes();
+        assert!(!is_encoded(message));
+        assert_eq!(
+            decode_if_encoded(message).unwrap(),
+            (message.to_vec(), None)
+        );
+    }
+
+    #[test]
+    fn test_invalid_parity() {
+        assert!(encode(b"message", 0).is_err());
+        assert!(encode(b"message", 200).is_err());
+    }
+}
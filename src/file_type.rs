@@ -1,21 +1,174 @@
+use std::error;
+use std::fmt;
 use std::path::PathBuf;
 
+use url::Url;
+
+use crate::Result;
+
+/// Media type written when a data URI output does not name one.
+const DEFAULT_MEDIA_TYPE: &str = "image/png";
+
+/// Where a png is read from or written to.
 #[derive(Debug, PartialEq)]
 pub enum FileType {
     Local(PathBuf),
+    /// http or https url
     Url(String),
+    /// `-`, stdin when reading and stdout when writing
+    Stdio,
+    /// data:[<media type>][;base64],<data>
+    DataUri(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileTypeError(pub &'static str);
+
+impl fmt::Display for FileTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let FileTypeError(msg) = self;
+        write!(f, "{}", msg)
+    }
 }
 
+impl error::Error for FileTypeError {}
+
+/// Anything that doesn't parse as a url with a known scheme is a
+/// local path, so `httpdump.png` or `C:\image.png` stay files.
 impl From<String> for FileType {
     fn from(file_path: String) -> Self {
-        if file_path.starts_with("http") || file_path.starts_with("https") {
-            return FileType::Url(file_path);
+        if file_path == "-" {
+            return FileType::Stdio;
         }
 
-        FileType::Local(PathBuf::from(file_path))
+        let url = match Url::parse(&file_path) {
+            Ok(url) => url,
+            Err(_) => return FileType::Local(PathBuf::from(file_path)),
+        };
+
+        match url.scheme() {
+            "http" | "https" => FileType::Url(file_path),
+            "data" => FileType::DataUri(file_path),
+            "file" => match url.to_file_path() {
+                Ok(path) => FileType::Local(path),
+                // file urls naming a remote host can't be opened
+                Err(_) => FileType::Local(PathBuf::from(file_path)),
+            },
+            _ => FileType::Local(PathBuf::from(file_path)),
+        }
+    }
+}
+
+impl FileType {
+    /// True if writing to this location prints to stdout.
+    pub fn is_stdout(&self) -> bool {
+        matches!(self, FileType::Stdio | FileType::DataUri(_))
+    }
+}
+
+/// Short name of a location read from for status messages, data
+/// URIs can be megabytes long.
+pub fn display_name(file_path: &str) -> String {
+    match FileType::from(file_path.to_string()) {
+        FileType::Stdio => "stdin".to_string(),
+        FileType::DataUri(_) => "data URI".to_string(),
+        _ => file_path.to_string(),
+    }
+}
+
+/// Short name of a location written to, see display_name.
+pub fn output_name(write_path: &str) -> String {
+    match FileType::from(write_path.to_string()) {
+        FileType::Stdio => "stdout".to_string(),
+        _ => display_name(write_path),
     }
 }
 
+/// Splits a data URI into its header (media type and parameters)
+/// and data.
+fn split_data_uri(uri: &str) -> Result<(&str, &str)> {
+    let rest = uri
+        .strip_prefix("data:")
+        .ok_or(FileTypeError("data URI must start with data:"))?;
+
+    Ok(rest
+        .split_once(',')
+        .ok_or(FileTypeError("data URI has no comma before the data"))?)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|value| value as u8)
+}
+
+/// Decodes %XX escapes, anything else is kept as is.
+fn percent_decode(data: &str) -> Result<Vec<u8>> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx] != b'%' {
+            decoded.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+
+        let high = bytes.get(idx + 1).copied().and_then(hex_value);
+        let low = bytes.get(idx + 2).copied().and_then(hex_value);
+
+        match (high, low) {
+            (Some(high), Some(low)) => decoded.push(high << 4 | low),
+            _ => {
+                return Err(Box::new(FileTypeError(
+                    "data URI has an invalid % escape",
+                )))
+            }
+        }
+        idx += 3;
+    }
+
+    Ok(decoded)
+}
+
+/// Bytes held by a data URI, base64 or percent encoded.
+pub fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+    let (header, data) = split_data_uri(uri)?;
+
+    if header.ends_with(";base64") {
+        // base64 in html is often wrapped over several lines
+        let data = data
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect::<String>();
+        return Ok(base64::decode(percent_decode(&data)?)?);
+    }
+
+    percent_decode(data)
+}
+
+/// Base64 data URI holding bytes.
+///
+/// The media type is taken from target (a data URI output location
+/// such as `data:` or `data:image/png;base64,`), image/png if it
+/// names none.
+pub fn encode_data_uri(target: &str, bytes: &[u8]) -> String {
+    let header = target
+        .strip_prefix("data:")
+        .map(|rest| rest.split(',').next().unwrap_or_default())
+        .unwrap_or_default();
+    let media_type = header.split(';').next().unwrap_or_default();
+
+    format!(
+        "data:{};base64,{}",
+        if media_type.is_empty() {
+            DEFAULT_MEDIA_TYPE
+        } else {
+            media_type
+        },
+        base64::encode(bytes)
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +190,56 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn handles_local_path_starting_with_http() {
+        for path in ["httpdump.png", "https-logo.png", "ftp://host/a.png"] {
+            assert_eq!(
+                FileType::from(path.to_string()),
+                FileType::Local(PathBuf::from(path))
+            );
+        }
+    }
+
+    #[test]
+    fn handles_stdio_file_url_and_data_uri() {
+        assert_eq!(FileType::from("-".to_string()), FileType::Stdio);
+        assert_eq!(
+            FileType::from("file:///tmp/my%20image.png".to_string()),
+            FileType::Local(PathBuf::from("/tmp/my image.png"))
+        );
+        assert_eq!(
+            FileType::from("data:,".to_string()),
+            FileType::DataUri("data:,".to_string())
+        );
+    }
+
+    #[test]
+    fn display_names() {
+        assert_eq!(display_name("-"), "stdin");
+        assert_eq!(output_name("-"), "stdout");
+        assert_eq!(display_name("data:,abc"), "data URI");
+        assert_eq!(output_name("data:,abc"), "data URI");
+        assert_eq!(output_name("a.png"), "a.png");
+    }
+
+    #[test]
+    fn data_uri_round_trip() {
+        let bytes = [0x89, b'P', b'N', b'G', 0, 255];
+        let uri = encode_data_uri("data:", &bytes);
+
+        assert_eq!(uri, "data:image/png;base64,iVBORwD/");
+        assert_eq!(decode_data_uri(&uri).unwrap(), bytes);
+        assert_eq!(
+            encode_data_uri("data:application/octet-stream;base64,", b"hi"),
+            "data:application/octet-stream;base64,aGk="
+        );
+        assert_eq!(
+            decode_data_uri("data:image/png;base64,iVBO\n  RwD/").unwrap(),
+            bytes
+        );
+        assert_eq!(decode_data_uri("data:,a%20b").unwrap(), b"a b");
+        assert!(decode_data_uri("data:,a%2").is_err());
+        assert!(decode_data_uri("data:image/png;base64").is_err());
+    }
 }
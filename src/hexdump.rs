@@ -0,0 +1,65 @@
+/// Bytes shown per hex dump line.
+const LINE_LENGTH: usize = 16;
+
+/// Printable ascii as is, everything else as a dot.
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Formats data like `hexdump -C`: offset, 16 hex bytes split in two
+/// groups of 8 and the printable characters.
+///
+/// Offsets start at base_offset so they can point into the whole file.
+pub fn hex_dump(data: &[u8], base_offset: usize) -> String {
+    data.chunks(LINE_LENGTH)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex = (0..LINE_LENGTH)
+                .map(|idx| {
+                    let separator = if idx == LINE_LENGTH / 2 { " " } else { "" };
+                    match bytes.get(idx) {
+                        Some(byte) => format!("{}{:02x} ", separator, byte),
+                        None => format!("{}   ", separator),
+                    }
+                })
+                .collect::<String>();
+
+            format!(
+                "{:08x}  {} |{}|",
+                base_offset + line * LINE_LENGTH,
+                hex,
+                bytes.iter().map(|&b| printable(b)).collect::<String>()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        let data = b"This is where your secret message will be!";
+
+        assert_eq!(
+            hex_dump(data, 0x20),
+            "00000020  54 68 69 73 20 69 73 20  77 68 65 72 65 20 79 6f  |This is where yo|\n\
+             00000030  75 72 20 73 65 63 72 65  74 20 6d 65 73 73 61 67  |ur secret messag|\n\
+             00000040  65 20 77 69 6c 6c 20 62  65 21                    |e will be!|"
+        );
+    }
+
+    #[test]
+    fn test_hex_dump_non_printable() {
+        assert_eq!(
+            hex_dump(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a], 0),
+            "00000000  89 50 4e 47 0d 0a                                 |.PNG..|"
+        );
+    }
+}
@@ -0,0 +1,477 @@
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::redirect::Policy;
+use reqwest::Proxy;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::limits::{self, Limits};
+use crate::write::{self, WriteOptions};
+use crate::Result;
+
+/// Content types accepted for downloads. Servers often send
+/// octet-stream for files, a missing content type is accepted too.
+const CONTENT_TYPES: [&str; 3] =
+    ["image/png", "image/apng", "application/octet-stream"];
+
+/// How http/https urls are fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// whole request, from connecting to the last body byte
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// largest body accepted, the max file size if None
+    pub max_download_size: Option<u64>,
+    pub max_redirects: usize,
+    /// if not empty only these hosts (and their subdomains) are fetched
+    pub allow_hosts: Vec<String>,
+    /// never fetched, wins over allow_hosts
+    pub deny_hosts: Vec<String>,
+    /// extra request headers as "Name: value"
+    pub headers: Vec<String>,
+    /// proxy url for every request, the HTTP(S)_PROXY environment
+    /// variables are used otherwise
+    pub proxy: Option<String>,
+    /// downloads are stored here and read back instead of refetched
+    pub cache_dir: Option<PathBuf>,
+    /// cached downloads older than this are fetched again
+    pub cache_max_age: Duration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            max_download_size: None,
+            max_redirects: 5,
+            allow_hosts: vec![],
+            deny_hosts: vec![],
+            headers: vec![],
+            proxy: None,
+            cache_dir: None,
+            cache_max_age: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HttpError {
+    InvalidUrl(String),
+    HostNotAllowed(String),
+    InvalidHeader(String),
+    Status(u16),
+    ContentType(String),
+    /// content length, limit
+    TooLarge(u64, u64),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::InvalidUrl(url) => write!(f, "{} is not an http(s) url", url),
+            HttpError::HostNotAllowed(host) => {
+                write!(f, "host {} is not allowed", host)
+            }
+            HttpError::InvalidHeader(header) => {
+                write!(f, "header {:?} is not of the form \"Name: value\"", header)
+            }
+            HttpError::Status(status) => write!(f, "server answered {}", status),
+            HttpError::ContentType(content_type) => {
+                write!(f, "content type {} is not a png", content_type)
+            }
+            HttpError::TooLarge(length, limit) => write!(
+                f,
+                "download of {} bytes exceeds the {} byte limit",
+                length, limit
+            ),
+        }
+    }
+}
+
+impl error::Error for HttpError {}
+
+/// True if host is pattern or one of its subdomains.
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let pattern = pattern.trim_start_matches("*.").to_ascii_lowercase();
+
+    host == pattern || host.ends_with(&format!(".{}", pattern))
+}
+
+fn is_host_allowed(
+    host: &str,
+    allow_hosts: &[String],
+    deny_hosts: &[String],
+) -> bool {
+    if deny_hosts.iter().any(|pattern| host_matches(host, pattern)) {
+        return false;
+    }
+
+    allow_hosts.is_empty()
+        || allow_hosts
+            .iter()
+            .any(|pattern| host_matches(host, pattern))
+}
+
+/// Checks scheme and host of a url, requests and every redirect go
+/// through here.
+fn check_url(
+    url: &Url,
+    options: &HttpOptions,
+) -> std::result::Result<(), HttpError> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(HttpError::InvalidUrl(url.to_string()));
+    }
+
+    let host = url.host_str().unwrap_or_default();
+
+    if !is_host_allowed(host, &options.allow_hosts, &options.deny_hosts) {
+        return Err(HttpError::HostNotAllowed(host.to_string()));
+    }
+
+    Ok(())
+}
+
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue)> {
+    let invalid = || HttpError::InvalidHeader(header.to_string());
+    let (name, value) = header.split_once(':').ok_or_else(invalid)?;
+
+    Ok((
+        HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?,
+        HeaderValue::from_str(value.trim()).map_err(|_| invalid())?,
+    ))
+}
+
+fn client(options: &HttpOptions) -> Result<Client> {
+    let mut headers = HeaderMap::new();
+
+    for header in options.headers.iter() {
+        let (name, value) = parse_header(header)?;
+        headers.append(name, value);
+    }
+
+    let redirect_options = options.clone();
+    let policy = Policy::custom(move |attempt| {
+        if attempt.previous().len() > redirect_options.max_redirects {
+            return attempt.error("too many redirects");
+        }
+
+        match check_url(attempt.url(), &redirect_options) {
+            Ok(()) => attempt.follow(),
+            Err(error) => attempt.error(error),
+        }
+    });
+
+    let mut builder = Client::builder()
+        .timeout(options.timeout)
+        .connect_timeout(options.connect_timeout)
+        .redirect(policy)
+        .default_headers(headers);
+
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(Proxy::all(proxy.as_str())?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Location of a cached download, named after the hash of its url and
+/// request headers, so a different token never reads another's download.
+fn cache_path(cache_dir: &Path, url: &str, headers: &[String]) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+
+    for header in headers {
+        hasher.update(b"\n");
+        hasher.update(header.as_bytes());
+    }
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    cache_dir.join(format!("{}.png", hash))
+}
+
+/// Downloads the bytes at an http/https url.
+///
+/// The host must pass the allow and deny lists, also after every
+/// redirect. The body must be a png (or untyped) and is read no further
+/// than the download limit. With a cache dir a url is only fetched again
+/// once its cached download is older than the cache max age.
+pub fn fetch(url: &str, options: &HttpOptions, limits: &Limits) -> Result<Vec<u8>> {
+    let parsed =
+        Url::parse(url).map_err(|_| HttpError::InvalidUrl(url.to_string()))?;
+    check_url(&parsed, options)?;
+
+    let limit = options.max_download_size.unwrap_or(limits.max_file_size);
+    let cached = options
+        .cache_dir
+        .as_ref()
+        .map(|dir| cache_path(dir, url, &options.headers));
+
+    if let Some(cached) = cached.as_ref().filter(|cached| {
+        fs::metadata(cached)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age <= options.cache_max_age)
+    }) {
+        return limits::read_limited(fs::File::open(cached)?, limit);
+    }
+
+    let response = client(options)?.get(parsed).send()?;
+
+    if !response.status().is_success() {
+        return Err(Box::new(HttpError::Status(response.status().as_u16())));
+    }
+
+    if let Some(length) = response.content_length().filter(|&length| length > limit)
+    {
+        return Err(Box::new(HttpError::TooLarge(length, limit)));
+    }
+
+    if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
+        let content_type = content_type.to_str().unwrap_or_default();
+        let media_type = content_type.split(';').next().unwrap_or_default().trim();
+
+        if !CONTENT_TYPES.contains(&media_type.to_ascii_lowercase().as_str()) {
+            return Err(Box::new(HttpError::ContentType(content_type.to_string())));
+        }
+    }
+
+    let bytes = limits::read_limited(response, limit)?;
+
+    if let Some(cached) = cached {
+        if let Some(dir) = cached.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // atomic so a concurrent run never reads a half written file
+        write::write_atomic(&cached, &bytes, &WriteOptions::default())?;
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write::testing_dir;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    /// Serves one canned response per connection and passes every
+    /// request head back. Returns the base url and the request heads.
+    fn stub(responses: Vec<String>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = match listener.accept() {
+                    Ok(connection) => connection,
+                    Err(_) => return,
+                };
+
+                let mut head = vec![];
+                let mut byte = [0];
+                while !head.ends_with(b"\r\n\r\n")
+                    && stream.read(&mut byte).unwrap_or(0) == 1
+                {
+                    head.push(byte[0]);
+                }
+
+                let _ = sender.send(String::from_utf8_lossy(&head).to_string());
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        (base, receiver)
+    }
+
+    fn response(content_type: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        )
+    }
+
+    fn redirect(location: &str) -> String {
+        format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            location
+        )
+    }
+
+    #[test]
+    fn test_fetch_sends_headers() {
+        let (base, requests) = stub(vec![response("image/png", "png bytes")]);
+        let options = HttpOptions {
+            headers: vec!["Authorization: Bearer token".to_string()],
+            ..HttpOptions::default()
+        };
+
+        let bytes =
+            fetch(&format!("{}/a.png", base), &options, &Limits::default()).unwrap();
+
+        assert_eq!(bytes, b"png bytes");
+        let head = requests.recv().unwrap().to_ascii_lowercase();
+        assert!(head.starts_with("get /a.png http/1.1"));
+        assert!(head.contains("authorization: bearer token"));
+    }
+
+    #[test]
+    fn test_fetch_rejects_large_and_non_png_bodies() {
+        let (base, _requests) = stub(vec![
+            response("image/png", "0123456789"),
+            response("text/html; charset=utf-8", "<b>"),
+        ]);
+        let options = HttpOptions {
+            max_download_size: Some(5),
+            ..HttpOptions::default()
+        };
+
+        let error = fetch(&base, &options, &Limits::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "download of 10 bytes exceeds the 5 byte limit"
+        );
+
+        let error = fetch(&base, &options, &Limits::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "content type text/html; charset=utf-8 is not a png"
+        );
+    }
+
+    #[test]
+    fn test_host_policy() {
+        assert!(host_matches("cdn.example.com", "example.com"));
+        assert!(host_matches("Example.com", "*.example.com"));
+        assert!(!host_matches("badexample.com", "example.com"));
+
+        let allow = vec!["example.com".to_string()];
+        let deny = vec!["private.example.com".to_string()];
+        assert!(is_host_allowed("img.example.com", &allow, &deny));
+        assert!(!is_host_allowed("private.example.com", &allow, &deny));
+        assert!(!is_host_allowed("example.org", &allow, &deny));
+
+        let options = HttpOptions {
+            deny_hosts: vec!["127.0.0.1".to_string()],
+            ..HttpOptions::default()
+        };
+        let error = fetch("http://127.0.0.1:1/a.png", &options, &Limits::default())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "host 127.0.0.1 is not allowed");
+    }
+
+    #[test]
+    fn test_redirects() {
+        let (base, _requests) = stub(vec![
+            redirect("/b.png"),
+            response("image/png", "moved"),
+            redirect("/c.png"),
+            redirect("/d.png"),
+            redirect("http://localhost/e.png"),
+        ]);
+        let options = HttpOptions {
+            max_redirects: 1,
+            allow_hosts: vec!["127.0.0.1".to_string()],
+            ..HttpOptions::default()
+        };
+
+        let bytes = fetch(&base, &options, &Limits::default()).unwrap();
+        assert_eq!(bytes, b"moved");
+
+        // two redirects in a row
+        assert!(fetch(&base, &options, &Limits::default()).is_err());
+
+        // redirect to a host outside the allow list
+        let error = fetch(&base, &options, &Limits::default()).unwrap_err();
+        assert!(format!("{:?}", error).contains("HostNotAllowed"));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let options = HttpOptions {
+            timeout: Duration::from_millis(200),
+            ..HttpOptions::default()
+        };
+
+        // the listener accepts the connection but never answers
+        assert!(fetch(&url, &options, &Limits::default()).is_err());
+        drop(listener);
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache_dir = testing_dir("http_cache");
+
+        let (base, _requests) = stub(vec![response("image/png", "cached")]);
+        let options = HttpOptions {
+            cache_dir: Some(cache_dir.to_path_buf()),
+            ..HttpOptions::default()
+        };
+        let url = format!("{}/a.png", base);
+
+        assert_eq!(
+            fetch(&url, &options, &Limits::default()).unwrap(),
+            b"cached"
+        );
+        // the stub only answers once, the second fetch must hit the cache
+        assert_eq!(
+            fetch(&url, &options, &Limits::default()).unwrap(),
+            b"cached"
+        );
+    }
+
+    #[test]
+    fn test_cache_key_and_max_age() {
+        let dir = Path::new("cache");
+        let url = "https://example.com/a.png";
+        let token = |value: &str| vec![format!("Authorization: Bearer {}", value)];
+
+        assert_eq!(cache_path(dir, url, &[]), cache_path(dir, url, &[]));
+        assert_ne!(
+            cache_path(dir, url, &token("a")),
+            cache_path(dir, url, &token("b"))
+        );
+        assert_ne!(cache_path(dir, url, &[]), cache_path(dir, url, &token("a")));
+
+        let cache_dir = testing_dir("http_cache_max_age");
+
+        let (base, requests) = stub(vec![
+            response("image/png", "old"),
+            response("image/png", "new"),
+        ]);
+        let options = HttpOptions {
+            cache_dir: Some(cache_dir.to_path_buf()),
+            cache_max_age: Duration::ZERO,
+            ..HttpOptions::default()
+        };
+        let url = format!("{}/a.png", base);
+
+        assert_eq!(fetch(&url, &options, &Limits::default()).unwrap(), b"old");
+        // an expired download is fetched again
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(fetch(&url, &options, &Limits::default()).unwrap(), b"new");
+        assert_eq!(requests.iter().take(2).count(), 2);
+    }
+}
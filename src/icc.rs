@@ -0,0 +1,210 @@
+use std::fmt;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+use crate::chunk_error::ChunkError;
+use crate::{Error, Result};
+
+/// Length of the fixed header every ICC profile starts with.
+const HEADER_LENGTH: usize = 128;
+
+/// Four character signature, trailing spaces and unset (null) bytes trimmed.
+fn signature(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches([' ', '\0'])
+        .to_string()
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Basic fields of an ICC profile header.
+/// https://www.color.org/specification/ICC.1-2022-05.pdf section 7.2
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IccHeader {
+    /// profile length in bytes
+    pub size: u32,
+    /// preferred color management module, e.g. appl or lcms
+    pub cmm: String,
+    /// major.minor.bugfix
+    pub version: String,
+    /// e.g. mntr for displays, prtr for printers
+    pub device_class: String,
+    /// color space of the image data, e.g. RGB or GRAY
+    pub color_space: String,
+    /// profile connection space, XYZ or Lab
+    pub connection_space: String,
+    /// creation time, YYYY-MM-DDTHH:MM:SS
+    pub created: String,
+    pub rendering_intent: u32,
+    pub creator: String,
+}
+
+impl TryFrom<&[u8]> for IccHeader {
+    type Error = Error;
+
+    fn try_from(profile: &[u8]) -> Result<Self> {
+        if profile.len() < HEADER_LENGTH || &profile[36..40] != b"acsp" {
+            return Err(Box::new(ChunkError("Not an ICC profile")));
+        }
+
+        let date = (0..6)
+            .map(|idx| read_u16(&profile[24 + idx * 2..]))
+            .collect::<Vec<u16>>();
+
+        Ok(IccHeader {
+            size: read_u32(&profile[0..4]),
+            cmm: signature(&profile[4..8]),
+            version: format!(
+                "{}.{}.{}",
+                profile[8],
+                profile[9] >> 4,
+                profile[9] & 0x0f
+            ),
+            device_class: signature(&profile[12..16]),
+            color_space: signature(&profile[16..20]),
+            connection_space: signature(&profile[20..24]),
+            created: format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                date[0], date[1], date[2], date[3], date[4], date[5]
+            ),
+            rendering_intent: read_u32(&profile[64..68]),
+            creator: signature(&profile[80..84]),
+        })
+    }
+}
+
+impl fmt::Display for IccHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let device_class = match self.device_class.as_str() {
+            "scnr" => "input",
+            "mntr" => "display",
+            "prtr" => "output",
+            "link" => "device link",
+            "spac" => "color space",
+            "abst" => "abstract",
+            "nmcl" => "named color",
+            other => other,
+        };
+
+        write!(
+            f,
+            "v{} {} {} -> {}, {} bytes",
+            self.version,
+            device_class,
+            self.color_space,
+            self.connection_space,
+            self.size
+        )
+    }
+}
+
+/// A profile name follows the tEXt keyword rules: 1-79 printable
+/// Latin-1 characters without leading, trailing or consecutive spaces.
+pub fn validate_name(name: &str) -> Result<()> {
+    let printable = name
+        .chars()
+        .all(|c| (' '..='~').contains(&c) || ('\u{a1}'..='\u{ff}').contains(&c));
+
+    if name.is_empty()
+        || name.chars().count() > 79
+        || !printable
+        || name.starts_with(' ')
+        || name.ends_with(' ')
+        || name.contains("  ")
+    {
+        return Err(Box::new(ChunkError(
+            "Profile name must be 1-79 printable Latin-1 characters without extra spaces",
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn compress(profile: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::best());
+    encoder.write_all(profile)?;
+    Ok(encoder.finish()?)
+}
+
+/// Reads the header of a compressed profile without inflating the
+/// rest, so describing an iCCP chunk needs no size limit.
+pub fn compressed_header(compressed: &[u8]) -> Result<IccHeader> {
+    let mut header = vec![];
+
+    ZlibDecoder::new(compressed)
+        .take(HEADER_LENGTH as u64)
+        .read_to_end(&mut header)?;
+
+    IccHeader::try_from(header.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits;
+
+    fn testing_profile() -> Vec<u8> {
+        let mut profile = vec![0; HEADER_LENGTH + 4];
+        profile[0..4].copy_from_slice(&(HEADER_LENGTH as u32 + 4).to_be_bytes());
+        profile[4..8].copy_from_slice(b"lcms");
+        profile[8..10].copy_from_slice(&[4, 0x30]);
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[24..26].copy_from_slice(&2024u16.to_be_bytes());
+        profile[27] = 5;
+        profile[29] = 17;
+        profile[36..40].copy_from_slice(b"acsp");
+        profile
+    }
+
+    #[test]
+    fn test_header() {
+        let header = IccHeader::try_from(testing_profile().as_slice()).unwrap();
+
+        assert_eq!(header.version, "4.3.0");
+        assert_eq!(header.color_space, "RGB");
+        assert_eq!(header.creator, "");
+        assert_eq!(header.created, "2024-05-17T00:00:00");
+        assert_eq!(header.to_string(), "v4.3.0 display RGB -> XYZ, 132 bytes");
+
+        assert!(IccHeader::try_from(&testing_profile()[..100]).is_err());
+        assert!(IccHeader::try_from(&[0; HEADER_LENGTH][..]).is_err());
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let profile = testing_profile();
+        let compressed = compress(&profile).unwrap();
+
+        let limit = profile.len() as u64;
+
+        assert_eq!(limits::inflate(&compressed, limit).unwrap(), profile);
+        assert!(limits::inflate(&compressed, limit - 1).is_err());
+        assert!(limits::inflate(b"not zlib", limit).is_err());
+        assert_eq!(
+            compressed_header(&compressed).unwrap(),
+            IccHeader::try_from(profile.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("Display P3").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name(" leading").is_err());
+        assert!(validate_name("two  spaces").is_err());
+        assert!(validate_name(&"x".repeat(80)).is_err());
+        assert!(validate_name("tab\there").is_err());
+    }
+}
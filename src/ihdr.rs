@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::chunk_type::ChunkType;
+use crate::{Error, Result};
+
+/// Adam7 passes as (x start, y start, x step, y step).
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The image header, always the first chunk of a png file.
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+
+    /// Samples per pixel of a color type, None if the color type is invalid.
+    pub fn channels(color_type: u8) -> Option<u64> {
+        match color_type {
+            0 | 3 => Some(1),
+            2 => Some(3),
+            4 => Some(2),
+            6 => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Width and height of every pass holding pixels, a single pass
+    /// for images without interlacing.
+    pub fn passes(&self) -> Vec<(u64, u64)> {
+        let (width, height) = (self.width as u64, self.height as u64);
+
+        if self.interlace_method == 0 {
+            return vec![(width, height)];
+        }
+
+        ADAM7
+            .iter()
+            .map(|&(x, y, dx, dy)| {
+                (
+                    (width + dx as u64 - 1 - x as u64) / dx as u64,
+                    (height + dy as u64 - 1 - y as u64) / dy as u64,
+                )
+            })
+            // passes without pixels store no rows at all
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .collect()
+    }
+
+    /// Bytes of a scanline width pixels wide, without the filter byte.
+    pub fn row_length(&self, width: u64) -> Option<u64> {
+        let bits = Ihdr::channels(self.color_type)? * self.bit_depth as u64;
+        Some((width * bits).div_ceil(8))
+    }
+
+    /// Length of the filtered, decompressed image data described by self.
+    ///
+    /// Saturates instead of overflowing for absurd dimensions.
+    pub fn image_data_length(&self) -> Option<u64> {
+        self.row_length(0)?;
+
+        Some(
+            self.passes()
+                .into_iter()
+                .map(|(width, height)| {
+                    height.saturating_mul(1 + self.row_length(width).unwrap_or(0))
+                })
+                .fold(0, u64::saturating_add),
+        )
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let data = self
+            .width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(
+                [
+                    self.bit_depth,
+                    self.color_type,
+                    self.compression_method,
+                    self.filter_method,
+                    self.interlace_method,
+                ]
+                .iter(),
+            )
+            .copied()
+            .collect();
+
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().to_string() != "IHDR" {
+            return Err(Box::new(ChunkError("Chunk is not an IHDR chunk")));
+        }
+
+        let data = chunk.data();
+
+        if data.len() != Ihdr::LENGTH {
+            return Err(Box::new(ChunkError("IHDR must be 13 bytes")));
+        }
+
+        Ok(Ihdr {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, bit depth {}, color type {}, interlace {}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            self.interlace_method
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_ihdr() -> Ihdr {
+        Ihdr {
+            width: 50,
+            height: 40,
+            bit_depth: 8,
+            color_type: 6,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        }
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = testing_ihdr();
+        let chunk = ihdr.to_chunk();
+
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_length() {
+        let chunk = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 12]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_expected_interlaced_length() {
+        let mut ihdr = Ihdr {
+            width: 8,
+            height: 8,
+            bit_depth: 8,
+            color_type: 2,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+
+        assert_eq!(ihdr.image_data_length(), Some(200));
+        ihdr.interlace_method = 1;
+        assert_eq!(ihdr.image_data_length(), Some(207));
+    }
+
+    #[test]
+    fn test_ihdr_wrong_chunk_type() {
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![0; 13]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::error;
+use std::fmt;
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+/// Largest value of a png four byte integer: chunk lengths, widths
+/// and heights.
+pub const MAX_U31: u32 = (1 << 31) - 1;
+
+/// Resource limits enforced while parsing, so untrusted files can't
+/// make pngsneak allocate unbounded memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// largest data length of a single chunk, never above MAX_U31
+    pub max_chunk_length: u32,
+    /// largest file read, in bytes
+    pub max_file_size: u64,
+    /// most chunks in a single file
+    pub max_chunks: usize,
+    /// largest decompressed image data implied by IHDR, also caps
+    /// decompressed zTXt and iTXt text and iCCP profiles
+    pub max_image_data: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_chunk_length: 64 << 20,
+            max_file_size: 256 << 20,
+            max_chunks: 65_536,
+            max_image_data: 1 << 30,
+        }
+    }
+}
+
+impl Limits {
+    pub fn check_file_size(&self, size: u64) -> Result<(), LimitError> {
+        if size > self.max_file_size {
+            return Err(LimitError::FileSize(self.max_file_size));
+        }
+        Ok(())
+    }
+
+    pub fn check_chunk_length(&self, length: u32) -> Result<(), LimitError> {
+        if length > MAX_U31 {
+            return Err(LimitError::ChunkLength(length, MAX_U31));
+        }
+        if length > self.max_chunk_length {
+            return Err(LimitError::ChunkLength(length, self.max_chunk_length));
+        }
+        Ok(())
+    }
+}
+
+/// Reads everything from reader, failing once it passes limit bytes
+/// instead of buffering an unbounded input.
+pub fn read_limited<R: Read>(reader: R, limit: u64) -> crate::Result<Vec<u8>> {
+    let mut bytes = vec![];
+
+    // one byte past the limit is enough to reject the input
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > limit {
+        return Err(Box::new(LimitError::FileSize(limit)));
+    }
+
+    Ok(bytes)
+}
+
+/// Inflates a zlib stream (image data, iCCP profiles, compressed
+/// iTXt text), failing once it passes limit bytes.
+pub fn inflate(compressed: &[u8], limit: u64) -> crate::Result<Vec<u8>> {
+    let mut inflated = vec![];
+
+    ZlibDecoder::new(compressed)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut inflated)?;
+
+    if inflated.len() as u64 > limit {
+        return Err(Box::new(LimitError::ImageData(
+            inflated.len() as u64,
+            limit,
+        )));
+    }
+
+    Ok(inflated)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimitError {
+    /// limit
+    FileSize(u64),
+    /// length, limit
+    ChunkLength(u32, u32),
+    /// limit
+    ChunkCount(usize),
+    /// required, limit
+    ImageData(u64, u64),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::FileSize(limit) => {
+                write!(f, "File is larger than the {} byte limit", limit)
+            }
+            LimitError::ChunkLength(length, limit) => write!(
+                f,
+                "Chunk length {} exceeds the {} byte limit",
+                length, limit
+            ),
+            LimitError::ChunkCount(limit) => {
+                write!(f, "File has more than the {} chunk limit", limit)
+            }
+            LimitError::ImageData(required, limit) => write!(
+                f,
+                "Image data of {} bytes exceeds the {} byte limit",
+                required, limit
+            ),
+        }
+    }
+}
+
+impl error::Error for LimitError {}
@@ -0,0 +1,826 @@
+use std::fmt;
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::{
+    ChunkType, AFTER_PLTE, BEFORE_IDAT, BEFORE_PLTE, SINGLE_INSTANCE,
+};
+use crate::ihdr::Ihdr;
+use crate::limits::{Limits, MAX_U31};
+use crate::png::Png;
+use crate::report::SCHEMA_VERSION;
+
+/// Critical chunk types defined by the spec.
+const KNOWN_CRITICAL: [&str; 4] = ["IHDR", "PLTE", "IDAT", "IEND"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single spec violation. chunk_index is None for file level findings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub chunk_index: Option<usize>,
+    pub chunk_type: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.chunk_index, &self.chunk_type) {
+            (Some(index), Some(chunk_type)) => {
+                write!(f, "chunk {} ({}): {}", index, chunk_type, self.message)
+            }
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Findings of a file, errors first.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub findings: Vec<Finding>,
+}
+
+impl LintReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    }
+}
+
+/// Errors and warnings in separate groups followed by a summary.
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (severity, heading) in
+            [(Severity::Error, "Errors"), (Severity::Warning, "Warnings")]
+        {
+            if self.count(severity) == 0 {
+                continue;
+            }
+
+            writeln!(f, "{}:", heading)?;
+
+            for finding in self.findings.iter() {
+                if finding.severity == severity {
+                    writeln!(f, "  {}", finding)?;
+                }
+            }
+        }
+
+        write!(
+            f,
+            "{}: {} error(s), {} warning(s)",
+            self.path,
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+/// A chunk as stored in the file, before any validation of its type.
+struct RawChunk<'a> {
+    index: usize,
+    chunk_type: [u8; 4],
+    data: &'a [u8],
+    crc: u32,
+}
+
+impl RawChunk<'_> {
+    fn name(&self) -> String {
+        String::from_utf8_lossy(&self.chunk_type).to_string()
+    }
+
+    fn is(&self, chunk_type: &str) -> bool {
+        self.chunk_type == chunk_type.as_bytes()
+    }
+
+    fn is_critical(&self) -> bool {
+        self.chunk_type[0].is_ascii_uppercase()
+    }
+}
+
+struct Linter {
+    findings: Vec<Finding>,
+    max_image_data: u64,
+}
+
+impl Linter {
+    fn file(&mut self, severity: Severity, message: String) {
+        self.findings.push(Finding {
+            severity,
+            chunk_index: None,
+            chunk_type: None,
+            message,
+        });
+    }
+
+    fn chunk(&mut self, severity: Severity, chunk: &RawChunk, message: String) {
+        self.findings.push(Finding {
+            severity,
+            chunk_index: Some(chunk.index),
+            chunk_type: Some(chunk.name()),
+            message,
+        });
+    }
+}
+
+/// Splits the file into chunks without trusting any field. Stops with a
+/// finding at the first truncated chunk.
+fn read_chunks<'a>(bytes: &'a [u8], linter: &mut Linter) -> Vec<RawChunk<'a>> {
+    let mut chunks = vec![];
+    let mut offset = Png::STANDARD_HEADER.len();
+
+    while offset < bytes.len() {
+        let index = chunks.len();
+
+        if bytes.len() - offset < 12 {
+            linter.file(
+                Severity::Error,
+                format!("chunk {} at offset {} is truncated", index, offset),
+            );
+            break;
+        }
+
+        let length =
+            u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let data_start = offset + 8;
+
+        if length > MAX_U31 || bytes.len() - data_start < length as usize + 4 {
+            linter.file(
+                Severity::Error,
+                format!(
+                    "chunk {} at offset {} claims {} bytes past the end of the file",
+                    index, offset, length
+                ),
+            );
+            break;
+        }
+
+        let data_end = data_start + length as usize;
+
+        chunks.push(RawChunk {
+            index,
+            chunk_type: bytes[offset + 4..data_start].try_into().unwrap(),
+            data: &bytes[data_start..data_end],
+            crc: u32::from_be_bytes(
+                bytes[data_end..data_end + 4].try_into().unwrap(),
+            ),
+        });
+
+        offset = data_end + 4;
+    }
+
+    chunks
+}
+
+fn check_chunk_types(chunks: &[RawChunk], linter: &mut Linter) {
+    for chunk in chunks {
+        if !chunk.chunk_type.iter().all(ChunkType::is_valid_byte) {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "chunk type must be 4 ascii letters".to_string(),
+            );
+            continue;
+        }
+
+        if !ChunkType::is_reserved_bit_valid(chunk.chunk_type[2]) {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "reserved bit is set, third letter must be uppercase".to_string(),
+            );
+        }
+
+        if chunk.is_critical() && !KNOWN_CRITICAL.iter().any(|name| chunk.is(name)) {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "unknown critical chunk, decoders must reject this file".to_string(),
+            );
+        }
+
+        let crc = Chunk::compute_crc(&chunk.chunk_type, chunk.data);
+
+        if crc != chunk.crc {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                format!("crc is {:08x}, expected {:08x}", chunk.crc, crc),
+            );
+        }
+    }
+}
+
+fn position(chunks: &[RawChunk], chunk_type: &str) -> Option<usize> {
+    chunks.iter().position(|chunk| chunk.is(chunk_type))
+}
+
+fn check_layout(chunks: &[RawChunk], linter: &mut Linter) {
+    match chunks.first() {
+        Some(first) if first.is("IHDR") => {}
+        _ => linter.file(Severity::Error, "first chunk must be IHDR".to_string()),
+    }
+
+    match chunks.last() {
+        Some(last) if last.is("IEND") => {
+            if !last.data.is_empty() {
+                linter.chunk(
+                    Severity::Error,
+                    last,
+                    "IEND must be empty".to_string(),
+                );
+            }
+        }
+        _ => linter.file(Severity::Error, "last chunk must be IEND".to_string()),
+    }
+
+    let idat = chunks
+        .iter()
+        .filter(|chunk| chunk.is("IDAT"))
+        .map(|chunk| chunk.index)
+        .collect::<Vec<usize>>();
+
+    match (idat.first(), idat.last()) {
+        (Some(&first), Some(&last)) => {
+            if last - first + 1 != idat.len() {
+                linter.file(
+                    Severity::Error,
+                    "IDAT chunks must be consecutive".to_string(),
+                );
+            }
+        }
+        _ => linter.file(Severity::Error, "no IDAT chunk found".to_string()),
+    }
+
+    let first_idat = idat.first().copied().unwrap_or(chunks.len());
+    let plte = position(chunks, "PLTE");
+
+    for chunk in chunks {
+        let name = chunk.name();
+
+        let is_single = ["IHDR", "PLTE", "IEND"].contains(&name.as_str())
+            || SINGLE_INSTANCE.contains(&name.as_str());
+
+        if is_single && position(chunks, &name) != Some(chunk.index) {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "chunk may only appear once".to_string(),
+            );
+        }
+
+        let before_idat = chunk.index < first_idat;
+        let before_plte = plte.is_some_and(|plte| chunk.index < plte);
+
+        if name == "PLTE" && !before_idat {
+            linter.chunk(Severity::Error, chunk, "must precede IDAT".to_string());
+        } else if BEFORE_PLTE.contains(&name.as_str())
+            && !(before_idat && (plte.is_none() || before_plte))
+        {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "must precede PLTE and IDAT".to_string(),
+            );
+        } else if AFTER_PLTE.contains(&name.as_str())
+            && (before_plte || !before_idat)
+        {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "must follow PLTE and precede IDAT".to_string(),
+            );
+        } else if BEFORE_IDAT.contains(&name.as_str()) && !before_idat {
+            linter.chunk(Severity::Error, chunk, "must precede IDAT".to_string());
+        }
+    }
+
+    if position(chunks, "sRGB").is_some() && position(chunks, "iCCP").is_some() {
+        linter.file(
+            Severity::Warning,
+            "sRGB and iCCP should not both be present".to_string(),
+        );
+    }
+}
+
+fn check_ihdr(chunks: &[RawChunk], linter: &mut Linter) -> Option<Ihdr> {
+    let chunk = chunks.iter().find(|chunk| chunk.is("IHDR"))?;
+
+    if chunk.data.len() != Ihdr::LENGTH {
+        linter.chunk(Severity::Error, chunk, "IHDR must be 13 bytes".to_string());
+        return None;
+    }
+
+    let ihdr = Ihdr::try_from(&Chunk::new(
+        ChunkType::try_from(*b"IHDR").unwrap(),
+        chunk.data.to_vec(),
+    ))
+    .ok()?;
+
+    let mut valid = true;
+    let mut error = |message: String| {
+        linter.chunk(Severity::Error, chunk, message);
+        valid = false;
+    };
+
+    if ihdr.width == 0 || ihdr.width > MAX_U31 {
+        error(format!("width {} must be between 1 and 2^31-1", ihdr.width));
+    }
+
+    if ihdr.height == 0 || ihdr.height > MAX_U31 {
+        error(format!(
+            "height {} must be between 1 and 2^31-1",
+            ihdr.height
+        ));
+    }
+
+    let bit_depths: &[u8] = match ihdr.color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => &[],
+    };
+
+    if bit_depths.is_empty() {
+        error(format!("color type {} is invalid", ihdr.color_type));
+    } else if !bit_depths.contains(&ihdr.bit_depth) {
+        error(format!(
+            "bit depth {} is invalid for color type {}",
+            ihdr.bit_depth, ihdr.color_type
+        ));
+    }
+
+    if ihdr.compression_method != 0 {
+        error(format!(
+            "compression method {} is invalid",
+            ihdr.compression_method
+        ));
+    }
+
+    if ihdr.filter_method != 0 {
+        error(format!("filter method {} is invalid", ihdr.filter_method));
+    }
+
+    if ihdr.interlace_method > 1 {
+        error(format!(
+            "interlace method {} is invalid",
+            ihdr.interlace_method
+        ));
+    }
+
+    valid.then_some(ihdr)
+}
+
+fn check_palette(chunks: &[RawChunk], ihdr: &Ihdr, linter: &mut Linter) {
+    let plte = chunks.iter().find(|chunk| chunk.is("PLTE"));
+    let mut entries = None;
+
+    match (plte, ihdr.color_type) {
+        (None, 3) => linter.file(
+            Severity::Error,
+            "color type 3 requires a PLTE chunk".to_string(),
+        ),
+        (Some(plte), 0 | 4) => linter.chunk(
+            Severity::Error,
+            plte,
+            format!("PLTE is not allowed with color type {}", ihdr.color_type),
+        ),
+        (Some(plte), _) => {
+            let count = plte.data.len() / 3;
+
+            if !plte.data.len().is_multiple_of(3) {
+                linter.chunk(
+                    Severity::Error,
+                    plte,
+                    "PLTE length must be a multiple of 3".to_string(),
+                );
+            } else if count == 0 || count > 256 {
+                linter.chunk(
+                    Severity::Error,
+                    plte,
+                    format!("PLTE holds {} entries, must be 1 to 256", count),
+                );
+            } else if ihdr.color_type == 3 && count > 1 << ihdr.bit_depth {
+                linter.chunk(
+                    Severity::Error,
+                    plte,
+                    format!(
+                        "PLTE holds {} entries, bit depth {} allows {}",
+                        count,
+                        ihdr.bit_depth,
+                        1 << ihdr.bit_depth
+                    ),
+                );
+            }
+
+            entries = Some(count);
+        }
+        (None, _) => {}
+    }
+
+    let trns = match chunks.iter().find(|chunk| chunk.is("tRNS")) {
+        Some(trns) => trns,
+        None => return,
+    };
+
+    let length = trns.data.len();
+
+    let message = match ihdr.color_type {
+        0 if length != 2 => Some(format!("tRNS must be 2 bytes, found {}", length)),
+        2 if length != 6 => Some(format!("tRNS must be 6 bytes, found {}", length)),
+        3 if length > entries.unwrap_or(0) => Some(format!(
+            "tRNS holds {} entries, PLTE only {}",
+            length,
+            entries.unwrap_or(0)
+        )),
+        4 | 6 => Some(format!(
+            "tRNS is not allowed with color type {}",
+            ihdr.color_type
+        )),
+        _ => None,
+    };
+
+    if let Some(message) = message {
+        linter.chunk(Severity::Error, trns, message);
+    }
+}
+
+/// Inflates a zlib stream, reading at most limit bytes.
+fn inflate(data: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    let mut inflated = vec![];
+
+    ZlibDecoder::new(data)
+        .take(limit)
+        .read_to_end(&mut inflated)
+        .map_err(|error| error.to_string())?;
+
+    Ok(inflated)
+}
+
+/// Keyword rules shared by tEXt, zTXt and iTXt.
+fn check_keyword(keyword: &[u8], chunk: &RawChunk, linter: &mut Linter) {
+    if keyword.is_empty() || keyword.len() > 79 {
+        linter.chunk(
+            Severity::Error,
+            chunk,
+            format!("keyword is {} bytes, must be 1 to 79", keyword.len()),
+        );
+    }
+
+    if keyword.iter().any(|&b| !(32..=126).contains(&b) && b < 161) {
+        linter.chunk(
+            Severity::Error,
+            chunk,
+            "keyword may only hold printable Latin-1 characters".to_string(),
+        );
+    }
+
+    if keyword.first() == Some(&b' ') || keyword.last() == Some(&b' ') {
+        linter.chunk(
+            Severity::Error,
+            chunk,
+            "keyword must not start or end with a space".to_string(),
+        );
+    }
+
+    if keyword.windows(2).any(|pair| pair == b"  ") {
+        linter.chunk(
+            Severity::Warning,
+            chunk,
+            "keyword should not hold consecutive spaces".to_string(),
+        );
+    }
+}
+
+fn check_text(chunks: &[RawChunk], linter: &mut Linter) {
+    for chunk in chunks {
+        if !chunk.is("tEXt") && !chunk.is("zTXt") && !chunk.is("iTXt") {
+            continue;
+        }
+
+        let separator = match chunk.data.iter().position(|&b| b == 0) {
+            Some(separator) => separator,
+            None => {
+                linter.chunk(
+                    Severity::Error,
+                    chunk,
+                    "keyword is not null terminated".to_string(),
+                );
+                continue;
+            }
+        };
+
+        check_keyword(&chunk.data[..separator], chunk, linter);
+        let rest = &chunk.data[separator + 1..];
+
+        if chunk.is("zTXt") {
+            match rest.split_first() {
+                Some((0, compressed)) => {
+                    if let Err(error) = inflate(compressed, linter.max_image_data) {
+                        linter.chunk(
+                            Severity::Error,
+                            chunk,
+                            format!("compressed text is corrupt: {}", error),
+                        );
+                    }
+                }
+                _ => linter.chunk(
+                    Severity::Error,
+                    chunk,
+                    "compression method must be 0".to_string(),
+                ),
+            }
+        }
+
+        if chunk.is("iTXt") {
+            check_international_text(rest, chunk, linter);
+        }
+    }
+}
+
+/// iTXt after the keyword:
+/// [compression flag, compression method, language\0, translated keyword\0, text]
+fn check_international_text(data: &[u8], chunk: &RawChunk, linter: &mut Linter) {
+    let (flag, method) = match data {
+        [flag, method, ..] => (*flag, *method),
+        _ => {
+            linter.chunk(Severity::Error, chunk, "iTXt is truncated".to_string());
+            return;
+        }
+    };
+
+    let mut fields = data[2..].splitn(3, |&b| b == 0);
+    let (_, translated, text) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(language), Some(translated), Some(text)) => {
+            (language, translated, text)
+        }
+        _ => {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "language tag and translated keyword must be null terminated"
+                    .to_string(),
+            );
+            return;
+        }
+    };
+
+    let text = match (flag, method) {
+        (0, _) => text.to_vec(),
+        (1, 0) => match inflate(text, linter.max_image_data) {
+            Ok(text) => text,
+            Err(error) => {
+                linter.chunk(
+                    Severity::Error,
+                    chunk,
+                    format!("compressed text is corrupt: {}", error),
+                );
+                return;
+            }
+        },
+        _ => {
+            linter.chunk(
+                Severity::Error,
+                chunk,
+                "compression flag must be 0 or 1 and method 0".to_string(),
+            );
+            return;
+        }
+    };
+
+    if std::str::from_utf8(translated).is_err()
+        || std::str::from_utf8(&text).is_err()
+    {
+        linter.chunk(
+            Severity::Error,
+            chunk,
+            "translated keyword and text must be UTF-8".to_string(),
+        );
+    }
+}
+
+fn check_image_data(chunks: &[RawChunk], ihdr: Option<&Ihdr>, linter: &mut Linter) {
+    let data = chunks
+        .iter()
+        .filter(|chunk| chunk.is("IDAT"))
+        .flat_map(|chunk| chunk.data.iter().copied())
+        .collect::<Vec<u8>>();
+
+    if data.is_empty() {
+        return;
+    }
+
+    let expected = ihdr.and_then(Ihdr::image_data_length);
+    let max_image_data = linter.max_image_data;
+
+    if let Some(expected) = expected.filter(|&expected| expected > max_image_data) {
+        linter.file(
+            Severity::Error,
+            format!(
+                "dimensions require {} bytes of image data, the limit is {}",
+                expected, max_image_data
+            ),
+        );
+        return;
+    }
+
+    // one byte past the expected length is enough to report extra data
+    let limit = expected.unwrap_or(max_image_data).saturating_add(1);
+
+    let inflated = match inflate(&data, limit) {
+        Ok(inflated) => inflated,
+        Err(error) => {
+            linter.file(
+                Severity::Error,
+                format!("IDAT zlib stream is corrupt: {}", error),
+            );
+            return;
+        }
+    };
+
+    match expected {
+        Some(expected) if (inflated.len() as u64) < expected => linter.file(
+            Severity::Error,
+            format!(
+                "IDAT decompresses to {} bytes, dimensions require {}",
+                inflated.len(),
+                expected
+            ),
+        ),
+        Some(expected) if inflated.len() as u64 > expected => linter.file(
+            Severity::Error,
+            format!(
+                "IDAT decompresses to more than the {} bytes the dimensions require",
+                expected
+            ),
+        ),
+        None if inflated.len() as u64 > max_image_data => linter.file(
+            Severity::Error,
+            format!(
+                "IDAT decompresses to more than the {} byte limit",
+                max_image_data
+            ),
+        ),
+        _ => {}
+    }
+}
+
+/// Checks png bytes against the PNG specification.
+///
+/// Works on the raw bytes so files the parser rejects (bad crc, invalid
+/// chunk types) can still be linted.
+pub fn lint(path: &str, bytes: &[u8], limits: &Limits) -> LintReport {
+    let mut linter = Linter {
+        findings: vec![],
+        max_image_data: limits.max_image_data,
+    };
+
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        linter.file(Severity::Error, "png signature is invalid".to_string());
+    } else {
+        let chunks = read_chunks(bytes, &mut linter);
+
+        check_chunk_types(&chunks, &mut linter);
+        check_layout(&chunks, &mut linter);
+
+        let ihdr = check_ihdr(&chunks, &mut linter);
+
+        if let Some(ihdr) = &ihdr {
+            check_palette(&chunks, ihdr, &mut linter);
+        }
+
+        check_text(&chunks, &mut linter);
+        check_image_data(&chunks, ihdr.as_ref(), &mut linter);
+    }
+
+    linter.findings.sort_by_key(|finding| finding.severity);
+
+    LintReport {
+        schema_version: SCHEMA_VERSION,
+        path: path.to_string(),
+        findings: linter.findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// 2x2 8 bit rgb image with the extra chunks inserted before IDAT.
+    fn testing_png(extra: Vec<Chunk>, image_data: &[u8]) -> Vec<u8> {
+        let ihdr = chunk_of("IHDR", &[0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+        let chunks = [ihdr]
+            .into_iter()
+            .chain(extra)
+            .chain([chunk_of("IDAT", &zlib(image_data)), chunk_of("IEND", &[])])
+            .collect();
+
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    fn messages(report: &LintReport) -> Vec<String> {
+        report
+            .findings
+            .iter()
+            .map(|finding| finding.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_png_has_no_findings() {
+        let report =
+            lint("a.png", &testing_png(vec![], &[0; 14]), &Limits::default());
+        assert!(report.findings.is_empty(), "{}", report);
+    }
+
+    #[test]
+    fn test_chunk_findings() {
+        let mut bad_crc = chunk_of("tIME", &[7, 230, 1, 1, 0, 0, 0]);
+        bad_crc.crc ^= 1;
+
+        let report = lint(
+            "a.png",
+            &testing_png(
+                vec![
+                    chunk_of("tEXt", b" Title  \0text"),
+                    chunk_of("tRNS", &[0, 0]),
+                    chunk_of("ABCD", &[]),
+                    bad_crc,
+                ],
+                &[0; 14],
+            ),
+            &Limits::default(),
+        );
+
+        let messages = messages(&report);
+        assert!(
+            messages.contains(&"keyword must not start or end with a space".into())
+        );
+        assert!(messages.contains(&"tRNS must be 6 bytes, found 2".into()));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("unknown critical chunk")));
+        assert!(messages.iter().any(|m| m.starts_with("crc is")));
+        assert_eq!(report.count(Severity::Warning), 1);
+        assert_eq!(report.findings.last().unwrap().severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_image_data_length() {
+        let report =
+            lint("a.png", &testing_png(vec![], &[0; 10]), &Limits::default());
+        assert_eq!(
+            messages(&report),
+            vec!["IDAT decompresses to 10 bytes, dimensions require 14"]
+        );
+
+        let mut bytes = testing_png(vec![], &[0; 14]);
+        let idat = bytes.windows(4).position(|w| w == b"IDAT").unwrap();
+        bytes[idat + 6] ^= 0xff;
+        let report = lint("a.png", &bytes, &Limits::default());
+        assert!(messages(&report)
+            .iter()
+            .any(|m| m.starts_with("IDAT zlib stream is corrupt")));
+    }
+
+    #[test]
+    fn test_truncated_file_does_not_panic() {
+        let bytes = testing_png(vec![], &[0; 14]);
+
+        for length in 0..bytes.len() {
+            lint("a.png", &bytes[..length], &Limits::default());
+        }
+    }
+}
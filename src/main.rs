@@ -1,15 +1,45 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use clap::Parser;
 
+mod ancillary;
+mod apng;
+mod batch;
+mod c2pa;
+mod cbor;
 mod chunk;
 mod chunk_error;
 mod chunk_type;
 mod cli;
 mod commands;
+mod diff;
+mod exif;
+mod fec;
 mod file_type;
+mod hexdump;
+mod http;
+mod icc;
+mod ihdr;
+mod limits;
+mod lint;
+mod pixels;
 mod png;
+mod report;
+mod shamir;
+mod signature;
+mod strip;
+mod transplant;
+mod verify;
+mod write;
+mod xmp;
 
+use crate::ancillary::{Ancillary, Chromaticities, Gamma, PhysicalDimensions, Srgb};
 use crate::cli::{Cli, Command};
-use crate::commands::Commands;
+use crate::commands::{Commands, EncodeMode, EncodeOptions, SetOptions, XmpOptions};
+use crate::http::HttpOptions;
+use crate::limits::Limits;
+use crate::write::WriteOptions;
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
@@ -17,22 +47,459 @@ pub type Result<T> = std::result::Result<T, Error>;
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    let defaults = Limits::default();
+    let limits = Limits {
+        max_chunk_length: cli.max_chunk_length.unwrap_or(defaults.max_chunk_length),
+        max_file_size: cli.max_file_size.unwrap_or(defaults.max_file_size),
+        max_chunks: cli.max_chunks.unwrap_or(defaults.max_chunks),
+        max_image_data: cli.max_image_data.unwrap_or(defaults.max_image_data),
+    };
+
+    let defaults = HttpOptions::default();
+    let http_options = HttpOptions {
+        timeout: cli.timeout.map_or(defaults.timeout, Duration::from_secs),
+        connect_timeout: cli
+            .connect_timeout
+            .map_or(defaults.connect_timeout, Duration::from_secs),
+        max_download_size: cli.max_download_size,
+        max_redirects: cli.max_redirects.unwrap_or(defaults.max_redirects),
+        allow_hosts: cli.allow_host.clone(),
+        deny_hosts: cli.deny_host.clone(),
+        headers: cli.header.clone(),
+        proxy: cli.proxy.clone(),
+        cache_dir: cli.cache_dir.as_ref().map(PathBuf::from),
+        cache_max_age: cli
+            .cache_max_age
+            .map_or(defaults.cache_max_age, Duration::from_secs),
+    };
+
+    let write_options = WriteOptions {
+        backup_suffix: cli.backup.then(|| cli.backup_suffix.clone()),
+        dry_run: cli.dry_run,
+        verify: cli.verify,
+        force: cli.force,
+    };
+
+    let output_dir = &cli.output_dir;
+    let jobs = &cli.jobs;
+
     match &cli.command {
+        Command::Encode {
+            file_path,
+            chunk_type,
+            message,
+            sign_key,
+            fec,
+            replace,
+            if_absent,
+            pixel_key,
+            matrix,
+            shares: Some(share_count),
+            threshold: Some(threshold),
+            ..
+        } => {
+            let options = EncodeOptions {
+                sign_key: sign_key.clone(),
+                fec_parity: *fec,
+                mode: EncodeMode::from_flags(*replace, *if_absent),
+                pixel_key: pixel_key.clone(),
+                matrix: *matrix,
+            };
+            Commands::encode_shares(
+                file_path,
+                chunk_type,
+                message,
+                *share_count,
+                *threshold,
+                output_dir,
+                &options,
+                &http_options,
+                &limits,
+                &write_options,
+            )?
+        }
         Command::Encode {
             file_path,
             chunk_type,
             message,
             output_path,
-        } => Commands::encode(file_path, chunk_type, message, output_path)?,
+            sign_key,
+            fec,
+            replace,
+            if_absent,
+            pixel_key,
+            matrix,
+            ..
+        } => {
+            let options = EncodeOptions {
+                sign_key: sign_key.clone(),
+                fec_parity: *fec,
+                mode: EncodeMode::from_flags(*replace, *if_absent),
+                pixel_key: pixel_key.clone(),
+                matrix: *matrix,
+            };
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::encode(
+                    file,
+                    chunk_type,
+                    message,
+                    &output_path,
+                    &options,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
         Command::Decode {
-            file_path,
+            file_paths,
+            chunk_type,
+            shares: true,
+            ..
+        } => {
+            Commands::decode_shares(file_paths, chunk_type, &http_options, &limits)?
+        }
+        Command::Decode {
+            file_paths,
             chunk_type,
-        } => Commands::decode(file_path, chunk_type)?,
+            verify_key,
+            pixel_key,
+            format,
+            ..
+        } => {
+            for file_path in file_paths {
+                batch::run(file_path, &None, jobs, |file, _| {
+                    Commands::decode(
+                        file,
+                        chunk_type,
+                        verify_key,
+                        pixel_key,
+                        *format,
+                        &http_options,
+                        &limits,
+                    )
+                })?;
+            }
+        }
         Command::Remove {
             file_path,
             chunk_type,
-        } => Commands::remove(file_path, chunk_type)?,
-        Command::Print { file_path } => Commands::print(file_path)?,
+            output_path,
+            output,
+            format,
+        } => {
+            let output_path = &output_path.clone().or_else(|| output.clone());
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::remove(
+                    file,
+                    chunk_type,
+                    &output_path,
+                    *format,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Strip {
+            file_path,
+            output_path,
+            keep,
+            preset,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::strip(
+                    file,
+                    keep,
+                    preset,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Transplant {
+            source_path,
+            file_path,
+            output_path,
+            include_unsafe,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::transplant(
+                    source_path,
+                    file,
+                    *include_unsafe,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Set {
+            file_path,
+            output_path,
+            dpi,
+            time,
+            srgb,
+            gamma,
+            chrm,
+            background,
+            sbit,
+            transparent,
+            remove_iccp,
+        } => {
+            let mut options = SetOptions {
+                remove_iccp: *remove_iccp,
+                ..SetOptions::default()
+            };
+            if let Some(dpi) = dpi {
+                options.values.push(Ancillary::PhysicalDimensions(
+                    PhysicalDimensions::from_dpi(*dpi)?,
+                ));
+            }
+            if let Some(time) = time {
+                options.values.push(Ancillary::Time(time.clone()));
+            }
+            if let Some(rendering_intent) = srgb {
+                options.values.push(Ancillary::Srgb(Srgb {
+                    rendering_intent: *rendering_intent,
+                }));
+            }
+            if let Some(gamma) = gamma {
+                options
+                    .values
+                    .push(Ancillary::Gamma(Gamma::from_value(*gamma)?));
+            }
+            if !chrm.is_empty() {
+                options.values.push(Ancillary::Chromaticities(
+                    Chromaticities::from_values(chrm)?,
+                ));
+            }
+            for (chunk_type, samples) in
+                [("bKGD", background), ("sBIT", sbit), ("tRNS", transparent)]
+            {
+                if !samples.is_empty() {
+                    options.samples.push((chunk_type, samples.clone()));
+                }
+            }
+
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::set(
+                    file,
+                    &options,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Exif {
+            file_path,
+            output_path,
+            remove_tag,
+            remove_ifd,
+            format,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::exif(
+                    file,
+                    remove_tag,
+                    remove_ifd,
+                    &output_path,
+                    *format,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Xmp {
+            file_path,
+            output_path,
+            get,
+            set,
+            replace,
+            delete,
+            raw,
+        } => {
+            let options = XmpOptions {
+                get: get.clone(),
+                set: set.clone(),
+                replace: replace.clone(),
+                delete: *delete,
+                raw: *raw,
+            };
+
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::xmp(
+                    file,
+                    &options,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Capacity {
+            file_path,
+            length,
+            format,
+        } => batch::run(file_path, &None, jobs, |file, _| {
+            Commands::capacity(file, *length, *format, &http_options, &limits)
+        })?,
+        Command::C2pa { file_path, format } => {
+            batch::run(file_path, &None, jobs, |file, _| {
+                Commands::c2pa(file, *format, &http_options, &limits)
+            })?
+        }
+        Command::ExtractIcc {
+            file_path,
+            output_path,
+        } => Commands::extract_icc(
+            file_path,
+            output_path,
+            &http_options,
+            &limits,
+            &write_options,
+        )?,
+        Command::EmbedIcc {
+            file_path,
+            profile_path,
+            output_path,
+            name,
+            remove_srgb,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::embed_icc(
+                    file,
+                    profile_path,
+                    name,
+                    *remove_srgb,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Cat {
+            file_path,
+            chunk_type,
+            index,
+            raw,
+            output,
+        } => Commands::cat(
+            file_path,
+            chunk_type,
+            *index,
+            *raw,
+            output,
+            &http_options,
+            &limits,
+            &write_options,
+        )?,
+        Command::Inject {
+            file_path,
+            chunk_type,
+            data_path,
+            output_path,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::inject(
+                    file,
+                    chunk_type,
+                    data_path,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Lint {
+            file_path,
+            strict,
+            format,
+        } => batch::run(file_path, &None, jobs, |file, _| {
+            Commands::lint(file, *strict, *format, &http_options, &limits)
+        })?,
+        Command::Diff {
+            left_path,
+            right_path,
+            format,
+        } => Commands::diff(left_path, right_path, *format, &http_options, &limits)?,
+        Command::Print { file_path, format } => {
+            batch::run(file_path, &None, jobs, |file, _| {
+                Commands::print(file, *format, &http_options, &limits)
+            })?
+        }
+        Command::Keygen { key_path } => Commands::keygen(key_path)?,
+        Command::Fingerprint { key_path } => Commands::fingerprint(key_path)?,
+        Command::Sign {
+            file_path,
+            key_path,
+            output_path,
+            include,
+        } => {
+            batch::check_output_path(file_path, output_path)?;
+            batch::run(file_path, output_dir, jobs, |file, output| {
+                let output_path = output.or_else(|| output_path.clone());
+                Commands::sign(
+                    file,
+                    key_path,
+                    include,
+                    &output_path,
+                    &http_options,
+                    &limits,
+                    &write_options,
+                )
+            })?
+        }
+        Command::Verify {
+            file_path,
+            key_path,
+        } => batch::run(file_path, &None, jobs, |file, _| {
+            Commands::verify(file, key_path, &http_options, &limits)
+        })?,
+        Command::Frames { file_path } => {
+            batch::run(file_path, &None, jobs, |file, _| {
+                Commands::frames(file, &http_options, &limits)
+            })?
+        }
+        Command::ExtractFrame {
+            file_path,
+            frame_index,
+            output_path,
+        } => Commands::extract_frame(
+            file_path,
+            *frame_index,
+            output_path,
+            &http_options,
+            &limits,
+            &write_options,
+        )?,
     }
 
     Ok(())
@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::limits::{self, Limits};
+use crate::png::Png;
+use crate::report::SCHEMA_VERSION;
+use crate::Result;
+
+/// Chunk type that encode and decode take to mean "in the pixels".
+pub const PIXEL_CHUNK_TYPE: &str = "IDAT";
+
+/// Big endian header in front of every pixel payload: the matrix
+/// code parameter minus one in the top 4 bits, the payload length
+/// below. Plain LSB payloads (parameter 1) start with the bare length.
+const HEADER_BITS: usize = 32;
+const LENGTH_MASK: u32 = (1 << 28) - 1;
+
+/// Largest matrix code parameter, 8 bits in blocks of 255 samples.
+pub const MAX_MATRIX: u8 = 8;
+
+/// Size of the IDAT chunks image data is split into when written.
+const IDAT_LENGTH: usize = 1 << 16;
+
+/// Separates the keystream from other uses of the same key.
+const KEY_CONTEXT: &[u8] = b"pngsneak pixel selection v1";
+
+/// A scanline of the unfiltered image data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    /// offset of the row in Pixels::data
+    start: usize,
+    length: usize,
+    pixels: usize,
+    /// filter type of the row, reused when writing
+    filter: u8,
+    /// the first row of an interlace pass is filtered against zeros
+    first_in_pass: bool,
+}
+
+/// Unfiltered image data of a png, decoded from its IDAT chunks.
+///
+/// Only the least significant bit of color samples carries payload:
+/// 8 and 16 bit grayscale and truecolor images, alpha left alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pixels {
+    ihdr: Ihdr,
+    rows: Vec<Row>,
+    data: Vec<u8>,
+    /// carriers before every row, to find a carrier's row quickly
+    carriers_before: Vec<usize>,
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let (pa, pb, pc) = (
+        (p - left as i16).abs(),
+        (p - up as i16).abs(),
+        (p - up_left as i16).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Predictor of filter for the byte at idx of a row.
+fn predict(filter: u8, row: &[u8], previous: &[u8], idx: usize, bpp: usize) -> u8 {
+    let left = if idx >= bpp { row[idx - bpp] } else { 0 };
+    let up = previous.get(idx).copied().unwrap_or(0);
+    let up_left = if idx >= bpp {
+        previous.get(idx - bpp).copied().unwrap_or(0)
+    } else {
+        0
+    };
+
+    match filter {
+        1 => left,
+        2 => up,
+        3 => ((left as u16 + up as u16) / 2) as u8,
+        4 => paeth(left, up, up_left),
+        _ => 0,
+    }
+}
+
+impl Pixels {
+    pub fn from_png(png: &Png, limits: &Limits) -> Result<Pixels> {
+        let ihdr = png.ihdr()?;
+
+        if !matches!(ihdr.color_type, 0 | 2 | 4 | 6) || ihdr.bit_depth < 8 {
+            return Err(Box::new(ChunkError(
+                "Pixel payloads need 8 or 16 bit grayscale or truecolor images",
+            )));
+        }
+
+        let compressed = png
+            .chunk_by_type("IDAT")
+            .iter()
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect::<Vec<u8>>();
+        let filtered = limits::inflate(&compressed, limits.max_image_data)?;
+
+        if Some(filtered.len() as u64) != ihdr.image_data_length() {
+            return Err(Box::new(ChunkError(
+                "IDAT does not hold the image data IHDR describes",
+            )));
+        }
+
+        let bpp = Self::bytes_per_pixel(&ihdr);
+        let mut rows = vec![];
+        let mut data = Vec::with_capacity(filtered.len());
+        let mut position = 0;
+
+        for (width, height) in ihdr.passes() {
+            let length = ihdr.row_length(width).unwrap_or(0) as usize;
+
+            for line in 0..height {
+                let filter = filtered[position];
+                if filter > 4 {
+                    return Err(Box::new(ChunkError(
+                        "IDAT has an unknown filter type",
+                    )));
+                }
+
+                let start = data.len();
+                let previous = match line {
+                    0 => vec![],
+                    _ => data[start - length..start].to_vec(),
+                };
+                data.extend(&filtered[position + 1..position + 1 + length]);
+
+                for idx in 0..length {
+                    let predicted =
+                        predict(filter, &data[start..], &previous, idx, bpp);
+                    data[start + idx] = data[start + idx].wrapping_add(predicted);
+                }
+
+                rows.push(Row {
+                    start,
+                    length,
+                    pixels: width as usize,
+                    filter,
+                    first_in_pass: line == 0,
+                });
+                position += 1 + length;
+            }
+        }
+
+        let mut pixels = Pixels {
+            ihdr,
+            rows,
+            data,
+            carriers_before: vec![],
+        };
+        pixels.carriers_before = pixels
+            .rows
+            .iter()
+            .scan(0, |count, row| {
+                let before = *count;
+                *count += row.pixels * pixels.color_channels();
+                Some(before)
+            })
+            .collect();
+
+        Ok(pixels)
+    }
+
+    fn bytes_per_pixel(ihdr: &Ihdr) -> usize {
+        let channels = Ihdr::channels(ihdr.color_type).unwrap_or(1) as usize;
+        channels * ihdr.bit_depth as usize / 8
+    }
+
+    /// Channels whose samples carry payload, every one but alpha.
+    fn color_channels(&self) -> usize {
+        match self.ihdr.color_type {
+            0 | 4 => 1,
+            _ => 3,
+        }
+    }
+
+    /// Color samples, each carries one bit.
+    pub fn samples(&self) -> usize {
+        self.carriers_before.last().copied().unwrap_or(0)
+            + self
+                .rows
+                .last()
+                .map(|row| row.pixels * self.color_channels())
+                .unwrap_or(0)
+    }
+
+    /// Index in data of the byte holding the least significant bit
+    /// of the carrier-th color sample.
+    fn carrier_byte(&self, carrier: usize) -> usize {
+        let row = self
+            .carriers_before
+            .partition_point(|&before| before <= carrier)
+            - 1;
+        let in_row = carrier - self.carriers_before[row];
+
+        let channels = Ihdr::channels(self.ihdr.color_type).unwrap_or(1) as usize;
+        let sample_bytes = self.ihdr.bit_depth as usize / 8;
+        let (pixel, channel) = (
+            in_row / self.color_channels(),
+            in_row % self.color_channels(),
+        );
+
+        // 16 bit samples are big endian, the low byte comes second
+        self.rows[row].start + (pixel * channels + channel + 1) * sample_bytes - 1
+    }
+
+    fn lsb(&self, carrier: usize) -> u8 {
+        self.data[self.carrier_byte(carrier)] & 1
+    }
+
+    /// Hides payload in the least significant bits of the color
+    /// samples, behind a 32 bit header. Returns how many samples
+    /// changed.
+    ///
+    /// Payload bits are matrix embedded: a Hamming code stores matrix
+    /// bits in the parity of a block of 2^matrix - 1 samples by
+    /// flipping at most one of them. matrix 1 is plain LSB replacement.
+    /// Samples are used in image order, or in an order only key
+    /// reproduces.
+    pub fn embed(
+        &mut self,
+        payload: &[u8],
+        key: Option<&str>,
+        matrix: u8,
+    ) -> Result<usize> {
+        if !(1..=MAX_MATRIX).contains(&matrix) {
+            return Err(Box::new(ChunkError(
+                "Matrix code parameter must be 1 to 8",
+            )));
+        }
+
+        let block = block_size(matrix);
+        let blocks = (payload.len() * 8).div_ceil(matrix as usize);
+        let needed = HEADER_BITS + blocks * block;
+
+        if needed > self.samples() || payload.len() > LENGTH_MASK as usize {
+            return Err(Box::new(ChunkError(
+                "Message does not fit in the pixels of the image",
+            )));
+        }
+
+        let header = (matrix as u32 - 1) << 28 | payload.len() as u32;
+        let carriers = selection(needed, self.samples(), key);
+        let mut changed = 0;
+        let mut flip = |pixels: &mut Pixels, carrier: usize| {
+            let byte = pixels.carrier_byte(carrier);
+            pixels.data[byte] ^= 1;
+            changed += 1;
+        };
+
+        for (bit, &carrier) in bit_stream(&header.to_be_bytes()).zip(carriers.iter())
+        {
+            if self.lsb(carrier) != bit {
+                flip(self, carrier);
+            }
+        }
+
+        // the last block is padded with zero bits
+        let mut bits = bit_stream(payload);
+        for group in carriers[HEADER_BITS..].chunks(block) {
+            let message = (0..matrix)
+                .fold(0, |value, _| value << 1 | bits.next().unwrap_or(0) as usize);
+
+            let position = self.syndrome(group) ^ message;
+            if position != 0 {
+                flip(self, group[position - 1]);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// XOR of the 1-based positions of the samples in group whose
+    /// least significant bit is set.
+    fn syndrome(&self, group: &[usize]) -> usize {
+        group
+            .iter()
+            .enumerate()
+            .filter(|&(_, &carrier)| self.lsb(carrier) == 1)
+            .fold(0, |syndrome, (position, _)| syndrome ^ (position + 1))
+    }
+
+    /// Reads back a payload hidden by embed with the same key.
+    pub fn extract(&self, key: Option<&str>) -> Result<Vec<u8>> {
+        let not_found =
+            || ChunkError("No message found in the pixels, is the key right?");
+
+        if self.samples() < HEADER_BITS {
+            return Err(Box::new(not_found()));
+        }
+
+        let header = selection(HEADER_BITS, self.samples(), key)
+            .into_iter()
+            .fold(0, |value, carrier| value << 1 | self.lsb(carrier) as u32);
+        let matrix = (header >> 28) as u8 + 1;
+        let length = (header & LENGTH_MASK) as usize;
+
+        if matrix > MAX_MATRIX {
+            return Err(Box::new(not_found()));
+        }
+
+        let block = block_size(matrix);
+        let blocks = (length * 8).div_ceil(matrix as usize);
+        let needed = HEADER_BITS + blocks * block;
+
+        if needed > self.samples() {
+            return Err(Box::new(not_found()));
+        }
+
+        // the selection of a longer payload starts with the same carriers
+        let carriers = selection(needed, self.samples(), key);
+        let bits = carriers[HEADER_BITS..]
+            .chunks(block)
+            .flat_map(|group| {
+                let syndrome = self.syndrome(group);
+                (0..matrix)
+                    .rev()
+                    .map(move |shift| (syndrome >> shift & 1) as u8)
+            })
+            .collect::<Vec<u8>>();
+
+        Ok(bits
+            .chunks(8)
+            .take(length)
+            .map(|byte| byte.iter().fold(0, |value, &bit| value << 1 | bit))
+            .collect())
+    }
+
+    /// True if other holds the same image, apart from the least
+    /// significant bits of its color samples. Alpha must be identical.
+    pub fn differs_only_in_lsb(&self, other: &Pixels) -> bool {
+        if self.ihdr != other.ihdr || self.data.len() != other.data.len() {
+            return false;
+        }
+
+        let sample_bytes = self.ihdr.bit_depth as usize / 8;
+        let pixel_bytes = Self::bytes_per_pixel(&self.ihdr);
+
+        self.rows.iter().all(|row| {
+            let a = &self.data[row.start..row.start + row.length];
+            let b = &other.data[row.start..row.start + row.length];
+
+            a.iter().zip(b.iter()).enumerate().all(|(idx, (a, b))| {
+                let in_pixel = idx % pixel_bytes;
+                let color = in_pixel / sample_bytes < self.color_channels();
+                let low_byte = in_pixel % sample_bytes == sample_bytes - 1;
+
+                match color && low_byte {
+                    true => a ^ b <= 1,
+                    false => a == b,
+                }
+            })
+        })
+    }
+
+    /// Filters and compresses the pixels into IDAT chunks that replace
+    /// the ones of png. Rows keep their filter type.
+    pub fn write_to(&self, png: &mut Png) -> Result<()> {
+        let bpp = Self::bytes_per_pixel(&self.ihdr);
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+
+        for (idx, row) in self.rows.iter().enumerate() {
+            let line = &self.data[row.start..row.start + row.length];
+            let previous = match row.first_in_pass {
+                true => &[][..],
+                false => {
+                    let previous = &self.rows[idx - 1];
+                    &self.data[previous.start..previous.start + previous.length]
+                }
+            };
+
+            let mut filtered = vec![row.filter];
+            filtered.extend((0..row.length).map(|idx| {
+                line[idx].wrapping_sub(predict(row.filter, line, previous, idx, bpp))
+            }));
+            encoder.write_all(&filtered)?;
+        }
+
+        let compressed = encoder.finish()?;
+        let first = png
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+            .ok_or(ChunkError("png has no IDAT chunk"))?;
+
+        png.chunks
+            .retain(|chunk| chunk.chunk_type().to_string() != "IDAT");
+
+        for (offset, data) in compressed.chunks(IDAT_LENGTH).enumerate() {
+            png.chunks.insert(
+                first + offset,
+                Chunk::new(ChunkType::from_str("IDAT")?, data.to_vec()),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Samples per block of the matrix code with parameter matrix.
+pub fn block_size(matrix: u8) -> usize {
+    (1 << matrix) - 1
+}
+
+/// Largest payload in bytes that samples carry with parameter matrix.
+pub fn capacity(samples: usize, matrix: u8) -> usize {
+    let blocks = samples.saturating_sub(HEADER_BITS) / block_size(matrix);
+    (blocks * matrix as usize / 8).min(LENGTH_MASK as usize)
+}
+
+/// Samples that change on average when embedding length bytes with
+/// parameter matrix. Every header bit and every block of the code
+/// needs no change with a chance of one in 2^matrix.
+pub fn expected_changes(length: usize, matrix: u8) -> f64 {
+    let blocks = (length * 8).div_ceil(matrix as usize);
+    HEADER_BITS as f64 / 2.0 + blocks as f64 * (1.0 - 0.5f64.powi(matrix as i32))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatrixReport {
+    pub matrix: u8,
+    pub block_size: usize,
+    /// largest payload in bytes
+    pub capacity: usize,
+    /// samples changed per payload bit on average, 0.5 for plain LSB
+    pub changes_per_bit: f64,
+    /// samples changed on average by a payload of the requested length,
+    /// null when it doesn't fit or no length was requested
+    pub expected_changes: Option<f64>,
+    /// expected_changes as a share of all samples
+    pub change_rate: Option<f64>,
+}
+
+/// Output of capacity.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapacityReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub samples: usize,
+    /// payload length the expected changes are for
+    pub length: Option<usize>,
+    pub codes: Vec<MatrixReport>,
+}
+
+impl CapacityReport {
+    pub fn new(path: &str, samples: usize, length: Option<usize>) -> CapacityReport {
+        let codes = (1..=MAX_MATRIX)
+            .map(|matrix| {
+                let capacity = capacity(samples, matrix);
+                let expected_changes = length
+                    .filter(|&length| length <= capacity)
+                    .map(|length| expected_changes(length, matrix));
+
+                MatrixReport {
+                    matrix,
+                    block_size: block_size(matrix),
+                    capacity,
+                    changes_per_bit: (1.0 - 0.5f64.powi(matrix as i32))
+                        / matrix as f64,
+                    expected_changes,
+                    change_rate: expected_changes
+                        .map(|changes| changes / samples as f64),
+                }
+            })
+            .filter(|code| code.capacity > 0)
+            .collect();
+
+        CapacityReport {
+            schema_version: SCHEMA_VERSION,
+            path: path.to_string(),
+            samples,
+            length,
+            codes,
+        }
+    }
+}
+
+/// One line per matrix code parameter.
+impl fmt::Display for CapacityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} color samples\nmatrix  block  capacity  changes/bit",
+            self.path, self.samples
+        )?;
+        if let Some(length) = self.length {
+            write!(f, "  changes for {} bytes", length)?;
+        }
+
+        for code in self.codes.iter() {
+            write!(
+                f,
+                "\n{:>6}  {:>5}  {:>8}  {:>11.3}",
+                code.matrix,
+                code.block_size,
+                format!("{} B", code.capacity),
+                code.changes_per_bit
+            )?;
+
+            match (self.length, code.expected_changes, code.change_rate) {
+                (None, _, _) => {}
+                (Some(_), Some(changes), Some(rate)) => {
+                    write!(f, "  {:.0} ({:.2}%)", changes, rate * 100.0)?
+                }
+                (Some(_), _, _) => write!(f, "  does not fit")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Bits of bytes, most significant first.
+fn bit_stream(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| byte >> shift & 1))
+}
+
+/// SHA-256 in counter mode, seeded from a key.
+struct KeyStream {
+    seed: [u8; 32],
+    counter: u64,
+    block: Vec<u8>,
+}
+
+impl KeyStream {
+    fn new(key: &str) -> KeyStream {
+        KeyStream {
+            seed: Sha256::new()
+                .chain_update(KEY_CONTEXT)
+                .chain_update(key.as_bytes())
+                .finalize()
+                .into(),
+            counter: 0,
+            block: vec![],
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.block.len() < 8 {
+            self.block = Sha256::new()
+                .chain_update(self.seed)
+                .chain_update(self.counter.to_be_bytes())
+                .finalize()
+                .to_vec();
+            self.counter += 1;
+        }
+
+        self.block
+            .drain(..8)
+            .fold(0, |value, b| value << 8 | b as u64)
+    }
+
+    /// Uniform in 0..bound, without modulo bias.
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// The first count of total carriers in embedding order: image order
+/// without a key, otherwise a keyed random permutation.
+///
+/// A Fisher-Yates shuffle that only tracks swapped positions, so
+/// memory grows with count rather than the image size.
+fn selection(count: usize, total: usize, key: Option<&str>) -> Vec<usize> {
+    let Some(key) = key else {
+        return (0..count).collect();
+    };
+
+    let mut stream = KeyStream::new(key);
+    let mut swapped = HashMap::new();
+
+    (0..count)
+        .map(|idx| {
+            let other = idx + stream.below((total - idx) as u64) as usize;
+            let picked = *swapped.get(&other).unwrap_or(&other);
+            swapped.insert(other, *swapped.get(&idx).unwrap_or(&idx));
+            picked
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    /// Truecolor image with alpha, rows using every filter type.
+    fn testing_png(width: u32, height: u32, interlace_method: u8) -> Png {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: 6,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        };
+
+        let mut filtered = vec![];
+        for (width, height) in ihdr.passes() {
+            for line in 0..height {
+                filtered.push(line as u8 % 5);
+                filtered.extend((0..width * 4).map(|x| (x * 7 + line * 3) as u8));
+            }
+        }
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            chunk_of("IDAT", &encoder.finish().unwrap()),
+            chunk_of("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_filters_round_trip() {
+        for interlace_method in [0, 1] {
+            let mut png = testing_png(8, 6, interlace_method);
+            let pixels = Pixels::from_png(&png, &Limits::default()).unwrap();
+
+            assert_eq!(pixels.samples(), 8 * 6 * 3);
+
+            pixels.write_to(&mut png).unwrap();
+            assert_eq!(Pixels::from_png(&png, &Limits::default()).unwrap(), pixels);
+        }
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        for key in [None, Some("secret")] {
+            let mut png = testing_png(8, 6, 1);
+            let mut pixels = Pixels::from_png(&png, &Limits::default()).unwrap();
+            let original = pixels.clone();
+
+            pixels.embed(b"hi there", key, 1).unwrap();
+            pixels.write_to(&mut png).unwrap();
+
+            let mut pixels = Pixels::from_png(&png, &Limits::default()).unwrap();
+            assert_eq!(pixels.extract(key).unwrap(), b"hi there");
+
+            // only least significant bits of color samples change
+            for (idx, (a, b)) in
+                original.data.iter().zip(pixels.data.iter()).enumerate()
+            {
+                assert!(a ^ b <= 1);
+                assert!(idx % 4 != 3 || a == b);
+            }
+            assert!(original.differs_only_in_lsb(&pixels));
+
+            // alpha and higher bits must not change
+            pixels.data[3] ^= 1;
+            assert!(!original.differs_only_in_lsb(&pixels));
+            pixels.data[3] ^= 1;
+            pixels.data[0] ^= 2;
+            assert!(!original.differs_only_in_lsb(&pixels));
+        }
+
+        let mut pixels =
+            Pixels::from_png(&testing_png(8, 6, 0), &Limits::default()).unwrap();
+        assert!(pixels.embed(&[0; 15], None, 1).is_err());
+
+        pixels.embed(b"keyed", Some("secret"), 1).unwrap();
+        assert_ne!(pixels.extract(Some("wrong")).ok(), Some(b"keyed".to_vec()));
+        assert_ne!(pixels.extract(None).ok(), Some(b"keyed".to_vec()));
+    }
+
+    #[test]
+    fn test_matrix_embedding() {
+        let message = b"matrix embedding changes fewer samples";
+        let original =
+            Pixels::from_png(&testing_png(64, 32, 0), &Limits::default()).unwrap();
+        let mut changes = vec![];
+
+        for matrix in 1..=4 {
+            let mut pixels = original.clone();
+            changes.push(pixels.embed(message, Some("secret"), matrix).unwrap());
+
+            assert_eq!(pixels.extract(Some("secret")).unwrap(), message);
+            assert!(
+                changes[matrix as usize - 1]
+                    <= 32 + (message.len() * 8).div_ceil(matrix as usize)
+            );
+        }
+
+        assert!(changes[3] < changes[0]);
+        assert!(original.clone().embed(message, None, 9).is_err());
+        assert!(original.clone().embed(&[0; 400], None, 4).is_err());
+    }
+
+    #[test]
+    fn test_capacity_report() {
+        let report = CapacityReport::new("image.png", 6144, Some(500));
+
+        assert_eq!(report.codes.len(), 8);
+        assert_eq!(report.codes[0].capacity, 764);
+        assert_eq!(report.codes[0].changes_per_bit, 0.5);
+        assert_eq!(report.codes[0].expected_changes, Some(2016.0));
+        assert_eq!(report.codes[2].block_size, 7);
+        assert_eq!(report.codes[2].capacity, 327);
+        assert_eq!(report.codes[2].expected_changes, None);
+        assert!(report.to_string().contains("does not fit"));
+    }
+
+    #[test]
+    fn test_selection() {
+        let keyed = selection(50, 100, Some("secret"));
+        let mut sorted = keyed.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        assert_eq!(sorted.len(), 50);
+        assert!(keyed.iter().all(|&carrier| carrier < 100));
+        assert_ne!(keyed, selection(50, 100, None));
+        assert_ne!(keyed, selection(50, 100, Some("other")));
+        assert_eq!(keyed[..10], selection(10, 100, Some("secret")));
+    }
+
+    #[test]
+    fn test_unsupported_images() {
+        let mut png = testing_png(8, 6, 0);
+        let mut ihdr = png.ihdr().unwrap();
+        ihdr.color_type = 3;
+        png.chunks[0] = ihdr.to_chunk();
+
+        assert!(Pixels::from_png(&png, &Limits::default()).is_err());
+    }
+}
@@ -1,8 +1,10 @@
+use crate::ancillary::Ancillary;
 use crate::chunk::Chunk;
 use crate::chunk_error::ChunkError;
+use crate::ihdr::Ihdr;
+use crate::limits::{LimitError, Limits};
 use std::fmt;
 use std::fs;
-use std::io::{BufRead, BufReader, Read};
 use std::path::PathBuf;
 
 use crate::{Error, Result};
@@ -14,13 +16,20 @@ pub struct Png {
 
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
-    #[allow(dead_code)]
-    fn from_chunks(chunks: Vec<Chunk>) -> Self {
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
         Png { chunks }
     }
 
+    /// Inserts the chunk directly before IEND.
+    ///
+    /// IEND is looked up by type so the chunk always lands after the
+    /// last IDAT/fdAT and never inside an animation frame.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        let insert_idx = self.chunks.len() - 1;
+        let insert_idx = self
+            .chunks
+            .iter()
+            .rposition(|chunk| chunk.chunk_type().to_string() == "IEND")
+            .unwrap_or(self.chunks.len());
         self.chunks.insert(insert_idx, chunk);
     }
 
@@ -34,7 +43,7 @@ impl Png {
             let filtered_chunks = self
                 .chunks
                 .iter()
-                .take(idx - 1)
+                .take(idx)
                 .chain(self.chunks[idx + 1..].iter())
                 .cloned()
                 .collect();
@@ -46,6 +55,30 @@ impl Png {
         }
     }
 
+    /// Puts chunk in place of the first chunk of its type and drops
+    /// any later ones.
+    ///
+    /// Returns the index of the replaced chunk, None (and no change)
+    /// if the png has no chunk of the type.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Option<usize> {
+        let chunk_type = chunk.chunk_type().clone();
+        let idx = self
+            .chunks
+            .iter()
+            .position(|chunk| *chunk.chunk_type() == chunk_type)?;
+
+        self.chunks[idx] = chunk;
+
+        let mut position = 0;
+        self.chunks.retain(|chunk| {
+            let keep = position <= idx || *chunk.chunk_type() != chunk_type;
+            position += 1;
+            keep
+        });
+
+        Some(idx)
+    }
+
     #[allow(dead_code)]
     fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
@@ -55,20 +88,15 @@ impl Png {
         &self.chunks
     }
 
-    pub fn get_bytes(reader: &mut BufReader<&[u8]>) -> Result<Vec<u8>> {
-        let mut b_length = [0; 4];
-        reader.read_exact(&mut b_length)?;
-        let data_length = u32::from_be_bytes(b_length);
-
-        // add 8 (4bytes type, 4bytes crc) to data length
-        let mut b_rest = vec![0; (data_length + 8) as usize];
-        reader.read_exact(&mut b_rest)?;
+    /// Parses the IHDR chunk, which must be the first chunk.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        let chunk = self.chunks.first().ok_or(ChunkError("Png has no chunks"))?;
+        Ihdr::try_from(chunk)
+    }
 
-        Ok(b_length
-            .iter()
-            .chain(b_rest.iter())
-            .copied()
-            .collect::<Vec<u8>>())
+    /// True if the png carries an acTL chunk.
+    pub fn is_animated(&self) -> bool {
+        !self.chunk_by_type("acTL").is_empty()
     }
 
     pub fn chunk_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
@@ -78,43 +106,92 @@ impl Png {
             .collect::<Vec<&Chunk>>()
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        Png::STANDARD_HEADER
-            .iter()
-            .copied()
-            .chain(self.chunks().iter().flat_map(|chunk| chunk.as_bytes()))
-            .collect()
+    /// Parses png bytes, keeping chunks whose crc does not match.
+    ///
+    /// Used when reading payloads that may be repaired afterwards.
+    pub fn from_bytes_lenient(bytes: &[u8], limits: &Limits) -> Result<Png> {
+        Png::parse(bytes, false, limits)
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
-
-    fn try_from(bytes: &[u8]) -> Result<Self> {
-        // standard header
-        let header = &bytes[0..8];
+    /// Parses png bytes like TryFrom, against limits instead of the defaults.
+    pub fn try_from_limited(bytes: &[u8], limits: &Limits) -> Result<Png> {
+        Png::parse(bytes, true, limits)
+    }
 
-        let is_header_invalid = header
-            .iter()
-            .zip(Png::STANDARD_HEADER.iter())
-            .any(|(a, b)| a.cmp(b) != std::cmp::Ordering::Equal);
+    /// Parses png bytes without trusting any length in them.
+    ///
+    /// Every length is checked against the remaining input and limits
+    /// before anything is allocated, so no input can panic the parser.
+    fn parse(bytes: &[u8], check_crc: bool, limits: &Limits) -> Result<Png> {
+        limits.check_file_size(bytes.len() as u64)?;
 
-        if is_header_invalid {
+        if !bytes.starts_with(&Png::STANDARD_HEADER) {
             return Err(Box::new(ChunkError("Header bytes are invalid")));
         }
 
         // skip header
-        let mut reader = BufReader::new(&bytes[8..]);
+        let mut rest = &bytes[Png::STANDARD_HEADER.len()..];
         let mut chunks = vec![];
 
-        while !reader.fill_buf()?.is_empty() {
-            let byte_seq = Png::get_bytes(&mut reader)?;
-            let chunk = Chunk::try_from(byte_seq.as_slice())?;
+        while !rest.is_empty() {
+            if chunks.len() == limits.max_chunks {
+                return Err(Box::new(LimitError::ChunkCount(limits.max_chunks)));
+            }
+
+            // length, type and crc fields are 4 bytes each
+            if rest.len() < 12 {
+                return Err(Box::new(ChunkError("Chunk is truncated")));
+            }
+
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+            limits.check_chunk_length(length)?;
+
+            let end = length as usize + 12;
+            if rest.len() < end {
+                return Err(Box::new(ChunkError("Chunk is truncated")));
+            }
+
+            let chunk = if check_crc {
+                Chunk::try_from(&rest[..end])?
+            } else {
+                Chunk::from_bytes_unchecked(&rest[..end])?
+            };
 
             chunks.push(chunk);
+            rest = &rest[end..];
         }
 
-        Ok(Png { chunks })
+        let png = Png { chunks };
+
+        // the image data is only decompressed later, refuse it up front
+        if let Ok(ihdr) = png.ihdr() {
+            if let Some(required) = ihdr.image_data_length() {
+                if required > limits.max_image_data {
+                    return Err(Box::new(LimitError::ImageData(
+                        required,
+                        limits.max_image_data,
+                    )));
+                }
+            }
+        }
+
+        Ok(png)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(self.chunks().iter().flat_map(|chunk| chunk.as_bytes()))
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Png::parse(bytes, true, &Limits::default())
     }
 }
 
@@ -122,6 +199,7 @@ impl TryFrom<PathBuf> for Png {
     type Error = Error;
 
     fn try_from(path: PathBuf) -> Result<Self> {
+        Limits::default().check_file_size(fs::metadata(&path)?.len())?;
         let contents = fs::read(path)?;
         Png::try_from(contents.as_slice())
     }
@@ -139,8 +217,13 @@ impl fmt::Display for Png {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Png {{\n",)?;
         writeln!(f, "chunks: [\n",)?;
+        let color_type = self.ihdr().ok().map(|ihdr| ihdr.color_type);
+
         for chunk in self.chunks.iter() {
-            writeln!(f, "{}", chunk.to_string())?;
+            let value = Ancillary::decode(chunk, color_type).ok();
+            chunk
+                .fmt_with_value(f, value.as_ref().map(|v| v as &dyn fmt::Display))?;
+            writeln!(f)?;
         }
         writeln!(f, "]",)?;
         write!(f, "}}",)?;
@@ -153,8 +236,10 @@ mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
+    use crate::limits::MAX_U31;
     use std::convert::TryFrom;
 
+    #[allow(clippy::vec_init_then_push)]
     fn testing_chunks() -> Vec<Chunk> {
         let mut chunks = Vec::new();
 
@@ -253,6 +338,118 @@ mod tests {
         assert!(error == Box::new(ChunkError("Chunk bytes are invalid")));
     }
 
+    #[test]
+    fn test_lenient_keeps_bad_crc() {
+        let mut bytes = Png::from_chunks(testing_chunks()).as_bytes();
+        // flip a data byte of the first chunk
+        bytes[16] ^= 0xFF;
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let png =
+            Png::from_bytes_lenient(bytes.as_ref(), &Limits::default()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+        assert!(!png.chunks()[0].is_crc_valid());
+        assert!(png.chunks()[1].is_crc_valid());
+    }
+
+    #[test]
+    fn test_short_input_is_an_error() {
+        for length in 0..8 {
+            assert!(Png::try_from(&PNG_FILE[..length]).is_err());
+        }
+
+        // cuts at a chunk boundary still parse, the rest must fail cleanly
+        for length in 8..PNG_FILE.len() {
+            let _ = Png::try_from(&PNG_FILE[..length]);
+            let _ = Png::from_bytes_lenient(&PNG_FILE[..length], &Limits::default());
+        }
+        assert!(Png::try_from(&PNG_FILE[..PNG_FILE.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_huge_chunk_length_is_rejected() {
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        bytes.extend_from_slice(b"ruSt\0\0\0\0");
+
+        let error: Box<LimitError> = Png::try_from(bytes.as_ref())
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert_eq!(*error, LimitError::ChunkLength(u32::MAX, MAX_U31));
+
+        // within 2^31-1 but longer than the input
+        bytes[8] = 0x7F;
+        let error: Box<LimitError> = Png::try_from(bytes.as_ref())
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert_eq!(
+            *error,
+            LimitError::ChunkLength(0x7FFF_FFFF, Limits::default().max_chunk_length)
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let bytes = Png::from_chunks(testing_chunks()).as_bytes();
+        let limits = Limits {
+            max_chunks: 2,
+            ..Limits::default()
+        };
+        let error: Box<LimitError> = Png::try_from_limited(&bytes, &limits)
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert_eq!(*error, LimitError::ChunkCount(2));
+
+        let limits = Limits {
+            max_file_size: bytes.len() as u64 - 1,
+            ..Limits::default()
+        };
+        assert!(Png::try_from_limited(&bytes, &limits).is_err());
+
+        let limits = Limits {
+            max_chunk_length: 19,
+            ..Limits::default()
+        };
+        assert!(Png::try_from_limited(&bytes, &limits).is_err());
+
+        // 40x50 rgba needs 8040 bytes of filtered image data
+        let limits = Limits {
+            max_image_data: 8000,
+            ..Limits::default()
+        };
+        let error: Box<LimitError> = Png::try_from_limited(&PNG_FILE, &limits)
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(*error, LimitError::ImageData(_, 8000)));
+    }
+
+    #[test]
+    fn test_mutated_input_does_not_panic() {
+        // xorshift, deterministic so failures can be reproduced
+        let mut state: u32 = 0x2545_F491;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize
+        };
+
+        for _ in 0..2000 {
+            let mut bytes = PNG_FILE.to_vec();
+            for _ in 0..1 + next() % 8 {
+                let idx = next() % bytes.len();
+                bytes[idx] = next() as u8;
+            }
+            let _ = Png::try_from(bytes.as_ref());
+            let _ = Png::from_bytes_lenient(bytes.as_ref(), &Limits::default());
+        }
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -286,6 +483,34 @@ mod tests {
         assert!(chunk.is_empty());
     }
 
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a duplicate").unwrap());
+
+        let idx = png.replace_chunk(chunk_from_strings("miDl", "New").unwrap());
+
+        assert_eq!(idx, Some(1));
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.chunks()[1].data_as_string().unwrap(), "New");
+        assert_eq!(
+            png.replace_chunk(chunk_from_strings("noNe", "").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remove_chunk_keeps_neighbours() {
+        let mut png = testing_png();
+        png.remove_chunk("miDl").unwrap();
+        let types = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(types, vec!["FrSt", "LASt"]);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -293,6 +518,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::iter_cloned_collect)]
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
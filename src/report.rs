@@ -0,0 +1,336 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::ancillary::{self, Ancillary};
+use crate::apng::{AnimationControl, FrameControl};
+use crate::c2pa::{self, ManifestStore};
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::cli::OutputFormat;
+use crate::ihdr::Ihdr;
+use crate::png::Png;
+use crate::signature::SignatureStatus;
+use crate::Result;
+
+/// Version of the machine-readable output, bumped on breaking changes.
+/// The schema is documented in docs/output.md.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Parsed contents of chunk types pngsneak understands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Decoded {
+    Header(Ihdr),
+    Palette {
+        entries: usize,
+    },
+    Text {
+        keyword: String,
+        text: String,
+    },
+    AnimationControl(AnimationControl),
+    FrameControl(FrameControl),
+    Ancillary(Ancillary),
+    ManifestStore {
+        manifests: usize,
+        active_manifest: Option<String>,
+    },
+}
+
+/// Latin-1 bytes as a string, every byte maps to the same code point.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+impl Decoded {
+    /// None for chunk types without a decoder or with malformed data.
+    ///
+    /// color_type comes from the IHDR of the png, some ancillary chunks
+    /// can't be told apart without it.
+    pub fn from_chunk(chunk: &Chunk, color_type: Option<u8>) -> Option<Decoded> {
+        let data = chunk.data();
+        let chunk_type = chunk.chunk_type().to_string();
+
+        if ancillary::ANCILLARY_CHUNK_TYPES.contains(&chunk_type.as_str()) {
+            return Ancillary::decode(chunk, color_type)
+                .ok()
+                .map(Decoded::Ancillary);
+        }
+
+        match chunk_type.as_str() {
+            "IHDR" => Ihdr::try_from(chunk).ok().map(Decoded::Header),
+            "PLTE" => Some(Decoded::Palette {
+                entries: data.len() / 3,
+            }),
+            "tEXt" => {
+                let separator = data.iter().position(|&b| b == 0)?;
+                Some(Decoded::Text {
+                    keyword: latin1(&data[..separator]),
+                    text: latin1(&data[separator + 1..]),
+                })
+            }
+            "acTL" => AnimationControl::try_from(chunk)
+                .ok()
+                .map(Decoded::AnimationControl),
+            "fcTL" => FrameControl::try_from(chunk)
+                .ok()
+                .map(Decoded::FrameControl),
+            c2pa::MANIFEST_CHUNK_TYPE => {
+                let store = ManifestStore::try_from(data).ok()?;
+                Some(Decoded::ManifestStore {
+                    manifests: store.manifests.len(),
+                    active_manifest: store
+                        .active()
+                        .map(|manifest| manifest.label.clone()),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// One chunk of a png as printed by print --format json|yaml.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkReport {
+    pub index: usize,
+    /// byte offset of the chunk's length field from the start of the file
+    pub offset: usize,
+    pub length: u32,
+    pub chunk_type: String,
+    pub critical: bool,
+    pub public: bool,
+    pub safe_to_copy: bool,
+    pub crc: u32,
+    pub crc_valid: bool,
+    pub decoded: Option<Decoded>,
+}
+
+impl ChunkReport {
+    pub fn new(
+        index: usize,
+        offset: usize,
+        chunk: &Chunk,
+        color_type: Option<u8>,
+    ) -> ChunkReport {
+        let chunk_type = chunk.chunk_type();
+
+        ChunkReport {
+            index,
+            offset,
+            length: chunk.length(),
+            chunk_type: chunk_type.to_string(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            crc: chunk.crc(),
+            crc_valid: chunk.is_crc_valid(),
+            decoded: Decoded::from_chunk(chunk, color_type),
+        }
+    }
+}
+
+/// Reports for every chunk of the png, offsets included.
+pub fn chunk_reports(png: &Png) -> Vec<ChunkReport> {
+    let mut offset = Png::STANDARD_HEADER.len();
+    let color_type = png.ihdr().ok().map(|ihdr| ihdr.color_type);
+
+    png.chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let report = ChunkReport::new(index, offset, chunk, color_type);
+            // length, type and crc fields are 4 bytes each
+            offset += chunk.length() as usize + 12;
+            report
+        })
+        .collect()
+}
+
+/// Output of print.
+#[derive(Debug, Clone, Serialize)]
+pub struct PngReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub size: usize,
+    pub chunks: Vec<ChunkReport>,
+}
+
+impl PngReport {
+    /// size is the byte count of the file png was parsed from.
+    pub fn new(path: &str, png: &Png, size: usize) -> PngReport {
+        PngReport {
+            schema_version: SCHEMA_VERSION,
+            path: path.to_string(),
+            size,
+            chunks: chunk_reports(png),
+        }
+    }
+}
+
+/// A single message found by decode.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageReport {
+    /// index of the chunk holding the message
+    pub index: usize,
+    /// None if the payload could not be recovered
+    pub message: Option<String>,
+    /// only set when a verify key was given
+    pub signature: Option<SignatureStatus>,
+    /// repaired symbols, only set for error corrected payloads
+    pub repaired: Option<usize>,
+    pub crc_valid: bool,
+    pub error: Option<String>,
+}
+
+/// Notes in brackets followed by the message, the text form of decode.
+impl fmt::Display for MessageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut notes = vec![];
+
+        if let Some(status) = &self.signature {
+            notes.push(status.to_string());
+        }
+
+        match self.repaired {
+            Some(repaired) if repaired > 0 => {
+                notes.push(format!("repaired {} symbol(s)", repaired))
+            }
+            Some(_) => {}
+            None if !self.crc_valid => notes.push("crc mismatch".to_string()),
+            None => {}
+        }
+
+        if let Some(error) = &self.error {
+            notes.push(error.clone());
+        }
+
+        if !notes.is_empty() {
+            write!(f, "[{}] ", notes.join(", "))?;
+        }
+
+        write!(f, "{}", self.message.as_deref().unwrap_or_default())
+    }
+}
+
+/// Output of decode.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodeReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub chunk_type: String,
+    pub messages: Vec<MessageReport>,
+}
+
+/// Output of remove.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoveReport {
+    pub schema_version: u32,
+    pub path: String,
+    pub output_path: String,
+    pub removed: ChunkReport,
+}
+
+/// Serializes value as a single line of JSON or a YAML document.
+///
+/// Every document stands on its own so batch runs can print one per file.
+pub fn render<T: Serialize>(value: &T, format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string(value)?),
+        OutputFormat::Yaml => Ok(format!("---\n{}", serde_yaml::to_string(value)?)),
+        OutputFormat::Text => Err(Box::new(ChunkError(
+            "Text output has no schema, use json or yaml",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    #[test]
+    fn test_chunk_offsets_and_flags() {
+        let png = Png::from_chunks(vec![
+            chunk_of("tEXt", b"Title\0Hello"),
+            chunk_of("ruSt", b"secret"),
+            chunk_of("IEND", b""),
+        ]);
+
+        let reports = chunk_reports(&png);
+
+        assert_eq!(
+            reports.iter().map(|r| r.offset).collect::<Vec<usize>>(),
+            vec![8, 31, 49]
+        );
+        assert!(!reports[1].critical);
+        assert!(!reports[1].public);
+        assert!(reports[1].safe_to_copy);
+        assert!(reports[2].critical);
+        assert_eq!(
+            reports[0].decoded,
+            Some(Decoded::Text {
+                keyword: "Title".to_string(),
+                text: "Hello".to_string()
+            })
+        );
+        assert_eq!(reports[1].decoded, None);
+    }
+
+    #[test]
+    fn test_ancillary_uses_color_type() {
+        let ihdr = |color_type: u8| Ihdr {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+        let trns = |color_type: u8| {
+            let png = Png::from_chunks(vec![
+                ihdr(color_type).to_chunk(),
+                chunk_of("tRNS", &[0, 7]),
+            ]);
+            chunk_reports(&png)[1].decoded.clone()
+        };
+
+        assert_eq!(
+            trns(0).map(|decoded| render(&decoded, OutputFormat::Json).unwrap()),
+            Some("{\"gray\":7}".to_string())
+        );
+        assert_eq!(
+            trns(3).map(|decoded| render(&decoded, OutputFormat::Json).unwrap()),
+            Some("{\"alphas\":[0,7]}".to_string())
+        );
+        assert_eq!(trns(6), None);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let png = Png::from_chunks(vec![chunk_of("IEND", b"")]);
+        let report = PngReport::new("a.png", &png, png.as_bytes().len());
+        let json = render(&report, OutputFormat::Json).unwrap();
+
+        assert!(!json.contains('\n'));
+        assert!(json.contains("\"chunk_type\":\"IEND\""));
+        assert!(json.contains("\"crc_valid\":true"));
+        assert!(json.contains("\"size\":20"));
+        assert!(render(&report, OutputFormat::Text).is_err());
+    }
+
+    #[test]
+    fn test_message_text() {
+        let message = MessageReport {
+            index: 2,
+            message: Some("hi".to_string()),
+            signature: Some(SignatureStatus::Valid),
+            repaired: Some(3),
+            crc_valid: true,
+            error: None,
+        };
+
+        assert_eq!(message.to_string(), "[valid, repaired 3 symbol(s)] hi");
+    }
+}
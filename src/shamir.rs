@@ -0,0 +1,384 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+const SHARE_VERSION: u8 = 2;
+
+/// version (1) + set id (8) + threshold (1) + index (1)
+const SHARE_HEADER_LENGTH: usize = 11;
+
+/// Truncated SHA-256 of the rest of the share.
+const CHECKSUM_LENGTH: usize = 8;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShareError {
+    Invalid(&'static str),
+    TooFewShares { found: usize, threshold: usize },
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::Invalid(msg) => write!(f, "{}", msg),
+            ShareError::TooFewShares { found, threshold } => write!(
+                f,
+                "Too few shares: found {} valid share(s), {} are required",
+                found, threshold
+            ),
+        }
+    }
+}
+
+impl error::Error for ShareError {}
+
+/// One share of a split payload, stored as the data of its own chunk.
+///
+/// Data layout: [version, set id (8 bytes), threshold, index, share bytes,
+/// checksum (8 bytes)]
+///
+/// The checksum covers everything before it, so a corrupted share is
+/// rejected instead of turning the secret into garbage. It is a digest
+/// of the share, not of the secret, so fewer than threshold shares still
+/// reveal nothing about the secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub set_id: [u8; 8],
+    pub threshold: u8,
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let digest = Sha256::digest(bytes);
+    let mut checksum = [0; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&digest[..CHECKSUM_LENGTH]);
+    checksum
+}
+
+impl Share {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = [SHARE_VERSION]
+            .iter()
+            .chain(self.set_id.iter())
+            .chain([self.threshold, self.index].iter())
+            .chain(self.data.iter())
+            .copied()
+            .collect::<Vec<u8>>();
+        bytes.extend(checksum(&bytes));
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Share {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < SHARE_HEADER_LENGTH + CHECKSUM_LENGTH
+            || bytes[0] != SHARE_VERSION
+        {
+            return Err(Box::new(ShareError::Invalid("Data is not a share")));
+        }
+
+        let (checked, stored) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+
+        if checksum(checked) != stored {
+            return Err(Box::new(ShareError::Invalid(
+                "Share checksum does not match, the share is corrupted",
+            )));
+        }
+
+        let share = Share {
+            set_id: bytes[1..9].try_into()?,
+            threshold: bytes[9],
+            index: bytes[10],
+            data: checked[SHARE_HEADER_LENGTH..].to_vec(),
+        };
+
+        if share.threshold < 2 || share.index == 0 {
+            return Err(Box::new(ShareError::Invalid("Share header is invalid")));
+        }
+
+        Ok(share)
+    }
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            // x^8 + x^4 + x^3 + x + 1
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+
+    product
+}
+
+/// a^254 is the multiplicative inverse of a in GF(256).
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    let mut base = a;
+    let mut exp = 254;
+
+    while exp != 0 {
+        if exp & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+
+    result
+}
+
+/// Evaluates the polynomial (constant term first) at x.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |acc, &coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+/// Splits secret into share_count shares, any threshold of which
+/// reconstruct it (Shamir secret sharing over GF(256)).
+///
+/// Every byte of the secret is the constant term of its own random
+/// polynomial of degree threshold - 1. Share i holds the polynomials
+/// evaluated at x = i. Fewer than threshold shares reveal nothing.
+pub fn split(secret: &[u8], share_count: u8, threshold: u8) -> Result<Vec<Share>> {
+    if threshold < 2 {
+        return Err(Box::new(ShareError::Invalid(
+            "Threshold must be at least 2",
+        )));
+    }
+
+    if share_count < threshold {
+        return Err(Box::new(ShareError::Invalid(
+            "Share count must be at least the threshold",
+        )));
+    }
+
+    let mut set_id = [0; 8];
+    OsRng.fill_bytes(&mut set_id);
+
+    let mut shares = (1..=share_count)
+        .map(|index| Share {
+            set_id,
+            threshold,
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect::<Vec<Share>>();
+
+    let mut coefficients = vec![0; threshold as usize];
+
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+
+        for share in shares.iter_mut() {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+
+    Ok(shares)
+}
+
+/// Reconstructs the secret from a collection of shares, returns it along
+/// with the number of shares dropped.
+///
+/// Shares are grouped by set id and the largest set is used. Duplicate
+/// indices are ignored. Shares that disagree with the rest of the set on
+/// threshold or length are dropped. Errors with TooFewShares when fewer
+/// distinct shares than the threshold remain.
+pub fn combine(shares: &[Share]) -> Result<(Vec<u8>, usize)> {
+    let mut sets: BTreeMap<[u8; 8], BTreeMap<u8, &Share>> = BTreeMap::new();
+
+    for share in shares {
+        sets.entry(share.set_id)
+            .or_default()
+            .entry(share.index)
+            .or_insert(share);
+    }
+
+    let set = sets.into_values().max_by_key(|set| set.len()).ok_or(
+        ShareError::TooFewShares {
+            found: 0,
+            threshold: 2,
+        },
+    )?;
+
+    // the (threshold, length) most shares of the set agree on
+    let mut layouts: BTreeMap<(u8, usize), usize> = BTreeMap::new();
+    for share in set.values() {
+        *layouts
+            .entry((share.threshold, share.data.len()))
+            .or_default() += 1;
+    }
+    let (threshold, length) = layouts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(layout, _)| layout)
+        .unwrap_or_default();
+    let threshold = threshold as usize;
+
+    let valid = set
+        .values()
+        .filter(|share| {
+            share.threshold as usize == threshold && share.data.len() == length
+        })
+        .collect::<Vec<&&Share>>();
+    let dropped = set.len() - valid.len();
+
+    if valid.len() < threshold {
+        return Err(Box::new(ShareError::TooFewShares {
+            found: valid.len(),
+            threshold,
+        }));
+    }
+
+    let used = &valid[..threshold];
+
+    // Lagrange basis polynomials evaluated at x = 0
+    let basis = used
+        .iter()
+        .map(|share_i| {
+            used.iter()
+                .filter(|share_j| share_j.index != share_i.index)
+                .fold(1, |acc, share_j| {
+                    let term =
+                        gf_mul(share_j.index, gf_inv(share_j.index ^ share_i.index));
+                    gf_mul(acc, term)
+                })
+        })
+        .collect::<Vec<u8>>();
+
+    let secret = (0..length)
+        .map(|byte_idx| {
+            used.iter()
+                .zip(basis.iter())
+                .fold(0, |acc, (share, &b)| acc ^ gf_mul(share.data[byte_idx], b))
+        })
+        .collect();
+
+    Ok((secret, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"This is where your secret message will be!";
+
+    #[test]
+    fn test_gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_shares_recover_secret() {
+        let shares = split(SECRET, 5, 3).unwrap();
+
+        assert_eq!(combine(&shares[0..3]).unwrap(), (SECRET.to_vec(), 0));
+        assert_eq!(combine(&shares[2..5]).unwrap(), (SECRET.to_vec(), 0));
+        assert_eq!(
+            combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()])
+                .unwrap(),
+            (SECRET.to_vec(), 0)
+        );
+        assert_eq!(combine(&shares).unwrap(), (SECRET.to_vec(), 0));
+    }
+
+    #[test]
+    fn test_too_few_shares() {
+        let shares = split(SECRET, 5, 3).unwrap();
+        let error =
+            combine(&[shares[0].clone(), shares[1].clone(), shares[1].clone()])
+                .unwrap_err()
+                .downcast::<ShareError>()
+                .unwrap();
+
+        assert_eq!(
+            *error,
+            ShareError::TooFewShares {
+                found: 2,
+                threshold: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_share_round_trip() {
+        let shares = split(SECRET, 3, 2).unwrap();
+        let bytes = shares[1].as_bytes();
+
+        assert_eq!(Share::try_from(bytes.as_slice()).unwrap(), shares[1]);
+        assert!(Share::try_from(&bytes[..5]).is_err());
+
+        // a share without its checksum is never accepted
+        let mut unchecked = bytes[..bytes.len() - CHECKSUM_LENGTH].to_vec();
+        unchecked[0] = 1;
+        assert!(Share::try_from(unchecked.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_share_is_rejected() {
+        let shares = split(SECRET, 3, 2).unwrap();
+        let mut bytes = shares[0].as_bytes();
+        bytes[SHARE_HEADER_LENGTH + 3] ^= 0x01;
+
+        assert!(Share::try_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(split(SECRET, 3, 1).is_err());
+        assert!(split(SECRET, 2, 3).is_err());
+    }
+
+    #[test]
+    fn test_sets_do_not_mix() {
+        let first = split(SECRET, 3, 2).unwrap();
+        let second = split(b"another secret", 3, 2).unwrap();
+
+        let mixed = vec![first[0].clone(), second[1].clone(), first[2].clone()];
+        assert_eq!(combine(&mixed).unwrap(), (SECRET.to_vec(), 0));
+    }
+
+    #[test]
+    fn test_mismatched_share_is_dropped() {
+        let mut shares = split(SECRET, 4, 3).unwrap();
+        shares[1].data.pop();
+        shares[2].threshold = 2;
+
+        assert_eq!(
+            *combine(&shares[..3])
+                .unwrap_err()
+                .downcast::<ShareError>()
+                .unwrap(),
+            ShareError::TooFewShares {
+                found: 1,
+                threshold: 3
+            }
+        );
+
+        let mut shares = split(SECRET, 4, 3).unwrap();
+        shares[1].data.pop();
+        assert_eq!(combine(&shares).unwrap(), (SECRET.to_vec(), 1));
+    }
+}
@@ -0,0 +1,569 @@
+use std::error;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::OsRng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::apng::APNG_CHUNK_TYPES;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// Ancillary, private, safe-to-copy chunk holding a detached
+/// Ed25519 signature for another chunk.
+///
+/// Data layout: [target chunk type (4 bytes), signature (64 bytes)]
+pub const SIGNATURE_CHUNK_TYPE: &str = "siGn";
+
+const SIGNATURE_DATA_LENGTH: usize = 4 + Signature::BYTE_SIZE;
+
+/// Ancillary, private, safe-to-copy chunk holding a signature over the
+/// image itself.
+///
+/// Data layout: [signature (64 bytes), included ancillary chunk types (4 bytes each)]
+pub const IMAGE_SIGNATURE_CHUNK_TYPE: &str = "imSg";
+
+const IMAGE_DIGEST_DOMAIN: &[u8] = b"pngsneak image signature v2";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignatureError(pub &'static str);
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let SignatureError(msg) = self;
+        write!(f, "{}", msg)
+    }
+}
+
+impl error::Error for SignatureError {}
+
+/// Result of checking a data chunk against a public key.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureStatus {
+    Valid,
+    Invalid,
+    Unsigned,
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            SignatureStatus::Valid => "valid",
+            SignatureStatus::Invalid => "invalid",
+            SignatureStatus::Unsigned => "unsigned",
+        };
+
+        write!(f, "{}", status)
+    }
+}
+
+/// Generates a new random signing key.
+pub fn generate_key() -> SigningKey {
+    SigningKey::generate(&mut OsRng)
+}
+
+/// Writes the secret key to key_path and the public key to key_path.pub,
+/// both hex encoded.
+///
+/// Existing files are never overwritten and the secret key is only
+/// readable by its owner.
+pub fn write_keypair(key: &SigningKey, key_path: &str) -> Result<()> {
+    let public_path = format!("{}.pub", key_path);
+
+    if Path::new(&public_path).exists() {
+        return Err(Box::new(SignatureError("Public key file already exists")));
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(key_path)?
+        .write_all(to_hex(&key.to_bytes()).as_bytes())?;
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(public_path)?
+        .write_all(to_hex(key.verifying_key().as_bytes()).as_bytes())?;
+
+    Ok(())
+}
+
+/// Reads a hex encoded secret key file.
+pub fn read_signing_key<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+    let bytes = read_key_bytes(path)?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Reads a hex encoded public key file.
+pub fn read_verifying_key<P: AsRef<Path>>(path: P) -> Result<VerifyingKey> {
+    let bytes = read_key_bytes(path)?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+fn read_key_bytes<P: AsRef<Path>>(path: P) -> Result<[u8; 32]> {
+    let contents = fs::read_to_string(path)?;
+    let bytes = from_hex(contents.trim())?;
+
+    bytes
+        .try_into()
+        .map_err(|_| SignatureError("Key must be 32 bytes").into())
+}
+
+/// Short, human comparable identifier for a public key.
+///
+/// First 16 bytes of the SHA-256 digest of the key, colon separated.
+pub fn fingerprint(key: &VerifyingKey) -> String {
+    let digest = Sha256::digest(key.as_bytes());
+
+    digest[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// The signed message is the chunk type bytes followed by the chunk data,
+/// so a payload cannot be moved to a different chunk type.
+fn signed_message(chunk: &Chunk) -> Vec<u8> {
+    chunk
+        .chunk_type()
+        .bytes()
+        .iter()
+        .chain(chunk.data().iter())
+        .copied()
+        .collect()
+}
+
+/// Creates a signature chunk for the passed data chunk.
+pub fn sign_chunk(key: &SigningKey, chunk: &Chunk) -> Result<Chunk> {
+    let signature = key.sign(&signed_message(chunk));
+
+    let data = chunk
+        .chunk_type()
+        .bytes()
+        .iter()
+        .chain(signature.to_bytes().iter())
+        .copied()
+        .collect();
+
+    Ok(Chunk::new(ChunkType::from_str(SIGNATURE_CHUNK_TYPE)?, data))
+}
+
+/// Splits a signature chunk into its target chunk type and signature.
+fn parse_signature_chunk(chunk: &Chunk) -> Option<([u8; 4], Signature)> {
+    let data = chunk.data();
+
+    if data.len() != SIGNATURE_DATA_LENGTH {
+        return None;
+    }
+
+    let target = [data[0], data[1], data[2], data[3]];
+    let signature = Signature::from_slice(&data[4..]).ok()?;
+
+    Some((target, signature))
+}
+
+/// True if chunk is a signature chunk for chunks of chunk_type.
+pub fn is_signature_for(chunk: &Chunk, chunk_type: &ChunkType) -> bool {
+    chunk.chunk_type().to_string() == SIGNATURE_CHUNK_TYPE
+        && parse_signature_chunk(chunk)
+            .is_some_and(|(target, _)| target == chunk_type.bytes())
+}
+
+/// Checks a data chunk against every signature chunk in the png that
+/// targets its chunk type.
+///
+/// Valid if any of them verifies, Invalid if there are signatures but none
+/// verify and Unsigned if there are no signatures for the chunk type.
+pub fn verify_chunk(
+    png: &Png,
+    chunk: &Chunk,
+    key: &VerifyingKey,
+) -> SignatureStatus {
+    let target = chunk.chunk_type().bytes();
+    let message = signed_message(chunk);
+
+    let signatures = png
+        .chunk_by_type(SIGNATURE_CHUNK_TYPE)
+        .into_iter()
+        .filter_map(parse_signature_chunk)
+        .filter(|(chunk_type, _)| *chunk_type == target)
+        .map(|(_, signature)| signature)
+        .collect::<Vec<Signature>>();
+
+    if signatures.is_empty() {
+        return SignatureStatus::Unsigned;
+    }
+
+    if signatures
+        .iter()
+        .any(|signature| key.verify(&message, signature).is_ok())
+    {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+/// Canonical SHA-256 digest of the image.
+///
+/// Covers every critical chunk (IHDR, PLTE, IDAT, IEND), the APNG
+/// animation chunks (acTL, fcTL, fdAT) plus the ancillary chunk types
+/// listed in include, in file order. Each chunk contributes its type,
+/// length and data. Consecutive IDAT chunks are hashed as one stream so
+/// re-splitting the image data keeps the signature valid. The include
+/// list is hashed too so it cannot be altered without invalidating the
+/// signature. Other ancillary chunks are ignored, which lets messages be
+/// added and removed without breaking it.
+pub fn image_digest(png: &Png, include: &[ChunkType]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(IMAGE_DIGEST_DOMAIN);
+
+    for chunk_type in include {
+        hasher.update(chunk_type.bytes());
+    }
+
+    let covered = png
+        .chunks
+        .iter()
+        .filter(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            chunk_type.is_critical()
+                || APNG_CHUNK_TYPES.contains(&chunk_type.to_string().as_str())
+                || include.contains(chunk_type)
+        })
+        .collect::<Vec<&Chunk>>();
+
+    for run in covered.chunk_by(|a, b| {
+        a.chunk_type().to_string() == "IDAT" && b.chunk_type().to_string() == "IDAT"
+    }) {
+        let length = run
+            .iter()
+            .map(|chunk| chunk.data().len() as u64)
+            .sum::<u64>();
+
+        hasher.update(run[0].chunk_type().bytes());
+        hasher.update(length.to_be_bytes());
+        for chunk in run {
+            hasher.update(chunk.data());
+        }
+    }
+
+    hasher.finalize().into()
+}
+
+/// Creates an image signature chunk for the png.
+pub fn sign_image(
+    key: &SigningKey,
+    png: &Png,
+    include: &[ChunkType],
+) -> Result<Chunk> {
+    if include
+        .iter()
+        .any(|chunk_type| chunk_type.to_string() == IMAGE_SIGNATURE_CHUNK_TYPE)
+    {
+        return Err(Box::new(SignatureError(
+            "The image signature chunk cannot sign itself",
+        )));
+    }
+
+    let signature = key.sign(&image_digest(png, include));
+
+    let data = signature
+        .to_bytes()
+        .into_iter()
+        .chain(include.iter().flat_map(|chunk_type| chunk_type.bytes()))
+        .collect();
+
+    Ok(Chunk::new(
+        ChunkType::from_str(IMAGE_SIGNATURE_CHUNK_TYPE)?,
+        data,
+    ))
+}
+
+/// Splits an image signature chunk into its signature and included
+/// ancillary chunk types.
+fn parse_image_signature_chunk(
+    chunk: &Chunk,
+) -> Option<(Signature, Vec<ChunkType>)> {
+    let data = chunk.data();
+
+    if data.len() < Signature::BYTE_SIZE
+        || !(data.len() - Signature::BYTE_SIZE).is_multiple_of(4)
+    {
+        return None;
+    }
+
+    let signature = Signature::from_slice(&data[..Signature::BYTE_SIZE]).ok()?;
+    let include = data[Signature::BYTE_SIZE..]
+        .chunks(4)
+        .map(|b| ChunkType::try_from([b[0], b[1], b[2], b[3]]).ok())
+        .collect::<Option<Vec<ChunkType>>>()?;
+
+    Some((signature, include))
+}
+
+/// Checks the image signature chunk(s) of the png.
+///
+/// Valid if any image signature verifies, Invalid if there are image
+/// signatures but none verify and Unsigned if there are none.
+pub fn verify_image(png: &Png, key: &VerifyingKey) -> SignatureStatus {
+    let signatures = png.chunk_by_type(IMAGE_SIGNATURE_CHUNK_TYPE);
+
+    if signatures.is_empty() {
+        return SignatureStatus::Unsigned;
+    }
+
+    let is_valid = signatures
+        .into_iter()
+        .filter_map(parse_image_signature_chunk)
+        .any(|(signature, include)| {
+            key.verify(&image_digest(png, &include), &signature).is_ok()
+        });
+
+    if is_valid {
+        SignatureStatus::Valid
+    } else {
+        SignatureStatus::Invalid
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(Box::new(SignatureError("Invalid hex string")));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| SignatureError("Invalid hex string").into())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write::testing_dir;
+
+    fn testing_png() -> Png {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ];
+        Png { chunks }
+    }
+
+    fn testing_chunk(message: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            message.as_bytes().to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_valid_signature() {
+        let key = generate_key();
+        let mut png = testing_png();
+        let chunk = testing_chunk("signed message");
+
+        png.append_chunk(chunk.clone());
+        png.append_chunk(sign_chunk(&key, &chunk).unwrap());
+
+        let status = verify_chunk(&png, &chunk, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_tampered_payload_is_invalid() {
+        let key = generate_key();
+        let mut png = testing_png();
+        let chunk = testing_chunk("signed message");
+        let swapped = testing_chunk("swapped message");
+
+        png.append_chunk(swapped.clone());
+        png.append_chunk(sign_chunk(&key, &chunk).unwrap());
+
+        let status = verify_chunk(&png, &swapped, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_wrong_key_is_invalid() {
+        let key = generate_key();
+        let other = generate_key();
+        let mut png = testing_png();
+        let chunk = testing_chunk("signed message");
+
+        png.append_chunk(chunk.clone());
+        png.append_chunk(sign_chunk(&key, &chunk).unwrap());
+
+        let status = verify_chunk(&png, &chunk, &other.verifying_key());
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_unsigned_chunk() {
+        let key = generate_key();
+        let mut png = testing_png();
+        let chunk = testing_chunk("plain message");
+
+        png.append_chunk(chunk.clone());
+
+        let status = verify_chunk(&png, &chunk, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Unsigned);
+    }
+
+    fn testing_image() -> Png {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            Chunk::new(
+                ChunkType::from_str("tEXt").unwrap(),
+                b"Title\0Dice".to_vec(),
+            ),
+            Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2, 3, 4]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ];
+        Png { chunks }
+    }
+
+    #[test]
+    fn test_image_signature_survives_metadata_edits() {
+        let key = generate_key();
+        let mut png = testing_image();
+
+        png.append_chunk(sign_image(&key, &png, &[]).unwrap());
+        png.append_chunk(testing_chunk("hidden message"));
+        png.remove_chunk("tEXt").unwrap();
+
+        let status = verify_image(&png, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_image_signature_detects_pixel_changes() {
+        let key = generate_key();
+        let mut png = testing_image();
+
+        png.append_chunk(sign_image(&key, &png, &[]).unwrap());
+        png.chunks[2] = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![9]);
+
+        let status = verify_image(&png, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_image_signature_covers_animation_frames() {
+        let key = generate_key();
+        let mut png = testing_image();
+        png.chunks.insert(
+            3,
+            Chunk::new(ChunkType::from_str("fdAT").unwrap(), vec![0, 0, 0, 1, 5]),
+        );
+
+        png.append_chunk(sign_image(&key, &png, &[]).unwrap());
+        png.chunks[3] =
+            Chunk::new(ChunkType::from_str("fdAT").unwrap(), vec![0, 0, 0, 1, 6]);
+
+        let status = verify_image(&png, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_image_signature_survives_resplit_image_data() {
+        let key = generate_key();
+        let mut png = testing_image();
+
+        png.append_chunk(sign_image(&key, &png, &[]).unwrap());
+        png.chunks.splice(
+            2..3,
+            [
+                Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![1, 2]),
+                Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![3, 4]),
+            ],
+        );
+
+        let status = verify_image(&png, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Valid);
+    }
+
+    #[test]
+    fn test_image_signature_covers_included_ancillary_chunks() {
+        let key = generate_key();
+        let mut png = testing_image();
+        let include = vec![ChunkType::from_str("tEXt").unwrap()];
+
+        png.append_chunk(sign_image(&key, &png, &include).unwrap());
+        png.remove_chunk("tEXt").unwrap();
+
+        let status = verify_image(&png, &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Invalid);
+    }
+
+    #[test]
+    fn test_unsigned_image() {
+        let key = generate_key();
+        let status = verify_image(&testing_image(), &key.verifying_key());
+        assert_eq!(status, SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn test_write_keypair() {
+        let dir = testing_dir("keypair");
+        let key_path = dir.join("key").to_string_lossy().to_string();
+        let key = generate_key();
+
+        write_keypair(&key, &key_path).unwrap();
+        assert_eq!(read_signing_key(&key_path).unwrap(), key);
+        assert_eq!(
+            read_verifying_key(format!("{}.pub", key_path)).unwrap(),
+            key.verifying_key()
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&key_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // an existing key is never overwritten
+        assert!(write_keypair(&generate_key(), &key_path).is_err());
+        assert_eq!(read_signing_key(&key_path).unwrap(), key);
+    }
+
+    #[test]
+    fn test_fingerprint_format() {
+        let key = generate_key();
+        let print = fingerprint(&key.verifying_key());
+
+        assert_eq!(print.split(':').count(), 16);
+        assert_eq!(print, fingerprint(&key.verifying_key()));
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let bytes = vec![0, 1, 127, 128, 255];
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+
+use clap::ArgEnum;
+
+use crate::apng;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::Result;
+
+/// Ancillary chunks that affect how the image is displayed and are kept
+/// unless a preset is given.
+pub const DEFAULT_KEEP: [&str; 6] = ["gAMA", "cHRM", "sRGB", "iCCP", "tRNS", "pHYs"];
+
+pub const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+/// Categories of metadata that can be stripped on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum StripPreset {
+    /// eXIf, may hold GPS location, camera and author data
+    Exif,
+    /// tEXt, zTXt and iTXt
+    Text,
+    /// tIME, the last modification time
+    Time,
+    /// every private chunk (second letter lowercase)
+    Private,
+}
+
+impl StripPreset {
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        let name = chunk_type.to_string();
+
+        match self {
+            StripPreset::Exif => name == "eXIf",
+            StripPreset::Text => TEXT_CHUNK_TYPES.contains(&name.as_str()),
+            StripPreset::Time => name == "tIME",
+            StripPreset::Private => !chunk_type.is_public(),
+        }
+    }
+}
+
+/// True if strip is allowed to remove the chunk.
+///
+/// Critical chunks are never touched and neither are the APNG chunks,
+/// which hold frames rather than metadata.
+fn is_strippable(chunk_type: &ChunkType) -> bool {
+    !chunk_type.is_critical() && !apng::is_apng_chunk_type(&chunk_type.to_string())
+}
+
+/// The default allowlist plus any extra chunk types.
+pub fn keep_list(extra: &[String]) -> Result<Vec<ChunkType>> {
+    DEFAULT_KEEP
+        .iter()
+        .map(|chunk_type| chunk_type.to_string())
+        .chain(extra.iter().cloned())
+        .map(|chunk_type| ChunkType::from_str(&chunk_type))
+        .collect()
+}
+
+/// Removes ancillary chunks from the png and returns them in file order.
+///
+/// Without presets every ancillary chunk not in keep is removed. With
+/// presets only chunks matching one of them are removed, chunks in keep
+/// are still left alone.
+pub fn strip(
+    png: &mut Png,
+    keep: &[ChunkType],
+    presets: &[StripPreset],
+) -> Vec<Chunk> {
+    let (removed, kept) = png.chunks.drain(..).partition(|chunk| {
+        let chunk_type = chunk.chunk_type();
+
+        is_strippable(chunk_type)
+            && !keep.contains(chunk_type)
+            && (presets.is_empty()
+                || presets.iter().any(|preset| preset.matches(chunk_type)))
+    });
+
+    png.chunks = kept;
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn testing_png() -> Png {
+        let chunks = [
+            "IHDR", "gAMA", "tEXt", "eXIf", "pHYs", "IDAT", "tIME", "ruSt", "zTXt",
+            "IEND",
+        ]
+        .iter()
+        .map(|chunk_type| chunk_of(chunk_type, &[]))
+        .collect();
+
+        Png::from_chunks(chunks)
+    }
+
+    fn chunk_types(chunks: &[Chunk]) -> Vec<String> {
+        chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_strip_keeps_allowlist_and_critical_chunks() {
+        let mut png = testing_png();
+        let removed = strip(&mut png, &keep_list(&[]).unwrap(), &[]);
+
+        assert_eq!(
+            chunk_types(&removed),
+            vec!["tEXt", "eXIf", "tIME", "ruSt", "zTXt"]
+        );
+        assert_eq!(
+            chunk_types(&png.chunks),
+            vec!["IHDR", "gAMA", "pHYs", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_strip_presets() {
+        let mut png = testing_png();
+        let removed = strip(
+            &mut png,
+            &keep_list(&[]).unwrap(),
+            &[StripPreset::Text, StripPreset::Exif],
+        );
+
+        assert_eq!(chunk_types(&removed), vec!["tEXt", "eXIf", "zTXt"]);
+
+        let mut png = testing_png();
+        let removed = strip(&mut png, &[], &[StripPreset::Private]);
+        assert_eq!(chunk_types(&removed), vec!["ruSt"]);
+    }
+
+    #[test]
+    fn test_strip_extra_keep() {
+        let mut png = testing_png();
+        let keep = keep_list(&["tIME".to_string()]).unwrap();
+        let removed = strip(&mut png, &keep, &[StripPreset::Time]);
+
+        assert!(removed.is_empty());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::ancillary;
+use crate::apng;
+use crate::chunk::Chunk;
+use crate::chunk_type::{
+    ChunkType, AFTER_PLTE, BEFORE_IDAT, BEFORE_PLTE, SINGLE_INSTANCE,
+};
+use crate::png::Png;
+use crate::signature;
+
+/// Region of a png a chunk is placed in, ordered as they appear in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Placement {
+    /// after IHDR, before PLTE (or the image data when there is none)
+    BeforePlte,
+    /// after PLTE, before the image data
+    BeforeIdat,
+    /// after the image data, before IEND
+    AfterIdat,
+}
+
+/// True for IDAT and fcTL, whichever comes first starts the image data
+/// (an fcTL precedes IDAT when the default image is the first frame).
+fn is_image_data(chunk: &Chunk) -> bool {
+    let chunk_type = chunk.chunk_type().to_string();
+    chunk_type == "IDAT" || chunk_type == "fcTL"
+}
+
+/// Where the chunk was found in the source png.
+fn source_placement(png: &Png, idx: usize) -> Placement {
+    let before = &png.chunks[..idx];
+
+    if before.iter().any(is_image_data) {
+        Placement::AfterIdat
+    } else if position(png, "PLTE").is_some_and(|plte| plte < idx) {
+        Placement::BeforeIdat
+    } else {
+        Placement::BeforePlte
+    }
+}
+
+/// Spec ordering rules for known chunk types, source position otherwise.
+fn placement(chunk_type: &ChunkType, source: Placement) -> Placement {
+    let name = chunk_type.to_string();
+
+    if BEFORE_PLTE.contains(&name.as_str()) {
+        Placement::BeforePlte
+    } else if AFTER_PLTE.contains(&name.as_str()) {
+        Placement::BeforeIdat
+    } else if BEFORE_IDAT.contains(&name.as_str()) {
+        source.min(Placement::BeforeIdat)
+    } else {
+        source
+    }
+}
+
+/// Index of the first chunk of the given type.
+fn position(png: &Png, chunk_type: &str) -> Option<usize> {
+    png.chunks
+        .iter()
+        .position(|chunk| chunk.chunk_type().to_string() == chunk_type)
+}
+
+/// Index the next chunk with the given placement is inserted at.
+fn insert_idx(png: &Png, placement: Placement) -> usize {
+    let image_data = png.chunks.iter().position(is_image_data);
+    let end = position(png, "IEND").unwrap_or(png.chunks.len());
+
+    match placement {
+        Placement::BeforePlte => position(png, "PLTE").or(image_data).unwrap_or(end),
+        Placement::BeforeIdat => image_data.unwrap_or(end),
+        Placement::AfterIdat => end,
+    }
+}
+
+/// True if transplant may copy the chunk at all.
+///
+/// Critical and APNG chunks describe the image itself and an image
+/// signature only holds for the image it was made for.
+fn is_transplantable(chunk_type: &ChunkType) -> bool {
+    let name = chunk_type.to_string();
+
+    !chunk_type.is_critical()
+        && !apng::is_apng_chunk_type(&name)
+        && name != signature::IMAGE_SIGNATURE_CHUNK_TYPE
+}
+
+/// Copies the ancillary chunks of source into target and returns the
+/// copied chunks in source order.
+///
+/// Only safe-to-copy chunks are copied unless include_unsafe is set.
+/// Chunks are inserted where the spec allows them, keeping their source
+/// order. A chunk type that may only appear once replaces the one
+/// already in target, other chunks identical to one in target are skipped.
+/// sRGB and iCCP exclude each other, so neither is
+/// copied when target (or an earlier copied chunk) has the other.
+pub fn transplant(
+    source: &Png,
+    target: &mut Png,
+    include_unsafe: bool,
+) -> Vec<Chunk> {
+    let mut color_spaces = target
+        .chunks
+        .iter()
+        .map(|chunk| chunk.chunk_type().to_string())
+        .filter(|name| ancillary::excluded_by(name).is_some())
+        .collect::<Vec<String>>();
+
+    let copied = source
+        .chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| {
+            let chunk_type = chunk.chunk_type();
+            is_transplantable(chunk_type)
+                && (include_unsafe || chunk_type.is_safe_to_copy())
+        })
+        // repeated runs must not pile up copies of multi-instance chunks
+        .filter(|(_, chunk)| {
+            SINGLE_INSTANCE.contains(&chunk.chunk_type().to_string().as_str())
+                || !target.chunks.contains(chunk)
+        })
+        .filter(|(_, chunk)| {
+            let name = chunk.chunk_type().to_string();
+            let Some(excluded) = ancillary::excluded_by(&name) else {
+                return true;
+            };
+
+            if color_spaces.iter().any(|present| present == excluded) {
+                return false;
+            }
+            color_spaces.push(name);
+            true
+        })
+        .map(|(idx, chunk)| {
+            let placement =
+                placement(chunk.chunk_type(), source_placement(source, idx));
+            (placement, chunk.clone())
+        })
+        .collect::<Vec<(Placement, Chunk)>>();
+
+    for (_, chunk) in copied.iter() {
+        let name = chunk.chunk_type().to_string();
+
+        if SINGLE_INSTANCE.contains(&name.as_str()) {
+            while target.remove_chunk(&name).is_some() {}
+        }
+    }
+
+    for (placement, chunk) in copied.iter() {
+        let idx = insert_idx(target, *placement);
+        target.chunks.insert(idx, chunk.clone());
+    }
+
+    copied.into_iter().map(|(_, chunk)| chunk).collect()
+}
+
+/// Puts chunk into target and returns its index.
+///
+/// An existing chunk of the same type is overwritten in place (and any
+/// later ones dropped), otherwise the chunk is inserted where the spec
+/// wants it, after the image data for chunk types without a rule.
+pub fn set_chunk(target: &mut Png, chunk: Chunk) -> usize {
+    let placement = placement(chunk.chunk_type(), Placement::AfterIdat);
+
+    match target.replace_chunk(chunk.clone()) {
+        Some(idx) => idx,
+        None => {
+            let idx = insert_idx(target, placement);
+            target.chunks.insert(idx, chunk);
+            idx
+        }
+    }
+}
+
+/// Inserts chunk before the image data and returns its index.
+pub fn insert_before_image_data(target: &mut Png, chunk: Chunk) -> usize {
+    let idx = insert_idx(target, Placement::BeforeIdat);
+    target.chunks.insert(idx, chunk);
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn testing_png(chunk_types: &[&str]) -> Png {
+        Png::from_chunks(
+            chunk_types
+                .iter()
+                .map(|chunk_type| chunk_of(chunk_type, &[]))
+                .collect(),
+        )
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks
+            .iter()
+            .map(|chunk| chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_transplant_safe_chunks_only() {
+        let source = testing_png(&[
+            "IHDR", "gAMA", "pHYs", "tEXt", "IDAT", "ruSt", "ruST", "IEND",
+        ]);
+        let mut target = testing_png(&["IHDR", "IDAT", "IEND"]);
+
+        let copied = transplant(&source, &mut target, false);
+
+        assert_eq!(copied.len(), 3);
+        assert_eq!(
+            chunk_types(&target),
+            vec!["IHDR", "pHYs", "tEXt", "IDAT", "ruSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_transplant_unsafe_chunks_respect_plte() {
+        let source = testing_png(&["IHDR", "gAMA", "tRNS", "IDAT", "tIME", "IEND"]);
+        let mut target =
+            testing_png(&["IHDR", "PLTE", "tIME", "IDAT", "IDAT", "IEND"]);
+
+        transplant(&source, &mut target, true);
+
+        assert_eq!(
+            chunk_types(&target),
+            vec!["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tIME", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_transplant_skips_critical_and_image_signature() {
+        let source = testing_png(&["IHDR", "PLTE", "IDAT", "imSg", "IEND"]);
+        let mut target = testing_png(&["IHDR", "IDAT", "IEND"]);
+
+        assert!(transplant(&source, &mut target, true).is_empty());
+        assert_eq!(chunk_types(&target), vec!["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_transplant_keeps_color_spaces_exclusive() {
+        let source = testing_png(&["IHDR", "sRGB", "gAMA", "IDAT", "IEND"]);
+        let mut target = testing_png(&["IHDR", "iCCP", "IDAT", "IEND"]);
+
+        transplant(&source, &mut target, true);
+        assert_eq!(
+            chunk_types(&target),
+            vec!["IHDR", "iCCP", "gAMA", "IDAT", "IEND"]
+        );
+
+        // an invalid source with both only passes on the first
+        let source = testing_png(&["IHDR", "iCCP", "sRGB", "IDAT", "IEND"]);
+        let mut target = testing_png(&["IHDR", "IDAT", "IEND"]);
+
+        transplant(&source, &mut target, true);
+        assert_eq!(chunk_types(&target), vec!["IHDR", "iCCP", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_transplant_twice_copies_once() {
+        let source = Png::from_chunks(vec![
+            chunk_of("IHDR", &[]),
+            chunk_of("tEXt", b"a\0b"),
+            chunk_of("siGn", &[1; 68]),
+            chunk_of("IDAT", &[]),
+            chunk_of("IEND", &[]),
+        ]);
+        let mut target = testing_png(&["IHDR", "IDAT", "IEND"]);
+
+        assert_eq!(transplant(&source, &mut target, false).len(), 2);
+        assert!(transplant(&source, &mut target, false).is_empty());
+        assert_eq!(
+            chunk_types(&target),
+            vec!["IHDR", "tEXt", "siGn", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_set_chunk() {
+        let mut target = testing_png(&["IHDR", "PLTE", "IDAT", "pHYs", "IEND"]);
+        assert_eq!(set_chunk(&mut target, chunk_of("sRGB", &[1])), 1);
+        assert_eq!(set_chunk(&mut target, chunk_of("tIME", &[1])), 5);
+        assert_eq!(set_chunk(&mut target, chunk_of("pHYs", &[1])), 4);
+        assert_eq!(target.chunks[4].data(), &[1]);
+        assert_eq!(
+            chunk_types(&target),
+            vec!["IHDR", "sRGB", "PLTE", "IDAT", "pHYs", "tIME", "IEND"]
+        );
+    }
+}
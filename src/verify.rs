@@ -0,0 +1,191 @@
+use std::error;
+use std::fmt;
+
+use crate::apng;
+use crate::chunk::Chunk;
+use crate::fec;
+use crate::limits::Limits;
+use crate::pixels::{self, Pixels};
+use crate::png::Png;
+use crate::Result;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    /// parse error of the written file
+    Unparseable(String),
+    /// chunk type of the payload that did not round-trip
+    PayloadMissing(String),
+    ImageDataChanged,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Unparseable(error) => {
+                write!(f, "written file does not parse: {}", error)
+            }
+            VerifyError::PayloadMissing(chunk_type) => {
+                write!(f, "written file does not decode the {} payload", chunk_type)
+            }
+            VerifyError::ImageDataChanged => {
+                write!(f, "image data of the written file differs from the input")
+            }
+        }
+    }
+}
+
+impl error::Error for VerifyError {}
+
+/// What a written file must decode back to.
+#[derive(Debug, Clone, Copy)]
+pub enum Payload<'a> {
+    /// chunk type and data before error correction
+    Chunk(&'a str, &'a [u8]),
+    /// data before error correction hidden in the pixels, with the key
+    /// that selects the pixels
+    Pixels(&'a [u8], Option<&'a str>),
+}
+
+/// Chunks that make up the displayed image: every critical chunk
+/// and the animation chunks.
+fn image_chunks(png: &Png) -> Vec<&Chunk> {
+    png.chunks
+        .iter()
+        .filter(|chunk| {
+            chunk.chunk_type().is_critical()
+                || apng::is_apng_chunk_type(&chunk.chunk_type().to_string())
+        })
+        .collect()
+}
+
+/// Checks the bytes of a written png.
+///
+/// They must parse, decode back to payload and, when original is set,
+/// carry the exact image data of original. Pixel payloads change the
+/// image data on purpose: the decoded images may only differ in the
+/// least significant bits of their color samples.
+pub fn verify_written(
+    bytes: &[u8],
+    original: Option<&Png>,
+    payload: Option<Payload>,
+    limits: &Limits,
+) -> Result<()> {
+    let png = Png::try_from_limited(bytes, limits)
+        .map_err(|error| VerifyError::Unparseable(error.to_string()))?;
+
+    let round_trips = match payload {
+        None => true,
+        Some(Payload::Chunk(chunk_type, data)) => {
+            png.chunk_by_type(chunk_type).iter().any(|chunk| {
+                fec::decode_if_encoded(chunk.data())
+                    .is_ok_and(|(decoded, _)| decoded == data)
+            })
+        }
+        Some(Payload::Pixels(data, key)) => Pixels::from_png(&png, limits)
+            .and_then(|pixels| pixels.extract(key))
+            .and_then(|extracted| fec::decode_if_encoded(&extracted))
+            .is_ok_and(|(decoded, _)| decoded == data),
+    };
+
+    if !round_trips {
+        let chunk_type = match payload {
+            Some(Payload::Chunk(chunk_type, _)) => chunk_type,
+            _ => pixels::PIXEL_CHUNK_TYPE,
+        };
+        return Err(Box::new(VerifyError::PayloadMissing(
+            chunk_type.to_string(),
+        )));
+    }
+
+    if let Some(original) = original {
+        let pixels = matches!(payload, Some(Payload::Pixels(..)));
+        let image_chunks = |png| {
+            image_chunks(png)
+                .into_iter()
+                .filter(|chunk| {
+                    !pixels
+                        || chunk.chunk_type().to_string() != pixels::PIXEL_CHUNK_TYPE
+                })
+                .collect::<Vec<&Chunk>>()
+        };
+
+        if image_chunks(original) != image_chunks(&png) {
+            return Err(Box::new(VerifyError::ImageDataChanged));
+        }
+
+        if pixels {
+            let unchanged = Pixels::from_png(original, limits)
+                .and_then(|before| {
+                    Pixels::from_png(&png, limits)
+                        .map(|after| before.differs_only_in_lsb(&after))
+                })
+                .unwrap_or(false);
+
+            if !unchanged {
+                return Err(Box::new(VerifyError::ImageDataChanged));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk_of("IHDR", b"header"),
+            chunk_of("IDAT", b"pixels"),
+            chunk_of("IEND", b""),
+        ])
+    }
+
+    #[test]
+    fn test_verify_payload_and_image_data() {
+        let limits = Limits::default();
+        let original = testing_png();
+        let mut png = testing_png();
+        png.append_chunk(chunk_of("ruSt", &fec::encode(b"hello", 4).unwrap()));
+        let bytes = png.as_bytes();
+
+        assert!(verify_written(
+            &bytes,
+            Some(&original),
+            Some(Payload::Chunk("ruSt", b"hello")),
+            &limits,
+        )
+        .is_ok());
+
+        let error = verify_written(
+            &bytes,
+            None,
+            Some(Payload::Chunk("ruSt", b"bye")),
+            &limits,
+        )
+        .unwrap_err()
+        .downcast::<VerifyError>()
+        .unwrap();
+        assert_eq!(*error, VerifyError::PayloadMissing("ruSt".to_string()));
+
+        png.chunks[1] = chunk_of("IDAT", b"changed");
+        let error = verify_written(&png.as_bytes(), Some(&original), None, &limits)
+            .unwrap_err()
+            .downcast::<VerifyError>()
+            .unwrap();
+        assert_eq!(*error, VerifyError::ImageDataChanged);
+    }
+
+    #[test]
+    fn test_verify_unparseable() {
+        let mut bytes = testing_png().as_bytes();
+        bytes.truncate(bytes.len() - 1);
+
+        assert!(verify_written(&bytes, None, None, &Limits::default())
+            .unwrap_err()
+            .to_string()
+            .starts_with("written file does not parse"));
+    }
+}
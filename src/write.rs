@@ -0,0 +1,235 @@
+use std::fs::{self, File, FileTimes, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// How files are replaced on disk.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// keep the replaced file next to the new one with this suffix
+    pub backup_suffix: Option<String>,
+    /// show planned changes instead of writing
+    pub dry_run: bool,
+    /// read written pngs back and check them
+    pub verify: bool,
+    /// write even when it destroys C2PA content credentials
+    pub force: bool,
+}
+
+/// Hidden sibling of path the new contents are written to first.
+/// The rename only stays atomic inside a single directory.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    PathBuf::from(backup)
+}
+
+/// Gives file the permissions and access/modification times of metadata.
+fn copy_metadata(file: &File, metadata: &fs::Metadata) -> io::Result<()> {
+    file.set_permissions(metadata.permissions())?;
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    file.set_times(times)
+}
+
+fn write_temp(
+    temp: &Path,
+    contents: &[u8],
+    replaced: Option<&fs::Metadata>,
+) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp)?;
+
+    file.write_all(contents)?;
+
+    if let Some(metadata) = replaced {
+        copy_metadata(&file, metadata)?;
+    }
+
+    file.sync_all()
+}
+
+/// Replaces the file at path with contents.
+///
+/// The contents go to a temp file in the same directory which is then
+/// renamed over path, so a crash never leaves a half written file. A
+/// replaced file hands its permissions and timestamps to the new one
+/// and is kept with options.backup_suffix appended, if set.
+///
+/// A symlink at path is written through: the file it points to is
+/// replaced and the link stays in place.
+pub fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    options: &WriteOptions,
+) -> io::Result<()> {
+    let path = &match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path)?,
+        _ => path.to_path_buf(),
+    };
+    let replaced = fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_file());
+    let temp = temp_path(path);
+
+    if let Err(error) = write_temp(&temp, contents, replaced.as_ref()) {
+        let _ = fs::remove_file(&temp);
+        return Err(error);
+    }
+
+    if let (Some(metadata), Some(suffix)) =
+        (replaced.as_ref(), &options.backup_suffix)
+    {
+        let backup = backup_path(path, suffix);
+        // an older backup may be read only, replace it instead of writing into it
+        let _ = fs::remove_file(&backup);
+        let result = fs::copy(path, &backup)
+            .and_then(|_| copy_metadata(&File::open(&backup)?, metadata));
+
+        if let Err(error) = result {
+            let _ = fs::remove_file(&temp);
+            return Err(error);
+        }
+    }
+
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+/// Directory of a test, removed with everything in it when dropped.
+#[cfg(test)]
+pub(crate) struct TestingDir(PathBuf);
+
+#[cfg(test)]
+impl std::ops::Deref for TestingDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TestingDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Empty directory below the system temp dir, unique to the test name
+/// and process so concurrent test runs never share files.
+#[cfg(test)]
+pub(crate) fn testing_dir(name: &str) -> TestingDir {
+    let dir =
+        std::env::temp_dir().join(format!("pngsneak_{}_{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    TestingDir(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_new_file() {
+        let dir = testing_dir("write_new");
+        let path = dir.join("a.png");
+
+        write_atomic(&path, b"new", &WriteOptions::default()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(entries(&dir), vec!["a.png"]);
+    }
+
+    #[test]
+    fn test_replace_keeps_metadata_and_backup() {
+        let dir = testing_dir("write_replace");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+        let mut permissions = file.metadata().unwrap().permissions();
+        permissions.set_readonly(true);
+        file.set_permissions(permissions).unwrap();
+        drop(file);
+
+        let options = WriteOptions {
+            backup_suffix: Some(".bak".to_string()),
+            ..WriteOptions::default()
+        };
+        write_atomic(&path, b"new", &options).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(dir.join("a.png.bak")).unwrap(), b"old");
+        assert_eq!(entries(&dir), vec!["a.png", "a.png.bak"]);
+
+        for path in [path, dir.join("a.png.bak")] {
+            let metadata = fs::metadata(&path).unwrap();
+            assert_eq!(metadata.modified().unwrap(), modified);
+            assert!(metadata.permissions().readonly());
+
+            let mut permissions = metadata.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(&path, permissions).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_failed_write_leaves_original() {
+        let dir = testing_dir("write_failed");
+        let path = dir.join("a.png");
+        fs::write(&path, b"old").unwrap();
+        // a directory in the way of the temp file makes the write fail
+        fs::create_dir(temp_path(&path)).unwrap();
+
+        assert!(write_atomic(&path, b"new", &WriteOptions::default()).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_through_symlink() {
+        let dir = testing_dir("write_symlink");
+        let target = dir.join("target.png");
+        let link = dir.join("link.png");
+        fs::write(&target, b"old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new", &WriteOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(entries(&dir), vec!["link.png", "target.png"]);
+    }
+}
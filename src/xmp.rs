@@ -0,0 +1,839 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_error::ChunkError;
+use crate::chunk_type::ChunkType;
+use crate::limits::{self, Limits};
+use crate::png::Png;
+use crate::transplant;
+use crate::Result;
+
+/// iTXt keyword XMP packets are stored under.
+/// https://github.com/adobe/XMP-Toolkit-SDK/blob/main/docs/XMPSpecificationPart3.pdf
+pub const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// Packet used when setting properties on a png without one.
+const EMPTY_PACKET: &str =
+    "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">
+  <rdf:Description rdf:about=\"\"/>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end=\"w\"?>";
+
+/// Namespaces declared automatically when a new property needs them.
+const NAMESPACES: [(&str, &str); 8] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+    (
+        "Iptc4xmpCore",
+        "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/",
+    ),
+];
+
+/// Properties whose value is an array, with the array type. Adding one
+/// stores a single item.
+const ARRAYS: [(&str, &str); 10] = [
+    ("dc:contributor", "rdf:Bag"),
+    ("dc:creator", "rdf:Seq"),
+    ("dc:description", "rdf:Alt"),
+    ("dc:language", "rdf:Bag"),
+    ("dc:publisher", "rdf:Bag"),
+    ("dc:rights", "rdf:Alt"),
+    ("dc:subject", "rdf:Bag"),
+    ("dc:title", "rdf:Alt"),
+    ("dc:type", "rdf:Bag"),
+    ("xmpRights:UsageTerms", "rdf:Alt"),
+];
+
+/// iTXt: UTF-8 text with a language tag, optionally compressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalText {
+    pub keyword: String,
+    pub compressed: bool,
+    pub language: String,
+    pub translated_keyword: String,
+    pub text: String,
+}
+
+impl InternationalText {
+    /// Parses an iTXt chunk, inflating compressed text up to the
+    /// max_image_data limit.
+    pub fn from_chunk(chunk: &Chunk, limits: &Limits) -> Result<Self> {
+        let invalid = || ChunkError("iTXt is malformed");
+        let data = chunk.data();
+
+        let mut fields = data.splitn(2, |&b| b == 0);
+        let keyword = fields.next().ok_or_else(invalid)?;
+        let rest = fields.next().ok_or_else(invalid)?;
+
+        let [flag, method, rest @ ..] = rest else {
+            return Err(Box::new(invalid()));
+        };
+
+        let mut fields = rest.splitn(3, |&b| b == 0);
+        let (language, translated_keyword, text) =
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(language), Some(translated), Some(text)) => {
+                    (language, translated, text)
+                }
+                _ => return Err(Box::new(invalid())),
+            };
+
+        let text = match (flag, method) {
+            (0, _) => text.to_vec(),
+            (1, 0) => limits::inflate(text, limits.max_image_data)?,
+            _ => return Err(Box::new(invalid())),
+        };
+
+        Ok(InternationalText {
+            keyword: keyword.iter().map(|&b| b as char).collect(),
+            compressed: *flag == 1,
+            language: String::from_utf8(language.to_vec())?,
+            translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+            text: String::from_utf8(text)?,
+        })
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let text = if self.compressed {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(self.text.as_bytes())?;
+            encoder.finish()?
+        } else {
+            self.text.as_bytes().to_vec()
+        };
+
+        let mut data = self.keyword.chars().map(|c| c as u8).collect::<Vec<u8>>();
+        data.extend([0, self.compressed as u8, 0]);
+        data.extend(self.language.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        data.extend(text);
+
+        Ok(Chunk::new(ChunkType::from_str("iTXt")?, data))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Open,
+    Close,
+    /// self-closing, e.g. <rdf:Description/>
+    Empty,
+    /// processing instructions, comments and doctypes
+    Other,
+    Text,
+}
+
+/// A tag or text node, start and end are byte offsets into the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token<'a> {
+    kind: TagKind,
+    name: &'a str,
+    start: usize,
+    end: usize,
+}
+
+/// Splits xml into tags and text, just enough to edit XMP packets.
+fn tokenize(xml: &str) -> Result<Vec<Token<'_>>> {
+    let unterminated = || ChunkError("XMP packet has an unterminated tag");
+    let mut tokens = vec![];
+    let mut position = 0;
+
+    while position < xml.len() {
+        let rest = &xml[position..];
+
+        let Some(open) = rest.find('<') else {
+            tokens.push(Token {
+                kind: TagKind::Text,
+                name: "",
+                start: position,
+                end: xml.len(),
+            });
+            break;
+        };
+
+        if open > 0 {
+            tokens.push(Token {
+                kind: TagKind::Text,
+                name: "",
+                start: position,
+                end: position + open,
+            });
+        }
+
+        let tag = &rest[open..];
+        let (kind, length) = if let Some(terminator) = ["<!--", "<![CDATA[", "<?"]
+            .iter()
+            .zip(["-->", "]]>", "?>"])
+            .find(|(start, _)| tag.starts_with(**start))
+            .map(|(_, end)| end)
+        {
+            let length =
+                tag.find(terminator).ok_or_else(unterminated)? + terminator.len();
+            let kind = if tag.starts_with("<![CDATA[") {
+                TagKind::Text
+            } else {
+                TagKind::Other
+            };
+            (kind, length)
+        } else {
+            // a > inside a quoted attribute value does not end the tag
+            let mut quote = None;
+            let length = tag
+                .char_indices()
+                .find(|&(_, c)| match quote {
+                    Some(q) if c == q => {
+                        quote = None;
+                        false
+                    }
+                    Some(_) => false,
+                    None if c == '"' || c == '\'' => {
+                        quote = Some(c);
+                        false
+                    }
+                    None => c == '>',
+                })
+                .ok_or_else(unterminated)?
+                .0
+                + 1;
+
+            let kind = if tag.starts_with("</") {
+                TagKind::Close
+            } else if tag.starts_with("<!") {
+                TagKind::Other
+            } else if tag[..length].ends_with("/>") {
+                TagKind::Empty
+            } else {
+                TagKind::Open
+            };
+            (kind, length)
+        };
+
+        let name = match kind {
+            TagKind::Open | TagKind::Close | TagKind::Empty => tag
+                .trim_start_matches(['<', '/'])
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or_default(),
+            _ => "",
+        };
+
+        tokens.push(Token {
+            kind,
+            name,
+            start: position + open,
+            end: position + open + length,
+        });
+        position += open + length;
+    }
+
+    Ok(tokens)
+}
+
+/// Index of the Close token matching the Open token at open.
+fn matching_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+
+    for (idx, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TagKind::Open => depth += 1,
+            TagKind::Close => {
+                depth -= 1;
+                if depth == 0 {
+                    return (token.name == tokens[open].name).then_some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Attributes of a tag as (name, value start, value end).
+fn attributes(xml: &str, token: &Token) -> Result<Vec<(String, usize, usize)>> {
+    let malformed = || ChunkError("XMP packet has a malformed attribute");
+    let offset = token.start + 1 + token.name.len();
+    let tag = xml[offset..token.end].trim_end_matches('>');
+    let tag = tag.strip_suffix('/').unwrap_or(tag);
+    let mut attributes = vec![];
+    let mut chars = tag.char_indices().peekable();
+
+    loop {
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+        let Some(&(name_start, _)) = chars.peek() else {
+            break;
+        };
+        while chars
+            .next_if(|(_, c)| !c.is_whitespace() && *c != '=')
+            .is_some()
+        {}
+        let name_end = chars.peek().map_or(tag.len(), |&(idx, _)| idx);
+
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        chars.next_if(|(_, c)| *c == '=').ok_or_else(malformed)?;
+        while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+        let (quote_at, quote) = chars
+            .next_if(|(_, c)| *c == '"' || *c == '\'')
+            .ok_or_else(malformed)?;
+        let (value_end, _) =
+            chars.find(|(_, c)| *c == quote).ok_or_else(malformed)?;
+
+        attributes.push((
+            tag[name_start..name_end].to_string(),
+            offset + quote_at + 1,
+            offset + value_end,
+        ));
+    }
+
+    Ok(attributes)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Errors unless packet is well-formed xml holding an RDF graph.
+pub fn check_packet(packet: &str) -> Result<()> {
+    let tokens = tokenize(packet)?;
+    let mut open = vec![];
+
+    for token in tokens.iter() {
+        match token.kind {
+            TagKind::Open => open.push(token.name),
+            TagKind::Close if open.pop() != Some(token.name) => {
+                return Err(Box::new(ChunkError("XMP packet has mismatched tags")));
+            }
+            _ => {}
+        }
+    }
+
+    if !open.is_empty() {
+        return Err(Box::new(ChunkError("XMP packet has unclosed tags")));
+    }
+
+    if !tokens.iter().any(|token| token.name == "rdf:RDF") {
+        return Err(Box::new(ChunkError("XMP packet has no rdf:RDF element")));
+    }
+
+    Ok(())
+}
+
+/// Value of a property like dc:creator or xmp:CreatorTool, written
+/// either as an rdf:Description attribute or as an element. Array
+/// items are joined with "; ".
+pub fn get_property(packet: &str, property: &str) -> Result<Option<String>> {
+    let tokens = tokenize(packet)?;
+
+    for (idx, token) in tokens.iter().enumerate() {
+        let is_tag = matches!(token.kind, TagKind::Open | TagKind::Empty);
+
+        if is_tag && token.name == "rdf:Description" {
+            let value = attributes(packet, token)?
+                .into_iter()
+                .find(|(name, _, _)| name == property);
+
+            if let Some((_, start, end)) = value {
+                return Ok(Some(unescape(&packet[start..end])));
+            }
+        }
+
+        if token.name != property {
+            continue;
+        }
+
+        match token.kind {
+            TagKind::Empty => {
+                let resource = attributes(packet, token)?
+                    .into_iter()
+                    .find(|(name, _, _)| name == "rdf:resource")
+                    .map(|(_, start, end)| unescape(&packet[start..end]));
+                return Ok(Some(resource.unwrap_or_default()));
+            }
+            TagKind::Open => {
+                let close = matching_close(&tokens, idx)
+                    .ok_or(ChunkError("XMP packet has mismatched tags"))?;
+                let inner = &tokens[idx + 1..close];
+
+                let items = inner
+                    .windows(2)
+                    .filter(|pair| {
+                        pair[0].kind == TagKind::Open
+                            && pair[0].name == "rdf:li"
+                            && pair[1].kind == TagKind::Text
+                    })
+                    .map(|pair| unescape(&packet[pair[1].start..pair[1].end]))
+                    .collect::<Vec<String>>();
+
+                if !items.is_empty() {
+                    return Ok(Some(items.join("; ")));
+                }
+
+                let text = &packet[tokens[idx].end..tokens[close].start];
+                return Ok(Some(unescape(text.trim())));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns packet with property set to value.
+///
+/// An existing attribute or element is overwritten. Language
+/// alternatives (rdf:Alt) only get their x-default item replaced, the
+/// translations are kept. Bags and sequences are replaced by a single
+/// item holding value. New properties become elements of the first
+/// rdf:Description, declaring well known namespaces as needed.
+pub fn set_property(packet: &str, property: &str, value: &str) -> Result<String> {
+    let tokens = tokenize(packet)?;
+    let value = escape(value);
+    let splice = |start: usize, end: usize, with: &str| {
+        format!("{}{}{}", &packet[..start], with, &packet[end..])
+    };
+
+    for (idx, token) in tokens.iter().enumerate() {
+        let is_tag = matches!(token.kind, TagKind::Open | TagKind::Empty);
+
+        if is_tag && token.name == "rdf:Description" {
+            let existing = attributes(packet, token)?
+                .into_iter()
+                .find(|(name, _, _)| name == property);
+
+            if let Some((_, start, end)) = existing {
+                return Ok(splice(start, end, &value));
+            }
+        }
+
+        if token.name != property {
+            continue;
+        }
+
+        match token.kind {
+            TagKind::Empty => {
+                let element = format!("<{0}>{1}</{0}>", property, value);
+                return Ok(splice(token.start, token.end, &element));
+            }
+            TagKind::Open => {
+                let close = matching_close(&tokens, idx)
+                    .ok_or(ChunkError("XMP packet has mismatched tags"))?;
+                let items = (idx + 1..close)
+                    .filter(|&item| {
+                        tokens[item].kind == TagKind::Open
+                            && tokens[item].name == "rdf:li"
+                    })
+                    .collect::<Vec<usize>>();
+
+                let (Some(&first), Some(&last)) = (items.first(), items.last())
+                else {
+                    return Ok(splice(token.end, tokens[close].start, &value));
+                };
+
+                let array = tokens[idx + 1..close]
+                    .iter()
+                    .find(|token| token.kind == TagKind::Open)
+                    .map(|token| token.name);
+
+                if array == Some("rdf:Alt") {
+                    return set_default_item(packet, &tokens, &items, &value);
+                }
+
+                // keeps the attributes of the first item
+                let last_close = matching_close(&tokens, last)
+                    .ok_or(ChunkError("XMP packet has mismatched tags"))?;
+                let item = format!(
+                    "{}{}</rdf:li>",
+                    &packet[tokens[first].start..tokens[first].end],
+                    value
+                );
+                return Ok(splice(
+                    tokens[first].start,
+                    tokens[last_close].end,
+                    &item,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    add_property(packet, &tokens, property, &value)
+}
+
+/// Replaces the x-default item of a language alternative, adding one
+/// in front of the translations if there is none.
+fn set_default_item(
+    packet: &str,
+    tokens: &[Token],
+    items: &[usize],
+    value: &str,
+) -> Result<String> {
+    let splice = |start: usize, end: usize, with: &str| {
+        format!("{}{}{}", &packet[..start], with, &packet[end..])
+    };
+
+    for &item in items {
+        let is_default =
+            attributes(packet, &tokens[item])?
+                .iter()
+                .any(|(name, start, end)| {
+                    name == "xml:lang" && &packet[*start..*end] == "x-default"
+                });
+
+        if is_default {
+            let close = matching_close(tokens, item)
+                .ok_or(ChunkError("XMP packet has mismatched tags"))?;
+            return Ok(splice(tokens[item].end, tokens[close].start, value));
+        }
+    }
+
+    let first = tokens[items[0]].start;
+    let item = format!("<rdf:li xml:lang=\"x-default\">{}</rdf:li>", value);
+    Ok(splice(first, first, &item))
+}
+
+fn add_property(
+    packet: &str,
+    tokens: &[Token],
+    property: &str,
+    value: &str,
+) -> Result<String> {
+    let (prefix, _) = property
+        .split_once(':')
+        .ok_or(ChunkError("XMP property must look like prefix:name"))?;
+
+    let (idx, description) = tokens
+        .iter()
+        .enumerate()
+        .find(|(_, token)| {
+            matches!(token.kind, TagKind::Open | TagKind::Empty)
+                && token.name == "rdf:Description"
+        })
+        .ok_or(ChunkError("XMP packet has no rdf:Description"))?;
+
+    let mut declaration = String::new();
+
+    if !packet.contains(&format!("xmlns:{}=", prefix)) {
+        let (_, uri) = NAMESPACES
+            .iter()
+            .find(|(known, _)| *known == prefix)
+            .ok_or(ChunkError(
+                "Unknown XMP namespace prefix, declare it in the packet first",
+            ))?;
+        declaration = format!(" xmlns:{}=\"{}\"", prefix, uri);
+    }
+
+    let element = match ARRAYS.iter().find(|(name, _)| *name == property) {
+        Some((_, "rdf:Alt")) => format!(
+            "<{0}><rdf:Alt><rdf:li xml:lang=\"x-default\">{1}</rdf:li></rdf:Alt></{0}>",
+            property, value
+        ),
+        Some((_, array)) => format!(
+            "<{0}><{1}><rdf:li>{2}</rdf:li></{1}></{0}>",
+            property, array, value
+        ),
+        None => format!("<{0}>{1}</{0}>", property, value),
+    };
+
+    let start_tag = &packet[description.start..description.end];
+    let mut edited = packet[..description.start].to_string();
+
+    if description.kind == TagKind::Empty {
+        let attributes = start_tag.trim_end_matches("/>").trim_end();
+        edited.push_str(&format!(
+            "{}{}>{}</rdf:Description>",
+            attributes, declaration, element
+        ));
+        edited.push_str(&packet[description.end..]);
+    } else {
+        let close = matching_close(tokens, idx)
+            .ok_or(ChunkError("XMP packet has mismatched tags"))?;
+        let attributes = start_tag.trim_end_matches('>');
+        edited.push_str(&format!("{}{}>", attributes, declaration));
+        edited.push_str(&packet[description.end..tokens[close].start]);
+        edited.push_str(&element);
+        edited.push_str(&packet[tokens[close].start..]);
+    }
+
+    Ok(edited)
+}
+
+/// Packet with one tag per line, indented by nesting. Elements holding
+/// only text stay on a single line.
+pub fn pretty_print(packet: &str) -> Result<String> {
+    let tokens = tokenize(packet)?
+        .into_iter()
+        .filter(|token| {
+            token.kind != TagKind::Text
+                || !packet[token.start..token.end].trim().is_empty()
+        })
+        .collect::<Vec<Token>>();
+
+    let text = |token: &Token| packet[token.start..token.end].trim().to_string();
+    let mut lines = vec![];
+    let mut depth: usize = 0;
+    let mut idx = 0;
+
+    while idx < tokens.len() {
+        let token = &tokens[idx];
+        let indent = " ".repeat(depth * 2);
+
+        match (token.kind, tokens.get(idx + 1), tokens.get(idx + 2)) {
+            (TagKind::Open, Some(inner), Some(close))
+                if inner.kind == TagKind::Text && close.kind == TagKind::Close =>
+            {
+                lines.push(format!(
+                    "{}{}{}{}",
+                    indent,
+                    text(token),
+                    text(inner),
+                    text(close)
+                ));
+                idx += 3;
+                continue;
+            }
+            (TagKind::Open, _, _) => {
+                lines.push(format!("{}{}", indent, text(token)));
+                depth += 1;
+            }
+            (TagKind::Close, _, _) => {
+                depth = depth.saturating_sub(1);
+                lines.push(format!("{}{}", " ".repeat(depth * 2), text(token)));
+            }
+            _ => lines.push(format!("{}{}", indent, text(token))),
+        }
+
+        idx += 1;
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn is_packet(chunk: &Chunk) -> bool {
+    chunk.chunk_type().to_string() == "iTXt"
+        && chunk.data().split(|&b| b == 0).next() == Some(XMP_KEYWORD.as_bytes())
+}
+
+/// Index and contents of the iTXt chunk holding the XMP packet.
+pub fn find_packet(
+    png: &Png,
+    limits: &Limits,
+) -> Option<(usize, InternationalText)> {
+    let idx = png.chunks.iter().position(is_packet)?;
+    let text = InternationalText::from_chunk(&png.chunks[idx], limits).ok()?;
+    Some((idx, text))
+}
+
+/// Stores packet in an uncompressed iTXt chunk, as Adobe tools expect.
+///
+/// An existing packet is replaced in place, a new one goes before
+/// the image data so readers find it without scanning the whole file.
+pub fn set_packet(png: &mut Png, packet: &str) -> Result<usize> {
+    check_packet(packet)?;
+
+    let chunk = InternationalText {
+        keyword: XMP_KEYWORD.to_string(),
+        compressed: false,
+        language: String::new(),
+        translated_keyword: String::new(),
+        text: packet.to_string(),
+    }
+    .to_chunk()?;
+
+    let idx = match png.chunks.iter().position(is_packet) {
+        Some(idx) => {
+            png.chunks[idx] = chunk;
+            idx
+        }
+        None => transplant::insert_before_image_data(png, chunk),
+    };
+
+    // a second packet would be ambiguous
+    let mut position = 0;
+    png.chunks.retain(|chunk| {
+        position += 1;
+        position - 1 <= idx || !is_packet(chunk)
+    });
+
+    Ok(idx)
+}
+
+/// Removes every XMP packet and returns how many there were.
+pub fn remove_packets(png: &mut Png) -> usize {
+    let count = png.chunks.len();
+    png.chunks.retain(|chunk| !is_packet(chunk));
+    count - png.chunks.len()
+}
+
+/// Packet of a png without one, to set properties on.
+pub fn empty_packet() -> String {
+    EMPTY_PACKET.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::chunk_of;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmp:CreatorTool="Photoshop &amp; co">
+<dc:title><rdf:Alt><rdf:li xml:lang="x-default">Sunset</rdf:li><rdf:li xml:lang="de">Abend</rdf:li></rdf:Alt></dc:title>
+<xmp:Rating>3</xmp:Rating>
+</rdf:Description></rdf:RDF></x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    fn get(packet: &str, property: &str) -> Option<String> {
+        get_property(packet, property).unwrap()
+    }
+
+    #[test]
+    fn test_get_property() {
+        assert_eq!(get(PACKET, "xmp:CreatorTool").unwrap(), "Photoshop & co");
+        assert_eq!(get(PACKET, "dc:title").unwrap(), "Sunset; Abend");
+        assert_eq!(get(PACKET, "xmp:Rating").unwrap(), "3");
+        assert_eq!(get(PACKET, "dc:creator"), None);
+    }
+
+    #[test]
+    fn test_set_property() {
+        let packet = set_property(PACKET, "xmp:CreatorTool", "pngsneak").unwrap();
+        let packet = set_property(&packet, "dc:title", "Dawn <1>").unwrap();
+        let packet = set_property(&packet, "xmp:Rating", "5").unwrap();
+        let packet = set_property(&packet, "dc:creator", "Jay").unwrap();
+        let packet = set_property(&packet, "photoshop:City", "Austin").unwrap();
+
+        check_packet(&packet).unwrap();
+        assert_eq!(get(&packet, "xmp:CreatorTool").unwrap(), "pngsneak");
+        // translations survive, only x-default changes
+        assert_eq!(get(&packet, "dc:title").unwrap(), "Dawn <1>; Abend");
+        assert!(packet.contains(
+            "<rdf:li xml:lang=\"x-default\">Dawn &lt;1&gt;</rdf:li>\
+             <rdf:li xml:lang=\"de\">Abend</rdf:li></rdf:Alt>"
+        ));
+        assert_eq!(get(&packet, "xmp:Rating").unwrap(), "5");
+        assert!(packet.contains(
+            "<dc:creator><rdf:Seq><rdf:li>Jay</rdf:li></rdf:Seq></dc:creator>"
+        ));
+        assert!(packet
+            .contains("xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\""));
+        assert_eq!(get(&packet, "photoshop:City").unwrap(), "Austin");
+
+        assert!(set_property(PACKET, "foo:bar", "x").is_err());
+    }
+
+    #[test]
+    fn test_set_array_items() {
+        let bag = PACKET.replace(
+            "<xmp:Rating>",
+            "<dc:subject><rdf:Bag><rdf:li>sun</rdf:li><rdf:li>sea</rdf:li>\
+             </rdf:Bag></dc:subject><xmp:Rating>",
+        );
+
+        // bags and sequences are overwritten with a single item
+        let packet = set_property(&bag, "dc:subject", "sky").unwrap();
+        assert_eq!(get(&packet, "dc:subject").unwrap(), "sky");
+        assert!(packet.contains("<rdf:Bag><rdf:li>sky</rdf:li></rdf:Bag>"));
+
+        let translated =
+            PACKET.replace("<rdf:li xml:lang=\"x-default\">Sunset</rdf:li>", "");
+        let packet = set_property(&translated, "dc:title", "Dusk").unwrap();
+        assert!(packet.contains(
+            "<rdf:Alt><rdf:li xml:lang=\"x-default\">Dusk</rdf:li>\
+             <rdf:li xml:lang=\"de\">Abend</rdf:li>"
+        ));
+    }
+
+    #[test]
+    fn test_multibyte_attributes() {
+        let packet = PACKET.replace("rdf:about=\"\"", "rdf:about = 'éxé' a:b=\"ü\"");
+
+        assert_eq!(get(&packet, "a:b").unwrap(), "ü");
+        let packet = set_property(&packet, "a:b", "ö").unwrap();
+        assert_eq!(get(&packet, "a:b").unwrap(), "ö");
+
+        let broken = PACKET.replace("rdf:about=\"\"", "a=éxé");
+        assert!(get_property(&broken, "xmp:Rating").is_err());
+        assert!(set_property(&broken, "xmp:Rating", "1").is_err());
+    }
+
+    #[test]
+    fn test_set_on_empty_packet() {
+        let packet = set_property(&empty_packet(), "xmp:Label", "Red").unwrap();
+
+        check_packet(&packet).unwrap();
+        assert_eq!(get(&packet, "xmp:Label").unwrap(), "Red");
+    }
+
+    #[test]
+    fn test_check_packet() {
+        assert!(check_packet(PACKET).is_ok());
+        assert!(check_packet("<x:xmpmeta><rdf:RDF></x:xmpmeta>").is_err());
+        assert!(check_packet("<a></a>").is_err());
+        assert!(check_packet("<rdf:RDF a=\"1>").is_err());
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let pretty = pretty_print(PACKET).unwrap();
+
+        assert!(pretty.contains("\n      <xmp:Rating>3</xmp:Rating>\n"));
+        assert!(pretty.contains(
+            "\n          <rdf:li xml:lang=\"x-default\">Sunset</rdf:li>\n"
+        ));
+    }
+
+    #[test]
+    fn test_packet_in_png() {
+        let mut png = Png::from_chunks(vec![
+            chunk_of("IHDR", &[]),
+            chunk_of("IDAT", &[]),
+            chunk_of("IEND", &[]),
+        ]);
+
+        assert_eq!(set_packet(&mut png, PACKET).unwrap(), 1);
+        assert_eq!(set_packet(&mut png, &empty_packet()).unwrap(), 1);
+        assert_eq!(png.chunks.len(), 4);
+
+        let limits = Limits::default();
+        let (_, text) = find_packet(&png, &limits).unwrap();
+        assert_eq!(text.text, empty_packet());
+        assert!(!text.compressed);
+        assert_eq!(
+            InternationalText::from_chunk(&text.to_chunk().unwrap(), &limits)
+                .unwrap(),
+            text
+        );
+
+        assert_eq!(remove_packets(&mut png), 1);
+        assert!(find_packet(&png, &limits).is_none());
+    }
+}